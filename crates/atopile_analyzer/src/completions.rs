@@ -0,0 +1,310 @@
+//! Suggestions for what to type at the cursor, based on what comes before it on its line.
+
+use std::{
+    collections::BTreeSet,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Result;
+use atopile_parser::{
+    parser::{BlockKind, Symbol},
+    AtopileSource, Position,
+};
+
+use crate::{
+    db::BlockDeclaration,
+    evaluator::{import_search_roots, resolve_import_path, ModuleRef},
+    units,
+    vfs::Vfs,
+    AtopileAnalyzer, AtopileSourceExt, Range,
+};
+
+/// A suggestion from a completion request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Completion {
+    pub label: String,
+    pub kind: CompletionKind,
+    /// More about the suggestion, e.g. the type of an instance or the file a block is declared
+    /// in.
+    pub detail: Option<String>,
+    /// The text the suggestion replaces, if it isn't just the word before the cursor.
+    pub range: Option<Range>,
+}
+
+/// What a completion suggests.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompletionKind {
+    Module,
+    Component,
+    Interface,
+    File,
+    Instance,
+    Signal,
+    Pin,
+    Attribute,
+    Unit,
+}
+
+impl From<&BlockKind> for CompletionKind {
+    fn from(kind: &BlockKind) -> Self {
+        match kind {
+            BlockKind::Module => CompletionKind::Module,
+            BlockKind::Component => CompletionKind::Component,
+            BlockKind::Interface => CompletionKind::Interface,
+        }
+    }
+}
+
+/// What the cursor is in the middle of typing.
+#[derive(Debug)]
+enum Context<'a> {
+    /// The path in the string of an import, typed as far as the given text.
+    ImportPath(&'a str),
+    /// The names imported from the file at the given import path.
+    ImportedNames(&'a str),
+    /// A type, after `new`, `->` or `from` in a block header.
+    Type,
+    /// A member of an instance, e.g. of `r1` after `r1.`, whose path starts at the given offset
+    /// into the line.
+    Member(Vec<&'a str>, usize),
+    /// The unit of a number other than a pin number, typed as far as the given text.
+    Unit(&'a str),
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Whether `text` ends with the keyword `keyword`, rather than a name ending with it.
+fn ends_with_keyword(text: &str, keyword: &str) -> bool {
+    text.strip_suffix(keyword)
+        .is_some_and(|rest| !rest.ends_with(is_name_char))
+}
+
+impl<'a> Context<'a> {
+    /// The context of the cursor at the end of `line`, the text of its line before it.
+    fn parse(line: &'a str) -> Option<Self> {
+        // Imports: `from "file.ato" import Name` and `import Name from "file.ato"`.
+        let trimmed = line.trim_start();
+        let import = trimmed.strip_prefix("from \"").or_else(|| {
+            let rest = trimmed.strip_prefix("import ")?;
+            let (_, path) = rest.split_once("from \"")?;
+            Some(path)
+        });
+        if let Some(rest) = import {
+            return match rest.split_once('"') {
+                None => Some(Context::ImportPath(rest)),
+                Some((path, rest)) => rest
+                    .trim_start()
+                    .strip_prefix("import")
+                    .filter(|names| names.starts_with(char::is_whitespace))
+                    .map(|_| Context::ImportedNames(path)),
+            };
+        }
+
+        let (before, word) = line.split_at(line.trim_end_matches(is_name_char).len());
+
+        let before_word = before.trim_end();
+        let header: Vec<_> = before_word.split_whitespace().collect();
+        let is_block_parent = matches!(
+            header.as_slice(),
+            ["module" | "component" | "interface", _, "from"]
+        );
+        if before_word.ends_with("->") || ends_with_keyword(before_word, "new") || is_block_parent {
+            return Some(Context::Type);
+        }
+
+        if let Some(port) = before.strip_suffix('.') {
+            let start = port.trim_end_matches(|c| is_name_char(c) || c == '.').len();
+            let parts: Vec<_> = port[start..].split('.').collect();
+            let is_port = parts
+                .first()
+                .is_some_and(|first| first.starts_with(|c: char| c.is_alphabetic() || c == '_'))
+                && parts.iter().all(|part| !part.is_empty());
+            if is_port {
+                return Some(Context::Member(parts, start));
+            }
+        }
+
+        // Pin numbers aren't quantities.
+        if word.starts_with(|c: char| c.is_ascii_digit()) && !ends_with_keyword(before_word, "pin")
+        {
+            return Some(Context::Unit(
+                word.trim_start_matches(|c: char| c.is_ascii_digit()),
+            ));
+        }
+
+        None
+    }
+}
+
+/// The index into `text` of the start of the line of `position`, and of the cursor at
+/// `position`, clamped to the end of the line.
+fn cursor_index(text: &str, position: Position) -> (usize, usize) {
+    let line_start = match position.line {
+        0 => 0,
+        line => text
+            .match_indices('\n')
+            .nth(line - 1)
+            .map_or(text.len(), |(i, _)| i + 1),
+    };
+    let line = &text[line_start..];
+    let line = &line[..line.find('\n').unwrap_or(line.len())];
+
+    let mut column = position.column.min(line.len());
+    while !line.is_char_boundary(column) {
+        column -= 1;
+    }
+    (line_start, line_start + column)
+}
+
+/// Suggest the block `name`, declared by `declaration` in the file at `path`.
+fn block_completion(name: &Symbol, declaration: &BlockDeclaration, path: &Path) -> Completion {
+    Completion {
+        label: name.to_string(),
+        kind: CompletionKind::from(&*declaration.stmt.kind),
+        detail: path
+            .file_name()
+            .map(|file_name| file_name.to_string_lossy().to_string()),
+        range: None,
+    }
+}
+
+impl<V: Vfs> AtopileAnalyzer<V> {
+    /// Suggest what to type at the given position: the blocks in scope after `new` and `->`,
+    /// the blocks a file declares after `from "file.ato" import`, files inside the string of an
+    /// import, the children and attributes of an instance after `.` as it was last evaluated,
+    /// and units after a number.
+    pub fn completions(&self, path: &Path, position: Position) -> Result<Vec<Completion>> {
+        let source = self.load_source(&path.to_path_buf())?;
+        let (line_start, index) = cursor_index(source.text(), position);
+        let line = &source.text()[line_start..index];
+
+        // The range of `text`, typed right before the cursor.
+        let cursor = Position {
+            line: position.line,
+            column: index - line_start,
+        };
+        let typed = |text: &str| Range {
+            start: Position {
+                line: cursor.line,
+                column: cursor.column.saturating_sub(text.len()),
+            },
+            end: cursor,
+        };
+
+        let completions = match Context::parse(line) {
+            Some(Context::ImportPath(import_path)) => {
+                self.file_completions(&source, typed(import_path))
+            }
+            Some(Context::ImportedNames(import_path)) => {
+                self.imported_name_completions(&source, import_path)
+            }
+            Some(Context::Type) => self.type_completions(&source),
+            Some(Context::Member(parts, offset)) => match source.block_at(line_start + offset) {
+                Some(block) => self
+                    .evaluator
+                    .state()
+                    .member_completions(&ModuleRef::new(source.path(), &block.name), &parts),
+                None => vec![],
+            },
+            Some(Context::Unit(unit)) => units::units()
+                .into_iter()
+                .map(|(label, dimension)| Completion {
+                    label,
+                    kind: CompletionKind::Unit,
+                    detail: dimension.name().map(str::to_string),
+                    range: Some(typed(unit)),
+                })
+                .collect(),
+            None => vec![],
+        };
+        Ok(completions)
+    }
+
+    /// The files that can be imported from `source`, relative to each of the places imports are
+    /// looked up in.
+    fn file_completions(&self, source: &AtopileSource, range: Range) -> Vec<Completion> {
+        let db = self.evaluator.db();
+        let path = source.path();
+
+        // Each directory to search, with the prefix its files are imported with.
+        let mut roots: Vec<(String, PathBuf)> = vec![];
+        if let Some(dir) = path.parent() {
+            roots.push((String::new(), dir.to_path_buf()));
+        }
+        if let Some(project) = db.project(path) {
+            for dependency in &project.config().dependencies {
+                roots.push((
+                    format!("{}/", dependency.name),
+                    project.package_root(dependency),
+                ));
+            }
+        }
+        roots.extend(
+            import_search_roots(db, path)
+                .into_iter()
+                .map(|root| (String::new(), root)),
+        );
+
+        let mut labels = BTreeSet::new();
+        for (prefix, root) in roots {
+            for file in db.find_sources(&root) {
+                let Ok(relative) = file.strip_prefix(&root) else {
+                    continue;
+                };
+                if file != path {
+                    let relative: Vec<_> = relative
+                        .components()
+                        .map(|component| component.as_os_str().to_string_lossy())
+                        .collect();
+                    labels.insert(format!("{}{}", prefix, relative.join("/")));
+                }
+            }
+        }
+
+        labels
+            .into_iter()
+            .map(|label| Completion {
+                label,
+                kind: CompletionKind::File,
+                detail: None,
+                range: Some(range),
+            })
+            .collect()
+    }
+
+    /// The blocks declared by the file `import_path` refers to from `source`.
+    fn imported_name_completions(
+        &self,
+        source: &AtopileSource,
+        import_path: &str,
+    ) -> Vec<Completion> {
+        let db = self.evaluator.db();
+        let Some(path) = resolve_import_path(db, source.path(), Path::new(import_path)) else {
+            return vec![];
+        };
+        db.block_declarations(&path)
+            .blocks
+            .iter()
+            .map(|declaration| block_completion(&declaration.name, declaration, &path))
+            .collect()
+    }
+
+    /// The blocks that can be used as a type in `source`: the ones it declares and imports.
+    fn type_completions(&self, source: &AtopileSource) -> Vec<Completion> {
+        let db = self.evaluator.db();
+        let scope = db.file_scope(source.path());
+        let mut completions: Vec<_> = scope
+            .names()
+            .filter_map(|name| {
+                let module = scope.resolve(name)?;
+                let declarations = db.block_declarations(module.source_path());
+                let declaration = declarations.get(module.module_name())?;
+                Some(block_completion(name, declaration, module.source_path()))
+            })
+            .collect();
+        completions.sort_by(|a, b| a.label.cmp(&b.label));
+        completions
+    }
+}
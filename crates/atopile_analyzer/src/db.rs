@@ -0,0 +1,709 @@
+//! The analysis database: the source files being analyzed and the queries derived from them.
+//!
+//! Every query result is memoized together with the revision of each file it read, and is
+//! recomputed on its next use once any of those files has changed. Evaluation and editor
+//! requests read through the same database, so they always see the same version of every file.
+//!
+//! The instances of modules and the diagnostics found while evaluating them aren't queries:
+//! the evaluator keeps them, and re-evaluates a file along with every file importing it when
+//! its revision changes. Only the diagnostics of parsing a file are memoized here.
+
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    io,
+    ops::Deref,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use atopile_parser::{
+    parser::{AttributeStmt, BlockStmt, Connectable, Expr, PinStmt, SignalStmt, Stmt, Symbol},
+    AtopileError, AtopileSource, Spanned,
+};
+use log::debug;
+
+use crate::{
+    diagnostics::{AnalyzerDiagnostic, SyntaxDiagnostic},
+    evaluator::{resolve_import_path, unresolved_import_path, ModuleRef},
+    project::{Dependency, Project, CONFIG_FILE_NAME},
+    vfs::{is_source_under, normalize, OsVfs, Vfs},
+    IntoLocation, Location,
+};
+
+/// Incremented every time a source is set or removed.
+pub type Revision = u64;
+
+#[derive(Debug, Clone)]
+struct Input {
+    source: Arc<AtopileSource>,
+    changed_at: Revision,
+}
+
+#[derive(Debug)]
+struct Memo<T> {
+    value: T,
+    /// The files read to compute `value` and their revisions at the time, or `None` if a file
+    /// couldn't be read.
+    inputs: Vec<(PathBuf, Option<Revision>)>,
+}
+
+#[derive(Debug)]
+pub struct Database<V: Vfs = OsVfs> {
+    vfs: V,
+    revision: Revision,
+    /// Sources set by the editor, which take precedence over `vfs`.
+    sources: HashMap<PathBuf, Input>,
+    /// Sources read from `vfs`, kept until they are set or removed.
+    loaded: RefCell<HashMap<PathBuf, Input>>,
+    block_declarations: RefCell<HashMap<PathBuf, Memo<Arc<BlockDeclarations>>>>,
+    file_scopes: RefCell<HashMap<PathBuf, Memo<Arc<FileScope>>>>,
+    syntax_diagnostics: RefCell<HashMap<PathBuf, Memo<Arc<Vec<AnalyzerDiagnostic>>>>>,
+    /// The project rooted at each directory looked at, or `None` if it has no `ato.yaml`.
+    /// `ato.yaml` isn't a source, so these are kept until [`Database::reload_config`] is told
+    /// it changed.
+    projects: RefCell<HashMap<PathBuf, Option<Arc<Project>>>>,
+}
+
+impl Default for Database {
+    fn default() -> Self {
+        Self::new(OsVfs)
+    }
+}
+
+impl<V: Vfs> Database<V> {
+    pub fn new(vfs: V) -> Self {
+        Self {
+            vfs,
+            revision: 0,
+            sources: HashMap::new(),
+            loaded: RefCell::default(),
+            block_declarations: RefCell::default(),
+            file_scopes: RefCell::default(),
+            syntax_diagnostics: RefCell::default(),
+            projects: RefCell::default(),
+        }
+    }
+
+    /// The file system that sources which haven't been set are read from.
+    pub fn vfs(&self) -> &V {
+        &self.vfs
+    }
+
+    pub fn revision(&self) -> Revision {
+        self.revision
+    }
+
+    /// Set the source of the file at `path`. Returns whether it changed.
+    pub fn set_source(&mut self, path: &Path, source: Arc<AtopileSource>) -> bool {
+        if self
+            .sources
+            .get(path)
+            .is_some_and(|input| input.source.text() == source.text())
+        {
+            return false;
+        }
+
+        self.revision += 1;
+        self.loaded.get_mut().remove(path);
+        self.sources.insert(
+            path.to_path_buf(),
+            Input {
+                source,
+                changed_at: self.revision,
+            },
+        );
+        true
+    }
+
+    /// Forget the source of the file at `path`. It is read from `vfs` again if needed.
+    pub fn remove_source(&mut self, path: &Path) {
+        self.revision += 1;
+        self.sources.remove(path);
+        self.loaded.get_mut().remove(path);
+    }
+
+    /// Forget the `ato.yaml` at `path`, which has been created, changed or deleted, so that it
+    /// is read again. The scopes of files depend on the dependencies it declares, so they are
+    /// recomputed too.
+    pub fn reload_config(&mut self, path: &Path) {
+        let Some(root) = path.parent() else {
+            return;
+        };
+        self.revision += 1;
+        self.projects.get_mut().remove(root);
+        self.file_scopes.get_mut().clear();
+    }
+
+    /// The paths of every source in the database, whether set or read from `vfs`.
+    pub fn paths(&self) -> Vec<PathBuf> {
+        let mut paths: Vec<_> = self
+            .sources
+            .keys()
+            .chain(self.loaded.borrow().keys())
+            .cloned()
+            .collect();
+        paths.sort();
+        paths.dedup();
+        paths
+    }
+
+    /// The parsed source of the file at `path`, read from `vfs` if it hasn't been set.
+    pub fn source(&self, path: &Path) -> io::Result<Arc<AtopileSource>> {
+        if let Some(input) = self.input(path) {
+            return Ok(input.source);
+        }
+
+        debug!("loading source from vfs: {:?}", path);
+        let content = self.vfs.read_to_string(path)?;
+        let source = Arc::new(AtopileSource::new(content, path.to_path_buf()));
+        self.loaded.borrow_mut().insert(
+            path.to_path_buf(),
+            Input {
+                source: source.clone(),
+                changed_at: self.revision,
+            },
+        );
+        Ok(source)
+    }
+
+    fn input(&self, path: &Path) -> Option<Input> {
+        self.sources
+            .get(path)
+            .or(self.loaded.borrow().get(path))
+            .cloned()
+    }
+
+    fn changed_at(&self, path: &Path) -> Option<Revision> {
+        self.input(path).map(|input| input.changed_at)
+    }
+
+    fn is_fresh<T>(&self, memo: &Memo<T>) -> bool {
+        memo.inputs
+            .iter()
+            .all(|(path, revision)| self.changed_at(path) == *revision)
+    }
+
+    /// Read `path` on behalf of a query, recording the revision that was read.
+    fn read(
+        &self,
+        path: &Path,
+        inputs: &mut Vec<(PathBuf, Option<Revision>)>,
+    ) -> Option<Arc<AtopileSource>> {
+        let source = self.source(path).ok();
+        inputs.push((path.to_path_buf(), self.changed_at(path)));
+        source
+    }
+
+    /// The blocks declared at the top level of the file at `path`.
+    pub(crate) fn block_declarations(&self, path: &Path) -> Arc<BlockDeclarations> {
+        if let Some(memo) = self.block_declarations.borrow().get(path) {
+            if self.is_fresh(memo) {
+                return memo.value.clone();
+            }
+        }
+
+        let mut inputs = vec![];
+        let value = Arc::new(
+            self.read(path, &mut inputs)
+                .map(|source| BlockDeclarations::collect(&source))
+                .unwrap_or_default(),
+        );
+        self.block_declarations.borrow_mut().insert(
+            path.to_path_buf(),
+            Memo {
+                value: value.clone(),
+                inputs,
+            },
+        );
+        value
+    }
+
+    /// The names usable as types in the file at `path`: the blocks it declares and the ones it
+    /// imports.
+    pub(crate) fn file_scope(&self, path: &Path) -> Arc<FileScope> {
+        if let Some(memo) = self.file_scopes.borrow().get(path) {
+            if self.is_fresh(memo) {
+                return memo.value.clone();
+            }
+        }
+
+        let mut inputs = vec![];
+        let mut scope = FileScope::default();
+        if let Some(source) = self.read(path, &mut inputs) {
+            for stmt in source.ast() {
+                let (from_path, symbols) = match stmt.deref() {
+                    Stmt::Import(import) => (&import.from_path, import.imports.as_slice()),
+                    Stmt::DepImport(import) => {
+                        (&import.from_path, std::slice::from_ref(&import.name))
+                    }
+                    _ => continue,
+                };
+                let Some(import_path) =
+                    resolve_import_path(self, path, Path::new(from_path.deref()))
+                else {
+                    // Depend on the file the import would most likely refer to, so that
+                    // creating it brings the import into scope.
+                    self.read(
+                        &unresolved_import_path(path, Path::new(from_path.deref())),
+                        &mut inputs,
+                    );
+                    continue;
+                };
+
+                self.read(&import_path, &mut inputs);
+                let declarations = self.block_declarations(&import_path);
+                for symbol in symbols {
+                    if declarations.get(symbol).is_some() {
+                        scope.define(symbol, &ModuleRef::new(&import_path, symbol));
+                    }
+                }
+            }
+
+            for declaration in &self.block_declarations(path).blocks {
+                scope.define(&declaration.name, &ModuleRef::new(path, &declaration.name));
+            }
+        }
+
+        let value = Arc::new(scope);
+        self.file_scopes.borrow_mut().insert(
+            path.to_path_buf(),
+            Memo {
+                value: value.clone(),
+                inputs,
+            },
+        );
+        value
+    }
+
+    /// The errors from parsing the file at `path`.
+    pub(crate) fn syntax_diagnostics(&self, path: &Path) -> Arc<Vec<AnalyzerDiagnostic>> {
+        if let Some(memo) = self.syntax_diagnostics.borrow().get(path) {
+            if self.is_fresh(memo) {
+                return memo.value.clone();
+            }
+        }
+
+        let mut inputs = vec![];
+        let mut diagnostics = vec![];
+        if let Some(source) = self.read(path, &mut inputs) {
+            for error in source.errors() {
+                diagnostics.push(
+                    SyntaxDiagnostic {
+                        error: error.clone(),
+                        location: error.report().span.to_location(&source),
+                    }
+                    .into(),
+                );
+            }
+
+            // Statements the parser skipped over are reported the same way.
+            for (stmt, _) in source.traverse_all_stmts() {
+                if let Stmt::ParseError(report) = stmt.deref() {
+                    diagnostics.push(
+                        SyntaxDiagnostic {
+                            error: AtopileError::Parser(report.clone()),
+                            location: stmt.span().to_location(&source),
+                        }
+                        .into(),
+                    );
+                }
+            }
+        }
+
+        let value = Arc::new(diagnostics);
+        self.syntax_diagnostics.borrow_mut().insert(
+            path.to_path_buf(),
+            Memo {
+                value: value.clone(),
+                inputs,
+            },
+        );
+        value
+    }
+
+    /// The project the file at `path` belongs to: the closest ancestor directory with an
+    /// `ato.yaml`.
+    pub fn project(&self, path: &Path) -> Option<Arc<Project>> {
+        path.ancestors()
+            .skip(1)
+            .find_map(|dir| self.project_at(dir))
+    }
+
+    /// The project rooted at `root`, if it has an `ato.yaml`.
+    fn project_at(&self, root: &Path) -> Option<Arc<Project>> {
+        if let Some(project) = self.projects.borrow().get(root) {
+            return project.clone();
+        }
+
+        let project = self
+            .read_to_string(&root.join(CONFIG_FILE_NAME))
+            .ok()
+            .map(|text| {
+                debug!("loading project: {:?}", root);
+                Arc::new(Project::new(root, text))
+            });
+        self.projects
+            .borrow_mut()
+            .insert(root.to_path_buf(), project.clone());
+        project
+    }
+
+    /// The package `import_path` refers into when imported from the file at `path`, if it is
+    /// qualified with one of the dependencies of the file's project.
+    pub(crate) fn package_import(&self, path: &Path, import_path: &Path) -> Option<PackageImport> {
+        let project = self.project(path)?;
+        let (dependency, rest) = project.dependency_of(import_path)?;
+        let root = project.package_root(dependency);
+        Some(PackageImport {
+            dependency: dependency.clone(),
+            path: root.join(rest),
+            root,
+        })
+    }
+
+    /// Whether the package of an import is installed.
+    pub(crate) fn is_installed(&self, package: &PackageImport) -> bool {
+        self.canonicalize(&package.root).is_ok()
+    }
+
+    /// The version of the package of an import, as declared by its own `ato.yaml`.
+    pub(crate) fn installed_version(&self, package: &PackageImport) -> Option<String> {
+        self.project_at(&package.root)?
+            .config()
+            .package
+            .as_ref()?
+            .version
+            .clone()
+    }
+
+    /// Where the type `name` used in the file at `path` is declared.
+    pub(crate) fn definition(&self, path: &Path, name: &Symbol) -> Option<Location> {
+        let module_ref = self.file_scope(path).resolve(name)?.clone();
+        self.block_declarations(module_ref.source_path())
+            .get(module_ref.module_name())
+            .map(|declaration| declaration.location.clone())
+    }
+
+    /// Where the instance, signal, pin or attribute `parts` (e.g. `r1.p1`) used inside the block
+    /// `block` is declared. Each part is looked up in the type of the part before it, following
+    /// `new` types, `->` specializations and inheritance into other files.
+    pub(crate) fn port_definition(&self, block: &ModuleRef, parts: &[&str]) -> Option<Location> {
+        let (last, members) = parts.split_last()?;
+
+        // The blocks each part is looked up in, starting from `block` itself.
+        let mut blocks = vec![block.clone()];
+        for (i, member) in members.iter().enumerate() {
+            // A specialization by an enclosing block wins over one further in.
+            let specialized = blocks
+                .iter()
+                .enumerate()
+                .find_map(|(depth, outer)| self.specialization(outer, &parts[depth..=i]));
+            let type_ref = specialized.or_else(|| self.member(&blocks[i], member)?.1)?;
+            blocks.push(type_ref);
+        }
+
+        self.member(blocks.last()?, last)
+            .map(|(location, _)| location)
+    }
+
+    /// The block `block` followed by the blocks it inherits from, nearest first.
+    pub(crate) fn ancestry(&self, block: &ModuleRef) -> Vec<(ModuleRef, Arc<BlockDeclarations>)> {
+        let mut ancestry: Vec<(ModuleRef, Arc<BlockDeclarations>)> = vec![];
+        let mut current = Some(block.clone());
+        while let Some(block) = current.take() {
+            if ancestry.iter().any(|(seen, _)| seen == &block) {
+                break;
+            }
+            let declarations = self.block_declarations(block.source_path());
+            current = declarations
+                .get(block.module_name())
+                .and_then(|declaration| declaration.parent.as_ref())
+                .and_then(|parent| {
+                    self.file_scope(block.source_path())
+                        .resolve(parent)
+                        .cloned()
+                });
+            ancestry.push((block, declarations));
+        }
+        ancestry
+    }
+
+    /// The blocks `roots` are made of: the roots themselves, the blocks they inherit from, and
+    /// every block created with `new` or specialized to with `->` in any of those, transitively.
+    pub(crate) fn used_blocks<'a>(
+        &self,
+        roots: impl IntoIterator<Item = &'a ModuleRef>,
+    ) -> HashSet<ModuleRef> {
+        let mut used = HashSet::new();
+        let mut queue: Vec<ModuleRef> = roots.into_iter().cloned().collect();
+        while let Some(block) = queue.pop() {
+            if used.contains(&block) {
+                continue;
+            }
+            for (block, declarations) in self.ancestry(&block) {
+                let Some(declaration) = declarations.get(block.module_name()) else {
+                    continue;
+                };
+                let scope = self.file_scope(block.source_path());
+                for stmt in &declaration.stmt.body {
+                    let type_name = match stmt.deref() {
+                        Stmt::Assign(assign) => match assign.value.deref() {
+                            Expr::New(type_name) => type_name,
+                            _ => continue,
+                        },
+                        Stmt::Specialize(specialize) => &specialize.value,
+                        _ => continue,
+                    };
+                    if let Some(type_ref) = scope.resolve(type_name) {
+                        queue.push(type_ref.clone());
+                    }
+                }
+                used.insert(block);
+            }
+        }
+        used
+    }
+
+    /// Where the member `name` of `block` is declared, and the type it is created with if it
+    /// is an instance.
+    fn member(&self, block: &ModuleRef, name: &str) -> Option<(Location, Option<ModuleRef>)> {
+        let mut assignment = None;
+        for (block, declarations) in self.ancestry(block) {
+            let Some(declaration) = declarations.get(block.module_name()) else {
+                continue;
+            };
+            let Ok(source) = self.source(block.source_path()) else {
+                continue;
+            };
+            for stmt in &declaration.stmt.body {
+                let (span, type_name) = match stmt.deref() {
+                    Stmt::Signal(SignalStmt { name: declared })
+                    | Stmt::Pin(PinStmt { name: declared })
+                    | Stmt::Attribute(AttributeStmt { name: declared, .. })
+                        if declared.as_str() == name =>
+                    {
+                        (declared.span(), None)
+                    }
+                    Stmt::Connect(connect) => {
+                        let declared =
+                            [&connect.left, &connect.right]
+                                .into_iter()
+                                .find_map(|connectable| match connectable.deref() {
+                                    Connectable::Signal(declared) | Connectable::Pin(declared)
+                                        if declared.as_str() == name =>
+                                    {
+                                        Some(declared.span())
+                                    }
+                                    _ => None,
+                                });
+                        match declared {
+                            Some(span) => (span, None),
+                            None => continue,
+                        }
+                    }
+                    Stmt::Assign(assign) if assign.target.parts.len() == 1 => {
+                        if assign.target.parts[0].as_str() != name {
+                            continue;
+                        }
+                        match assign.value.deref() {
+                            Expr::New(type_name) => (assign.target.span(), Some(type_name)),
+                            // Assigning a value only declares the attribute if nothing else does.
+                            _ => {
+                                assignment.get_or_insert_with(|| {
+                                    assign.target.span().to_location(&source)
+                                });
+                                continue;
+                            }
+                        }
+                    }
+                    _ => continue,
+                };
+
+                let type_ref = type_name.and_then(|type_name| {
+                    self.file_scope(block.source_path())
+                        .resolve(type_name)
+                        .cloned()
+                });
+                return Some((span.to_location(&source), type_ref));
+            }
+        }
+        assignment.map(|location| (location, None))
+    }
+
+    /// The type `parts`, relative to `block`, is specialized to by `block` or the blocks it
+    /// inherits from.
+    fn specialization(&self, block: &ModuleRef, parts: &[&str]) -> Option<ModuleRef> {
+        self.ancestry(block)
+            .into_iter()
+            .find_map(|(block, declarations)| {
+                let declaration = declarations.get(block.module_name())?;
+                declaration
+                    .stmt
+                    .body
+                    .iter()
+                    .rev()
+                    .find_map(|stmt| match stmt.deref() {
+                        Stmt::Specialize(specialize)
+                            if specialize
+                                .port
+                                .parts
+                                .iter()
+                                .map(|part| part.as_str())
+                                .eq(parts.iter().copied()) =>
+                        {
+                            self.file_scope(block.source_path())
+                                .resolve(&specialize.value)
+                                .cloned()
+                        }
+                        _ => None,
+                    })
+            })
+    }
+}
+
+/// An import qualified with a package, e.g. `generics/resistors.ato`.
+#[derive(Debug, Clone)]
+pub(crate) struct PackageImport {
+    pub(crate) dependency: Dependency,
+    /// Where the package is installed.
+    pub(crate) root: PathBuf,
+    /// The imported file within the package.
+    pub(crate) path: PathBuf,
+}
+
+/// Sources that have been set are overlaid on top of the underlying file system.
+impl<V: Vfs> Vfs for Database<V> {
+    fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        match self.sources.get(&normalize(path)) {
+            Some(input) => Ok(input.source.text().to_string()),
+            None => self.vfs.read_to_string(path),
+        }
+    }
+
+    fn is_file(&self, path: &Path) -> bool {
+        self.sources.contains_key(&normalize(path)) || self.vfs.is_file(path)
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        let normalized = normalize(path);
+        if self.sources.contains_key(&normalized) {
+            return Ok(normalized);
+        }
+        self.vfs.canonicalize(path)
+    }
+
+    fn find_sources(&self, dir: &Path) -> Vec<PathBuf> {
+        let mut sources = self.vfs.find_sources(dir);
+        sources.extend(
+            self.sources
+                .keys()
+                .filter(|path| is_source_under(dir, path))
+                .cloned(),
+        );
+        sources.sort();
+        sources.dedup();
+        sources
+    }
+}
+
+/// The top-level blocks of a file.
+#[derive(Debug, Default)]
+pub(crate) struct BlockDeclarations {
+    pub(crate) blocks: Vec<BlockDeclaration>,
+    /// Blocks declaring a name that an earlier block already declared, with the location of
+    /// that earlier block.
+    pub(crate) duplicates: Vec<(BlockDeclaration, Location)>,
+}
+
+impl BlockDeclarations {
+    fn collect(source: &AtopileSource) -> Self {
+        debug!(
+            "Collecting block declarations from source: {:?}",
+            source.path()
+        );
+        let mut declarations = Self::default();
+        for stmt in source.ast() {
+            if let Stmt::Block(block) = stmt.deref() {
+                let declaration = BlockDeclaration::new(block, stmt.span().to_location(source));
+                match declarations.get(&declaration.name) {
+                    Some(first) => {
+                        let first_location = first.location.clone();
+                        declarations.duplicates.push((declaration, first_location));
+                    }
+                    None => declarations.blocks.push(declaration),
+                }
+            }
+        }
+        declarations
+    }
+
+    pub(crate) fn get(&self, name: &Symbol) -> Option<&BlockDeclaration> {
+        self.blocks.iter().find(|block| &block.name == name)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct BlockDeclaration {
+    pub(crate) name: Symbol,
+    pub(crate) parent: Option<Symbol>,
+    pub(crate) location: Location,
+    pub(crate) stmt: BlockStmt,
+    /// The types this block's body creates or specializes to.
+    pub(crate) dependencies: HashSet<Symbol>,
+}
+
+impl BlockDeclaration {
+    fn new(block: &BlockStmt, location: Location) -> Self {
+        Self {
+            name: block.name.deref().clone(),
+            parent: block.parent.as_ref().map(|p| p.deref().clone()),
+            location,
+            stmt: block.clone(),
+            dependencies: collect_dependencies(&block.body),
+        }
+    }
+}
+
+fn collect_dependencies(body: &[Spanned<Stmt>]) -> HashSet<Symbol> {
+    let mut deps = HashSet::new();
+    for stmt in body {
+        match stmt.deref() {
+            // Handle new expressions like: x = new Module
+            Stmt::Assign(assign) => {
+                if let Expr::New(type_name) = assign.value.deref() {
+                    deps.insert(type_name.deref().clone());
+                }
+            }
+            // Handle specialize statements like: x.y -> Module
+            Stmt::Specialize(specialize) => {
+                deps.insert(specialize.value.deref().clone());
+            }
+            // Handle nested blocks to find dependencies in their bodies
+            Stmt::Block(block) => {
+                deps.extend(collect_dependencies(&block.body));
+            }
+            _ => {}
+        }
+    }
+    deps
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct FileScope {
+    symbols: HashMap<Symbol, ModuleRef>,
+}
+
+impl FileScope {
+    fn define(&mut self, symbol: &Symbol, module_ref: &ModuleRef) {
+        self.symbols.insert(symbol.clone(), module_ref.clone());
+    }
+
+    pub(crate) fn resolve(&self, symbol: &Symbol) -> Option<&ModuleRef> {
+        self.symbols.get(symbol)
+    }
+
+    pub(crate) fn names(&self) -> impl Iterator<Item = &Symbol> {
+        self.symbols.keys()
+    }
+}
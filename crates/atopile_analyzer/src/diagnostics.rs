@@ -1,10 +1,16 @@
 use std::{
-    cell::{Ref, RefCell},
+    cell::RefCell,
     collections::HashMap,
+    fmt::Display,
     path::{Path, PathBuf},
 };
 
-use crate::{evaluator::EvaluatorError, Location};
+use atopile_parser::AtopileError;
+
+use crate::{
+    evaluator::{EvaluatorError, EvaluatorErrorKind},
+    Location,
+};
 
 /// A diagnostic from the analyzer.
 #[derive(Debug, Clone)]
@@ -14,6 +20,39 @@ pub struct AnalyzerDiagnostic {
     pub file: PathBuf,
 }
 
+impl AnalyzerDiagnostic {
+    /// The stable code identifying this kind of diagnostic.
+    pub fn code(&self) -> DiagnosticCode {
+        match &self.kind {
+            AnalyzerDiagnosticKind::UnconnectedInterface(_) => {
+                DiagnosticCode::UNCONNECTED_INTERFACE
+            }
+            AnalyzerDiagnosticKind::Evaluator(error) => DiagnosticCode::from(&error.kind),
+            AnalyzerDiagnosticKind::Syntax(_) => DiagnosticCode::SYNTAX_ERROR,
+        }
+    }
+
+    /// Other places in the source that help explain this diagnostic.
+    pub fn related(&self) -> Vec<RelatedLocation> {
+        match &self.kind {
+            AnalyzerDiagnosticKind::UnconnectedInterface(diag) => vec![RelatedLocation::new(
+                &diag.interface_location,
+                format!("`{}` is declared here", diag.interface_name),
+            )],
+            AnalyzerDiagnosticKind::Evaluator(error) => error.details.related.clone(),
+            AnalyzerDiagnosticKind::Syntax(_) => vec![],
+        }
+    }
+
+    /// Edits that resolve this diagnostic and can be applied without further input.
+    pub fn fixes(&self) -> &[Fix] {
+        match &self.kind {
+            AnalyzerDiagnosticKind::Evaluator(error) => &error.details.fixes,
+            _ => &[],
+        }
+    }
+}
+
 #[derive(Debug, Copy, Clone)]
 pub enum AnalyzerDiagnosticSeverity {
     Error,
@@ -24,20 +63,207 @@ pub enum AnalyzerDiagnosticSeverity {
 pub enum AnalyzerDiagnosticKind {
     UnconnectedInterface(UnconnectedInterfaceDiagnostic),
     Evaluator(EvaluatorError),
+    Syntax(SyntaxDiagnostic),
 }
 
 impl From<EvaluatorError> for AnalyzerDiagnostic {
     fn from(error: EvaluatorError) -> Self {
         let file = error.location.file.clone();
+        let severity = match error.kind {
+            EvaluatorErrorKind::DimensionMismatch
+            | EvaluatorErrorKind::MissingAttribute
+            | EvaluatorErrorKind::InterfaceMismatch
+            | EvaluatorErrorKind::UnusedModule
+            | EvaluatorErrorKind::PackageVersionMismatch => AnalyzerDiagnosticSeverity::Warning,
+            _ => AnalyzerDiagnosticSeverity::Error,
+        };
         Self {
-            severity: AnalyzerDiagnosticSeverity::Error,
+            severity,
             kind: AnalyzerDiagnosticKind::Evaluator(error),
             file,
         }
     }
 }
+
+/// A stable identifier for a class of diagnostic, e.g. `E0101 type-not-found`. Codes are never
+/// reused or renumbered, so they can be referred to from documentation and editor settings.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct DiagnosticCode {
+    pub code: &'static str,
+    pub name: &'static str,
+}
+
+impl DiagnosticCode {
+    const fn new(code: &'static str, name: &'static str) -> Self {
+        Self { code, name }
+    }
+
+    pub const SYNTAX_ERROR: Self = Self::new("E0001", "syntax-error");
+    pub const UNEXPECTED_STATEMENT: Self = Self::new("E0002", "unexpected-statement");
+
+    pub const TYPE_NOT_FOUND: Self = Self::new("E0101", "type-not-found");
+    pub const IMPORT_PATH_NOT_FOUND: Self = Self::new("E0102", "import-path-not-found");
+    pub const IMPORT_CYCLE: Self = Self::new("E0103", "import-cycle");
+    pub const IMPORT_LOAD_FAILED: Self = Self::new("E0104", "import-load-failed");
+    pub const IMPORT_NOT_FOUND: Self = Self::new("E0105", "import-not-found");
+    pub const CYCLIC_INHERITANCE: Self = Self::new("E0106", "cyclic-inheritance");
+    pub const PACKAGE_NOT_FOUND: Self = Self::new("E0107", "package-not-found");
+    pub const PACKAGE_VERSION_MISMATCH: Self = Self::new("E0108", "package-version-mismatch");
+
+    pub const DUPLICATE_DECLARATION: Self = Self::new("E0201", "duplicate-declaration");
+    pub const INVALID_ASSIGNMENT: Self = Self::new("E0202", "invalid-assignment");
+    pub const UNDECLARED_ATTRIBUTE: Self = Self::new("E0203", "undeclared-attribute");
+    pub const MISSING_ATTRIBUTE: Self = Self::new("E0204", "missing-attribute");
+    pub const UNUSED_MODULE: Self = Self::new("E0205", "unused-module");
+
+    pub const INVALID_UNIT: Self = Self::new("E0301", "invalid-unit");
+    pub const INVALID_EXPRESSION: Self = Self::new("E0302", "invalid-expression");
+    pub const DIMENSION_MISMATCH: Self = Self::new("E0303", "dimension-mismatch");
+    pub const ASSERTION_FAILED: Self = Self::new("E0304", "assertion-failed");
+
+    pub const INVALID_CONNECTION: Self = Self::new("E0401", "invalid-connection");
+    pub const UNCONNECTED_INTERFACE: Self = Self::new("E0402", "unconnected-interface");
+    pub const INTERFACE_MISMATCH: Self = Self::new("E0403", "interface-mismatch");
+
+    pub const NETLIST_PARSE_ERROR: Self = Self::new("E0501", "netlist-parse-error");
+
+    pub const INVALID_PROJECT_CONFIG: Self = Self::new("E0601", "invalid-project-config");
+    pub const BUILD_ENTRY_NOT_FOUND: Self = Self::new("E0602", "build-entry-not-found");
+
+    pub const INTERNAL: Self = Self::new("E9999", "internal");
+}
+
+impl From<&EvaluatorErrorKind> for DiagnosticCode {
+    fn from(kind: &EvaluatorErrorKind) -> Self {
+        match kind {
+            EvaluatorErrorKind::ImportPathNotFound => Self::IMPORT_PATH_NOT_FOUND,
+            EvaluatorErrorKind::ImportCycle => Self::IMPORT_CYCLE,
+            EvaluatorErrorKind::ImportLoadFailed => Self::IMPORT_LOAD_FAILED,
+            EvaluatorErrorKind::ImportNotFound => Self::IMPORT_NOT_FOUND,
+            EvaluatorErrorKind::UnexpectedStmt => Self::UNEXPECTED_STATEMENT,
+            EvaluatorErrorKind::TypeNotFound => Self::TYPE_NOT_FOUND,
+            EvaluatorErrorKind::InvalidAssignment => Self::INVALID_ASSIGNMENT,
+            EvaluatorErrorKind::InvalidConnection => Self::INVALID_CONNECTION,
+            EvaluatorErrorKind::DuplicateDeclaration => Self::DUPLICATE_DECLARATION,
+            EvaluatorErrorKind::CyclicInheritance => Self::CYCLIC_INHERITANCE,
+            EvaluatorErrorKind::NetlistParseError => Self::NETLIST_PARSE_ERROR,
+            EvaluatorErrorKind::InvalidUnit => Self::INVALID_UNIT,
+            EvaluatorErrorKind::InvalidExpression => Self::INVALID_EXPRESSION,
+            EvaluatorErrorKind::AssertionFailed => Self::ASSERTION_FAILED,
+            EvaluatorErrorKind::DimensionMismatch => Self::DIMENSION_MISMATCH,
+            EvaluatorErrorKind::UndeclaredAttribute => Self::UNDECLARED_ATTRIBUTE,
+            EvaluatorErrorKind::MissingAttribute => Self::MISSING_ATTRIBUTE,
+            EvaluatorErrorKind::InterfaceMismatch => Self::INTERFACE_MISMATCH,
+            EvaluatorErrorKind::InvalidProjectConfig => Self::INVALID_PROJECT_CONFIG,
+            EvaluatorErrorKind::BuildEntryNotFound => Self::BUILD_ENTRY_NOT_FOUND,
+            EvaluatorErrorKind::UnusedModule => Self::UNUSED_MODULE,
+            EvaluatorErrorKind::PackageNotFound => Self::PACKAGE_NOT_FOUND,
+            EvaluatorErrorKind::PackageVersionMismatch => Self::PACKAGE_VERSION_MISMATCH,
+            EvaluatorErrorKind::Internal => Self::INTERNAL,
+        }
+    }
+}
+
+impl Display for DiagnosticCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.code, self.name)
+    }
+}
+
+/// Secondary locations and suggested fixes attached to a diagnostic.
+#[derive(Debug, Clone, Default)]
+pub struct DiagnosticDetails {
+    pub related: Vec<RelatedLocation>,
+    pub fixes: Vec<Fix>,
+}
+
+/// A secondary location attached to a diagnostic, e.g. the earlier declaration of a name that
+/// is declared twice.
+#[derive(Debug, Clone)]
+pub struct RelatedLocation {
+    pub location: Location,
+    pub message: String,
+}
+
+impl RelatedLocation {
+    pub fn new(location: &Location, message: String) -> Self {
+        Self {
+            location: location.clone(),
+            message,
+        }
+    }
+}
+
+/// A suggested fix for a diagnostic, made of edits that can be applied as-is.
+#[derive(Debug, Clone)]
+pub struct Fix {
+    pub title: String,
+    pub edits: Vec<TextEdit>,
+}
+
+impl Fix {
+    /// A fix that replaces the text at `location` with `new_text`.
+    pub fn replace(title: String, location: &Location, new_text: String) -> Self {
+        Self {
+            title,
+            edits: vec![TextEdit {
+                location: location.clone(),
+                new_text,
+            }],
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TextEdit {
+    pub location: Location,
+    pub new_text: String,
+}
+
+/// Find the candidate closest to a misspelled `name`, if any is close enough to be a likely
+/// typo.
+pub(crate) fn closest_match<'a>(
+    name: &str,
+    candidates: impl IntoIterator<Item = &'a str>,
+) -> Option<&'a str> {
+    let max_distance = (name.chars().count() / 3).max(1);
+    candidates
+        .into_iter()
+        .filter(|candidate| *candidate != name)
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min()
+        .map(|(_, candidate)| candidate)
+}
+
+/// The Levenshtein distance between two strings.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// What produced a set of diagnostics, so that they can be dropped when it is redone.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+enum DiagnosticOrigin {
+    /// Evaluating a single file.
+    File(PathBuf),
+    /// The checks run over the whole state once evaluation has finished.
+    Checks,
+    /// Reading the netlists the builds were last written to.
+    Netlists,
+}
+
 pub struct AnalyzerReporter {
-    diagnostics: RefCell<HashMap<PathBuf, Vec<AnalyzerDiagnostic>>>,
+    diagnostics: RefCell<HashMap<DiagnosticOrigin, Vec<AnalyzerDiagnostic>>>,
 }
 
 impl AnalyzerReporter {
@@ -59,24 +285,78 @@ impl AnalyzerReporter {
         self.diagnostics.borrow_mut().clear();
     }
 
+    /// Drop the diagnostics reported while evaluating `path`.
     pub fn clear(&self, path: &Path) {
         self.diagnostics
             .borrow_mut()
-            .entry(path.to_path_buf())
-            .or_default()
-            .clear();
+            .remove(&DiagnosticOrigin::File(path.to_path_buf()));
+    }
+
+    pub(crate) fn clear_checks(&self) {
+        self.diagnostics
+            .borrow_mut()
+            .remove(&DiagnosticOrigin::Checks);
+    }
+
+    /// Drop the diagnostics reported while reading netlists.
+    pub(crate) fn clear_netlists(&self) {
+        self.diagnostics
+            .borrow_mut()
+            .remove(&DiagnosticOrigin::Netlists);
     }
 
     pub fn report(&self, diagnostic: AnalyzerDiagnostic) {
+        self.report_from(DiagnosticOrigin::File(diagnostic.file.clone()), diagnostic);
+    }
+
+    pub(crate) fn report_check(&self, diagnostic: AnalyzerDiagnostic) {
+        self.report_from(DiagnosticOrigin::Checks, diagnostic);
+    }
+
+    pub(crate) fn report_netlist(&self, diagnostic: AnalyzerDiagnostic) {
+        self.report_from(DiagnosticOrigin::Netlists, diagnostic);
+    }
+
+    fn report_from(&self, origin: DiagnosticOrigin, diagnostic: AnalyzerDiagnostic) {
         self.diagnostics
             .borrow_mut()
-            .entry(diagnostic.file.clone())
+            .entry(origin)
             .or_default()
             .push(diagnostic);
     }
 
-    pub fn diagnostics(&self) -> Ref<'_, HashMap<PathBuf, Vec<AnalyzerDiagnostic>>> {
-        self.diagnostics.borrow()
+    /// All diagnostics, grouped by the file they are reported in.
+    pub fn diagnostics(&self) -> HashMap<PathBuf, Vec<AnalyzerDiagnostic>> {
+        let diagnostics = self.diagnostics.borrow();
+        let mut origins: Vec<_> = diagnostics.keys().collect();
+        origins.sort();
+
+        let mut by_file: HashMap<PathBuf, Vec<AnalyzerDiagnostic>> = HashMap::new();
+        for diagnostic in origins.into_iter().flat_map(|origin| &diagnostics[origin]) {
+            by_file
+                .entry(diagnostic.file.clone())
+                .or_default()
+                .push(diagnostic.clone());
+        }
+        by_file
+    }
+}
+
+/// An error from the lexer or parser, with the tokens it expected and found.
+#[derive(Debug, Clone)]
+pub struct SyntaxDiagnostic {
+    pub error: AtopileError,
+    pub location: Location,
+}
+
+impl From<SyntaxDiagnostic> for AnalyzerDiagnostic {
+    fn from(diagnostic: SyntaxDiagnostic) -> Self {
+        let file = diagnostic.location.file.clone();
+        Self {
+            severity: AnalyzerDiagnosticSeverity::Error,
+            kind: AnalyzerDiagnosticKind::Syntax(diagnostic),
+            file,
+        }
     }
 }
 
@@ -1,6 +1,5 @@
 use std::{
     collections::{HashMap, HashSet},
-    fs,
     ops::Deref,
     path::{Path, PathBuf},
     sync::Arc,
@@ -11,56 +10,72 @@ use atopile_parser::{
     parser::{BlockKind, BlockStmt, Connectable, Expr, Stmt, Symbol},
     AtopileSource, Spanned,
 };
-use fancy_regex::Regex;
-use lazy_static::lazy_static;
 use log::debug;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
 use crate::{
-    diagnostics::AnalyzerReporter, AsLocation, IntoLocated, IntoLocation, Located, Location,
+    db::{BlockDeclaration, Database, FileScope, PackageImport},
+    diagnostics::{closest_match, AnalyzerReporter, DiagnosticDetails, Fix, RelatedLocation},
+    netlist::KicadNetlist,
+    units::{Dimension, Quantity},
+    vfs::{normalize, OsVfs, Vfs},
+    AsLocation, IntoLocated, IntoLocation, Located, Location,
 };
 
-#[derive(Debug, Clone)]
-struct BlockDeclaration {
-    name: Symbol,
-    parent: Option<Symbol>,
-    location: Location,
-    stmt: BlockStmt,
-    dependencies: HashSet<Symbol>,
-}
-
-impl BlockDeclaration {
-    fn new(block: &BlockStmt, location: Location) -> Self {
-        Self {
-            name: block.name.deref().clone(),
-            parent: block.parent.as_ref().map(|p| p.deref().clone()),
-            location,
-            stmt: block.clone(),
-            dependencies: HashSet::new(),
-        }
-    }
-}
+mod asserts;
+mod attributes;
+mod builds;
+mod completions;
+mod dependencies;
+mod designators;
+mod expr;
+mod hover;
+mod interfaces;
+mod kicad;
+mod nets;
+
+use asserts::Assertion;
+use attributes::AttributeDeclaration;
+pub(crate) use attributes::BUILTIN_ATTRIBUTES;
+pub(crate) use builds::is_dependency;
+pub use builds::Build;
+use dependencies::FileGraph;
+pub(crate) use nets::Net;
 
 #[derive(Debug, Clone, Serialize, Default)]
 pub struct EvaluatorState {
     instances: HashMap<InstanceRef, Instance>,
+    /// The electrical nets of each root module, keyed by the root module's instance.
+    nets: HashMap<InstanceRef, Vec<Net>>,
+    /// The reference designators last assigned to each component, kept across re-evaluations
+    /// so that components keep theirs as the design changes around them.
+    #[serde(skip)]
+    designators: HashMap<InstanceRef, String>,
 }
 
 impl EvaluatorState {
     fn new() -> Self {
         Self {
             instances: HashMap::new(),
+            nets: HashMap::new(),
+            designators: HashMap::new(),
         }
     }
 
-    pub fn resolve_reference_designators(&mut self) {
-        lazy_static! {
-            static ref COMP_RE: Regex = Regex::new(r#"(?s)\(comp\s+\(ref\s+"([^"]+)"\)(?:(?!\(comp\s+).)*?sheetpath\s+\(names\s+"([^"]+)"\)"#).unwrap();
-        }
+    /// Fill in reference designators from the netlist each build was last written to,
+    /// replacing those pinned by an earlier read. Returns an error for each netlist that failed
+    /// to parse.
+    fn resolve_reference_designators(
+        &mut self,
+        vfs: &impl Vfs,
+        netlist_paths: &HashMap<ModuleRef, PathBuf>,
+    ) -> Vec<EvaluatorError> {
+        let mut errors = Vec::new();
 
-        // Keep track of already processed netlists to avoid reading them multiple times
-        let mut processed_netlists: HashMap<PathBuf, HashMap<String, String>> = HashMap::new();
+        // Keep track of already processed netlists to avoid reading them multiple times. A
+        // netlist that failed to parse is cached as `None`.
+        let mut processed_netlists: HashMap<PathBuf, Option<KicadNetlist>> = HashMap::new();
 
         // First, collect all component instances
         let component_instances: Vec<_> = self
@@ -71,103 +86,82 @@ impl EvaluatorState {
             .collect();
 
         for instance_ref in component_instances {
-            // Convert instance path to filesystem path
-            let source_path = &instance_ref.module.source_path;
-            if let Some(source_dir) = source_path.parent() {
-                // Walk up the directory tree to find ato.yaml
-                let mut current_dir = source_dir;
-                let mut found_root = false;
-
-                // First check the current directory
-                if current_dir.join("ato.yaml").exists() {
-                    found_root = true;
-                }
-
-                // If not found, walk up the tree
-                while !found_root {
-                    match current_dir.parent() {
-                        Some(parent) => {
-                            current_dir = parent;
-                            if current_dir.join("ato.yaml").exists() {
-                                found_root = true;
-                            }
-                        }
-                        None => break,
-                    }
-                }
-
-                if !found_root {
-                    continue;
-                }
+            // Drop the designator pinned or allocated last time, so that one pinned by a netlist
+            // that has since changed or gone doesn't outlive it, even if the component's file
+            // hasn't been re-evaluated.
+            if let Some(instance) = self.instances.get_mut(&instance_ref) {
+                instance.reference_designator = None;
+            }
 
-                // Look for the netlist file
-                let netlist_path = current_dir.join("build").join("default.net");
-                if !netlist_path.exists() {
-                    continue;
-                }
+            let Some(netlist_path) = netlist_paths.get(&instance_ref.module) else {
+                continue;
+            };
+            if !vfs.is_file(netlist_path) {
+                continue;
+            }
 
-                // Get or create the cache for this netlist
-                let ref_map = if let Some(map) = processed_netlists.get(&netlist_path) {
-                    map
-                } else {
+            // Get or create the cache for this netlist
+            let netlist = processed_netlists
+                .entry(netlist_path.clone())
+                .or_insert_with(|| {
                     // Read and parse the netlist file
-                    match fs::read_to_string(&netlist_path) {
-                        Ok(contents) => {
-                            let mut map: HashMap<String, String> = HashMap::new();
-                            for cap in COMP_RE.captures_iter(&contents) {
-                                let ref_des = cap
-                                    .as_ref()
-                                    .expect("Failed to get captures")
-                                    .get(1)
-                                    .expect("Failed to get ref_des")
-                                    .as_str();
-                                let sheet_path = cap
-                                    .as_ref()
-                                    .expect("Failed to get captures")
-                                    .get(2)
-                                    .expect("Failed to get sheet_path")
-                                    .as_str();
-                                map.insert(sheet_path.to_string(), ref_des.to_string());
-                            }
-                            processed_netlists.insert(netlist_path.clone(), map);
-                            processed_netlists.get(&netlist_path).unwrap()
-                        }
-                        Err(_) => continue,
-                    }
-                };
-
-                // Convert our instance path format to netlist format
-                // From: path/to/file.ato:RootModule.path.to.instance
-                // To: /absolute/path/to/file.ato:RootModule::path.to.instance
-                let instance_path = format!(
-                    "{}:{}::{}",
-                    source_path.display(),
-                    instance_ref.module.module_name,
-                    instance_ref
-                        .instance_path
-                        .iter()
-                        .map(|s| s.to_string())
-                        .collect::<Vec<_>>()
-                        .join(".")
-                );
+                    let contents = vfs.read_to_string(netlist_path).ok()?;
+                    KicadNetlist::parse(&contents)
+                        .map_err(|e| {
+                            let location = Location {
+                                file: netlist_path.clone(),
+                                range: crate::Range {
+                                    start: e.position,
+                                    end: e.position,
+                                },
+                            };
+                            errors.push(
+                                EvaluatorError::new(
+                                    EvaluatorErrorKind::NetlistParseError,
+                                    &location,
+                                )
+                                .with_message(e.message),
+                            );
+                        })
+                        .ok()
+                });
+            let Some(netlist) = netlist else {
+                continue;
+            };
 
-                // Look up the reference designator
-                if let Some(ref_des) = ref_map.get(&instance_path) {
-                    if let Some(instance) = self.instances.get_mut(&instance_ref) {
-                        instance.reference_designator = Some(ref_des.clone());
-                    }
+            // Look up the reference designator
+            if let Some(component) = netlist.component_at(&instance_ref.sheet_path()) {
+                if let Some(instance) = self.instances.get_mut(&instance_ref) {
+                    instance.reference_designator = Some(component.reference.clone());
                 }
             }
         }
+
+        errors
     }
 }
 
-#[derive(Default)]
-pub struct Evaluator {
+pub struct Evaluator<V: Vfs = OsVfs> {
     state: EvaluatorState,
     reporter: AnalyzerReporter,
-    files: HashMap<PathBuf, Arc<AtopileSource>>,
+    db: Database<V>,
     visited_files: HashSet<PathBuf>,
+    graph: FileGraph,
+    /// The builds being evaluated by name, with their root modules, when evaluating from entry
+    /// points rather than every module of every file.
+    entry_points: Option<Vec<(String, ModuleRef)>>,
+    /// The blocks the entry points are made of, which are the only ones elaborated in the
+    /// projects being built.
+    used_blocks: HashSet<ModuleRef>,
+    /// The root of the project whose builds are being evaluated, if they were taken from its
+    /// `ato.yaml`.
+    project: Option<PathBuf>,
+}
+
+impl Default for Evaluator {
+    fn default() -> Self {
+        Self::with_vfs(OsVfs)
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
@@ -180,7 +174,15 @@ pub(crate) struct ModuleRef {
 }
 
 impl ModuleRef {
-    fn new(source_path: &Path, module_name: &Symbol) -> Self {
+    pub(crate) fn source_path(&self) -> &Path {
+        &self.source_path
+    }
+
+    pub(crate) fn module_name(&self) -> &Symbol {
+        &self.module_name
+    }
+
+    pub(crate) fn new(source_path: &Path, module_name: &Symbol) -> Self {
         Self {
             source_path: source_path.to_path_buf(),
             module_name: module_name.clone(),
@@ -232,6 +234,32 @@ impl InstanceRef {
     fn len(&self) -> usize {
         self.instance_path.len()
     }
+
+    /// The address of this instance within its root module, e.g. `RootModule.path.to.instance`.
+    fn address(&self) -> String {
+        let mut address = self.module.module_name.to_string();
+        for part in &self.instance_path {
+            address.push('.');
+            address.push_str(part);
+        }
+        address
+    }
+
+    /// The address of this instance as it appears in a netlist's `sheetpath`.
+    /// From: path/to/file.ato:RootModule.path.to.instance
+    /// To: path/to/file.ato:RootModule::path.to.instance
+    fn sheet_path(&self) -> String {
+        format!(
+            "{}:{}::{}",
+            self.module.source_path.display(),
+            self.module.module_name,
+            self.instance_path
+                .iter()
+                .map(|s| s.to_string())
+                .collect::<Vec<_>>()
+                .join(".")
+        )
+    }
 }
 
 impl From<&ModuleRef> for InstanceRef {
@@ -282,11 +310,19 @@ pub(crate) enum AttributeValue {
     String(String),
     Number(f64),
     Boolean(bool),
-    Physical(String),
+    Physical(PhysicalAttribute),
     Port(String),
     Array(Vec<AttributeValue>),
 }
 
+/// A physical value as written in the source, along with its normalized quantity.
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct PhysicalAttribute {
+    text: String,
+    #[serde(flatten)]
+    quantity: Quantity,
+}
+
 impl From<String> for AttributeValue {
     fn from(s: String) -> Self {
         AttributeValue::String(s)
@@ -317,30 +353,35 @@ impl<T: Into<AttributeValue>> From<Vec<T>> for AttributeValue {
     }
 }
 
-impl From<&Expr> for AttributeValue {
-    fn from(expr: &Expr) -> Self {
-        match expr {
-            Expr::String(s) => AttributeValue::String(s.deref().clone()),
-            Expr::Number(n) => {
-                if let Ok(num) = n.deref().parse::<f64>() {
-                    AttributeValue::Number(num)
-                } else {
-                    // If parsing fails, store as string
-                    AttributeValue::String(n.deref().clone())
-                }
-            }
-            Expr::Bool(b) => AttributeValue::Boolean(*b.deref()),
-            Expr::Physical(p) => AttributeValue::Physical(p.deref().to_string()),
-            Expr::Port(p) => AttributeValue::Port(p.deref().to_string()),
-            // For other types, convert to string representation
-            _ => AttributeValue::String("".to_string()),
+impl AttributeValue {
+    /// The dimension of a numeric value. Plain numbers are dimensionless.
+    fn dimension(&self) -> Option<Dimension> {
+        match self {
+            AttributeValue::Number(_) => Some(Dimension::DIMENSIONLESS),
+            AttributeValue::Physical(p) => Some(p.quantity.dimension),
+            _ => None,
         }
     }
 }
 
-impl From<Expr> for AttributeValue {
-    fn from(expr: Expr) -> Self {
-        (&expr).into()
+impl std::fmt::Display for AttributeValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AttributeValue::String(s) => write!(f, "{}", s),
+            AttributeValue::Number(n) => write!(f, "{}", n),
+            AttributeValue::Boolean(b) => write!(f, "{}", b),
+            AttributeValue::Physical(p) => write!(f, "{}", p.text),
+            AttributeValue::Port(p) => write!(f, "{}", p),
+            AttributeValue::Array(values) => write!(
+                f,
+                "[{}]",
+                values
+                    .iter()
+                    .map(|v| v.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        }
     }
 }
 
@@ -352,6 +393,37 @@ pub(crate) struct Instance {
     children: HashMap<Symbol, InstanceRef>,
     connections: Vec<Connection>,
     reference_designator: Option<String>,
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    declarations: HashMap<Symbol, AttributeDeclaration>,
+    /// The statement that created this instance: the `new`, `signal` or `pin` in the block
+    /// that declares it, or the block itself for the root instance of a block.
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "source_location::option"
+    )]
+    location: Option<Location>,
+    /// The statements this instance was copied into place through, innermost first: the `new`
+    /// of each instance it is part of, and any `from` inheritance or `->` specialization.
+    #[serde(
+        skip_serializing_if = "Vec::is_empty",
+        serialize_with = "source_location::list"
+    )]
+    provenance: Vec<Location>,
+    /// Where each attribute was last assigned.
+    #[serde(
+        skip_serializing_if = "HashMap::is_empty",
+        serialize_with = "source_location::map"
+    )]
+    assignments: HashMap<Symbol, Location>,
+    /// The block this block was declared `from`. Only set on the root instance of a block.
+    #[serde(skip)]
+    parent: Option<ModuleRef>,
+    #[serde(skip)]
+    asserts: Vec<Assertion>,
+    /// The dimension each physical attribute is expected to have, either declared (`v: voltage`)
+    /// or inferred from its first assignment.
+    #[serde(skip)]
+    dimensions: HashMap<Symbol, Dimension>,
 }
 
 impl Instance {
@@ -363,28 +435,27 @@ impl Instance {
             children: HashMap::new(),
             connections: Vec::new(),
             reference_designator: None,
+            declarations: HashMap::new(),
+            location: None,
+            provenance: Vec::new(),
+            assignments: HashMap::new(),
+            parent: None,
+            asserts: Vec::new(),
+            dimensions: HashMap::new(),
         }
     }
 
-    fn port() -> Self {
+    fn port(location: Location) -> Self {
         Self {
-            type_ref: ModuleRef::port(),
-            kind: InstanceKind::Port,
-            attributes: HashMap::new(),
-            children: HashMap::new(),
-            connections: Vec::new(),
-            reference_designator: None,
+            location: Some(location),
+            ..Self::new(&ModuleRef::port(), InstanceKind::Port)
         }
     }
 
-    fn pin() -> Self {
+    fn pin(location: Location) -> Self {
         Self {
-            type_ref: ModuleRef::pin(),
-            kind: InstanceKind::Pin,
-            attributes: HashMap::new(),
-            children: HashMap::new(),
-            connections: Vec::new(),
-            reference_designator: None,
+            location: Some(location),
+            ..Self::new(&ModuleRef::pin(), InstanceKind::Pin)
         }
     }
 
@@ -392,6 +463,27 @@ impl Instance {
         self.attributes.insert(attribute.clone(), value.into());
     }
 
+    /// Assign an attribute at `location`, checking it against the dimension expected for it.
+    /// Returns the expected dimension if the value doesn't match it.
+    fn assign_attribute(
+        &mut self,
+        attribute: &Symbol,
+        value: AttributeValue,
+        location: &Location,
+    ) -> Option<Dimension> {
+        self.assignments.insert(attribute.clone(), location.clone());
+        let mismatch = match (value.dimension(), self.dimensions.get(attribute)) {
+            (Some(found), Some(expected)) if found != *expected => Some(*expected),
+            (Some(found), None) => {
+                self.dimensions.insert(attribute.clone(), found);
+                None
+            }
+            _ => None,
+        };
+        self.add_attribute(attribute, value);
+        mismatch
+    }
+
     fn add_child(&mut self, child: &Symbol, instance_ref: &InstanceRef) {
         self.children.insert(child.clone(), instance_ref.clone());
     }
@@ -401,11 +493,127 @@ impl Instance {
 pub(crate) struct Connection {
     left: InstanceRef,
     right: InstanceRef,
+    /// The location of the `~` statement that produced this connection.
+    #[serde(
+        serialize_with = "source_location::one",
+        deserialize_with = "source_location::deserialize_one"
+    )]
+    location: Location,
+    /// The statements this connection was copied into place through, as for
+    /// [`Instance::provenance`].
+    #[serde(
+        default,
+        skip_serializing_if = "Vec::is_empty",
+        serialize_with = "source_location::list",
+        deserialize_with = "source_location::deserialize_list"
+    )]
+    provenance: Vec<Location>,
 }
 
 impl Connection {
-    fn new(left: InstanceRef, right: InstanceRef) -> Self {
-        Self { left, right }
+    fn new(left: InstanceRef, right: InstanceRef, location: Location) -> Self {
+        Self {
+            left,
+            right,
+            location,
+            provenance: Vec::new(),
+        }
+    }
+
+    /// This connection between other instances, copied into place through `via`.
+    fn transpose(&self, left: InstanceRef, right: InstanceRef, via: &Location) -> Self {
+        let mut provenance = self.provenance.clone();
+        provenance.push(via.clone());
+        Self {
+            left,
+            right,
+            location: self.location.clone(),
+            provenance,
+        }
+    }
+}
+
+/// Serializers for the source locations of instances and connections. Unlike a plain
+/// [`Location`], these include the file, as an instance can be declared in a different file
+/// than the root module it belongs to.
+mod source_location {
+    use std::{collections::HashMap, path::PathBuf};
+
+    use atopile_parser::parser::Symbol;
+    use serde::{ser::SerializeStruct, Deserialize, Deserializer, Serialize, Serializer};
+
+    use crate::{Location, Range};
+
+    struct SourceLocation<'a>(&'a Location);
+
+    /// A location as serialized by [`SourceLocation`].
+    #[derive(Deserialize)]
+    struct OwnedSourceLocation {
+        file: PathBuf,
+        range: Range,
+    }
+
+    impl From<OwnedSourceLocation> for Location {
+        fn from(location: OwnedSourceLocation) -> Self {
+            Location {
+                file: location.file,
+                range: location.range,
+            }
+        }
+    }
+
+    impl Serialize for SourceLocation<'_> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            let mut location = serializer.serialize_struct("SourceLocation", 2)?;
+            location.serialize_field("file", &self.0.file)?;
+            location.serialize_field("range", &self.0.range)?;
+            location.end()
+        }
+    }
+
+    pub(super) fn one<S: Serializer>(
+        location: &Location,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        SourceLocation(location).serialize(serializer)
+    }
+
+    pub(super) fn option<S: Serializer>(
+        location: &Option<Location>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        location.as_ref().map(SourceLocation).serialize(serializer)
+    }
+
+    pub(super) fn list<S: Serializer>(
+        locations: &[Location],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(locations.iter().map(SourceLocation))
+    }
+
+    pub(super) fn map<S: Serializer>(
+        locations: &HashMap<Symbol, Location>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(
+            locations
+                .iter()
+                .map(|(name, location)| (name, SourceLocation(location))),
+        )
+    }
+
+    pub(super) fn deserialize_one<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Location, D::Error> {
+        OwnedSourceLocation::deserialize(deserializer).map(Location::from)
+    }
+
+    pub(super) fn deserialize_list<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<Location>, D::Error> {
+        let locations = Vec::<OwnedSourceLocation>::deserialize(deserializer)?;
+        Ok(locations.into_iter().map(Location::from).collect())
     }
 }
 
@@ -415,15 +623,17 @@ pub struct EvaluatorError {
     pub kind: EvaluatorErrorKind,
     pub location: Location,
     pub message: Option<String>,
+    pub details: Box<DiagnosticDetails>,
 }
 
 impl EvaluatorError {
-    fn new(kind: EvaluatorErrorKind, location: &Location) -> Self {
+    pub(crate) fn new(kind: EvaluatorErrorKind, location: &Location) -> Self {
         debug!("Creating EvaluatorError: {:?} @ {:?}", kind, location);
         Self {
             kind,
             location: location.clone(),
             message: None,
+            details: Box::default(),
         }
     }
 
@@ -439,7 +649,7 @@ impl EvaluatorError {
         .with_message(message)
     }
 
-    fn with_message(mut self, message: String) -> Self {
+    pub(crate) fn with_message(mut self, message: String) -> Self {
         debug!(
             "Adding message to EvaluatorError: {:?} @ {:?} = {}",
             self.kind, self.location, message
@@ -447,6 +657,18 @@ impl EvaluatorError {
         self.message = Some(message);
         self
     }
+
+    fn with_related(mut self, location: &Location, message: String) -> Self {
+        self.details
+            .related
+            .push(RelatedLocation::new(location, message));
+        self
+    }
+
+    fn with_fix(mut self, fix: Fix) -> Self {
+        self.details.fixes.push(fix);
+        self
+    }
 }
 
 #[derive(Debug, Clone, Error)]
@@ -467,12 +689,36 @@ pub enum EvaluatorErrorKind {
     InvalidAssignment,
     #[error("invalid connection")]
     InvalidConnection,
-    #[error("parse error")]
-    ParseError,
     #[error("duplicate declaration")]
     DuplicateDeclaration,
     #[error("cyclic inheritance detected")]
     CyclicInheritance,
+    #[error("failed to parse netlist")]
+    NetlistParseError,
+    #[error("invalid physical value")]
+    InvalidUnit,
+    #[error("invalid expression")]
+    InvalidExpression,
+    #[error("assertion failed")]
+    AssertionFailed,
+    #[error("dimension mismatch")]
+    DimensionMismatch,
+    #[error("undeclared attribute")]
+    UndeclaredAttribute,
+    #[error("missing attribute")]
+    MissingAttribute,
+    #[error("interface mismatch")]
+    InterfaceMismatch,
+    #[error("invalid project configuration")]
+    InvalidProjectConfig,
+    #[error("build entry point not found")]
+    BuildEntryNotFound,
+    #[error("unused module")]
+    UnusedModule,
+    #[error("package not found")]
+    PackageNotFound,
+    #[error("package version mismatch")]
+    PackageVersionMismatch,
 
     #[error("internal error")]
     Internal,
@@ -480,6 +726,47 @@ pub enum EvaluatorErrorKind {
 
 type EvaluatorResult<T> = Result<T, EvaluatorError>;
 
+fn conflicting_declaration(
+    name: &Symbol,
+    existing: &AttributeDeclaration,
+    location: &Location,
+) -> EvaluatorError {
+    EvaluatorError::new(EvaluatorErrorKind::DuplicateDeclaration, location)
+        .with_message(format!(
+            "`{}` is already declared as `{}`",
+            name,
+            existing.type_info()
+        ))
+        .with_related(
+            existing.location(),
+            format!("`{}` is first declared here", name),
+        )
+}
+
+/// A `TypeNotFound` error for `name`, suggesting the closest type in scope.
+fn type_not_found(
+    source: &AtopileSource,
+    file_scope: &FileScope,
+    name: &Spanned<Symbol>,
+) -> EvaluatorError {
+    let location = name.span().to_location(source);
+    let error = EvaluatorError::new(EvaluatorErrorKind::TypeNotFound, &location);
+    match closest_match(name.as_str(), file_scope.names().map(|s| s.as_str())) {
+        Some(suggestion) => error
+            .with_message(format!(
+                "`{}` is not defined; did you mean `{}`?",
+                name.deref(),
+                suggestion
+            ))
+            .with_fix(Fix::replace(
+                format!("Replace with `{}`", suggestion),
+                &location,
+                suggestion.to_string(),
+            )),
+        None => error.with_message(format!("`{}` is not defined", name.deref())),
+    }
+}
+
 trait ResultExt<T, E, U> {
     fn with_context(
         self,
@@ -498,35 +785,11 @@ impl<T, E: std::fmt::Display, U> ResultExt<T, E, U> for Result<T, E> {
     ) -> EvaluatorResult<T> {
         self.map_err(|e| {
             let message = e.to_string();
-            EvaluatorError {
-                kind: kind(e),
-                location: spanned.span().to_location(source),
-                message: Some(message),
-            }
+            EvaluatorError::new(kind(e), &spanned.span().to_location(source)).with_message(message)
         })
     }
 }
 
-struct FileScope {
-    symbols: HashMap<Symbol, ModuleRef>,
-}
-
-impl FileScope {
-    fn new() -> Self {
-        Self {
-            symbols: HashMap::new(),
-        }
-    }
-
-    fn define(&mut self, symbol: &Symbol, module_ref: &ModuleRef) {
-        self.symbols.insert(symbol.clone(), module_ref.clone());
-    }
-
-    fn resolve(&self, symbol: &Symbol) -> Option<&ModuleRef> {
-        self.symbols.get(symbol)
-    }
-}
-
 impl<T, U> ResultExt<T, (), U> for Option<T> {
     fn with_context(
         self,
@@ -541,16 +804,22 @@ impl<T, U> ResultExt<T, (), U> for Option<T> {
 /// Resolve an import `import_path` relative to current path `ctx_path`. We check these paths
 /// in order of precedence:
 /// 1. Relative to the folder of `ctx_path`
-/// 2. Relative to the project root (marked by ato.yaml)
-/// 3. Relative to .ato/modules in the project root.
+/// 2. Within the package, if the import is qualified with a dependency declared in the
+///    project's ato.yaml. Such an import never resolves anywhere else.
+/// 3. Relative to the project root (marked by ato.yaml)
+/// 4. Relative to .ato/modules in the project root.
 ///
 /// The "project root" is determined by these rules:
 ///  - If the `ctx_path` is in a `.ato` directory, the parent of the `.ato` directory is the
 ///    project root.
 ///  - Otherwise, walk up the tree until a directory containing `ato.yaml` is found.
 ///
-/// The returned path is always canonicalized.
-pub(crate) fn resolve_import_path(ctx_path: &Path, import_path: &Path) -> Option<PathBuf> {
+/// The returned path is always canonicalized by `db`.
+pub(crate) fn resolve_import_path(
+    db: &Database<impl Vfs>,
+    ctx_path: &Path,
+    import_path: &Path,
+) -> Option<PathBuf> {
     if import_path.is_absolute() {
         return Some(import_path.to_path_buf());
     }
@@ -558,64 +827,86 @@ pub(crate) fn resolve_import_path(ctx_path: &Path, import_path: &Path) -> Option
     // 1. Check relative to the folder of ctx_path
     if let Some(parent) = ctx_path.parent() {
         let relative_path = parent.join(import_path);
-        if let Ok(path) = relative_path.canonicalize() {
+        if let Ok(path) = db.canonicalize(&relative_path) {
             return Some(path);
         }
     }
 
-    // 2. If we're in a .ato folder, use its parent as project root
-    let mut current_dir = ctx_path.parent();
-    while let Some(dir) = current_dir {
-        if dir.file_name().is_some_and(|name| name == ".ato") {
-            if let Some(project_root) = dir.parent() {
-                // Check relative to project root
-                let project_relative = project_root.join(import_path);
-                if let Ok(path) = project_relative.canonicalize() {
-                    return Some(path);
-                }
-
-                // Check in .ato/modules
-                let modules_path = project_root.join(".ato").join("modules").join(import_path);
-                if let Ok(path) = modules_path.canonicalize() {
-                    return Some(path);
-                }
-            }
-            break;
-        }
-        current_dir = dir.parent();
+    // 2. Check in the package the import is qualified with
+    if let Some(package) = db.package_import(ctx_path, import_path) {
+        return db.canonicalize(&package.path).ok();
     }
 
-    // 3. Walk up the tree to find project root (marked by ato.yaml)
-    let mut current_dir = ctx_path.parent();
-    while let Some(dir) = current_dir {
-        if dir.join("ato.yaml").exists() {
-            // Found project root, check if import exists relative to it
-            let project_relative = dir.join(import_path);
-            if let Ok(path) = project_relative.canonicalize() {
-                return Some(path);
-            }
+    // 3. and 4. Relative to the project root, and to its .ato/modules
+    import_search_roots(db, ctx_path)
+        .iter()
+        .find_map(|root| db.canonicalize(&root.join(import_path)).ok())
+}
 
-            // Check in .ato/modules
-            let modules_path = dir.join(".ato").join("modules").join(import_path);
-            if let Ok(path) = modules_path.canonicalize() {
-                return Some(path);
-            }
+/// The directories an import from `ctx_path` is looked up in when it isn't relative to the
+/// folder of `ctx_path` or qualified with a package: each project root, as described for
+/// [`resolve_import_path`], followed by its `.ato/modules`.
+pub(crate) fn import_search_roots(db: &Database<impl Vfs>, ctx_path: &Path) -> Vec<PathBuf> {
+    let mut project_roots = vec![];
+
+    // If we're in a .ato folder, use its parent as project root
+    if let Some(dir) = ctx_path
+        .ancestors()
+        .skip(1)
+        .find(|dir| dir.file_name().is_some_and(|name| name == ".ato"))
+    {
+        project_roots.extend(dir.parent());
+    }
+
+    // Walk up the tree to find the project root (marked by ato.yaml), stopping at the first
+    // one found
+    if let Some(dir) = ctx_path
+        .ancestors()
+        .skip(1)
+        .find(|dir| db.is_file(&dir.join("ato.yaml")))
+    {
+        project_roots.push(dir);
+    }
+
+    project_roots
+        .into_iter()
+        .flat_map(|root| [root.to_path_buf(), root.join(".ato").join("modules")])
+        .collect()
+}
 
-            // If we found the project root but couldn't find the import,
-            // break to avoid walking up further
-            break;
+/// The path an import that couldn't be resolved would refer to if it existed, relative to the
+/// folder of `ctx_path`.
+pub(crate) fn unresolved_import_path(ctx_path: &Path, import_path: &Path) -> PathBuf {
+    normalize(
+        &ctx_path
+            .parent()
+            .map_or_else(|| import_path.to_path_buf(), |dir| dir.join(import_path)),
+    )
+}
+
+impl<V: Vfs> Evaluator<V> {
+    /// Create an evaluator that reads sources which haven't been set from `vfs`.
+    pub fn with_vfs(vfs: V) -> Self {
+        Self {
+            state: EvaluatorState::new(),
+            reporter: AnalyzerReporter::default(),
+            db: Database::new(vfs),
+            visited_files: HashSet::new(),
+            graph: FileGraph::default(),
+            entry_points: None,
+            used_blocks: HashSet::new(),
+            project: None,
         }
-        current_dir = dir.parent();
     }
 
-    None
-}
-
-impl Evaluator {
     pub fn reset(&mut self) {
         self.state = EvaluatorState::new();
         self.reporter.reset();
         self.visited_files.clear();
+        self.graph = FileGraph::default();
+        self.entry_points = None;
+        self.used_blocks.clear();
+        self.project = None;
     }
 
     pub fn reporter(&self) -> &AnalyzerReporter {
@@ -643,10 +934,13 @@ impl Evaluator {
         self.state.instances.remove(instance_ref)
     }
 
+    /// Copy the instance `from_ref` and everything under it to `to_ref`, through the statement
+    /// at `via`.
     fn clone_instance(
         &mut self,
         from_ref: &InstanceRef,
         to_ref: &InstanceRef,
+        via: &Location,
     ) -> anyhow::Result<()> {
         debug!("Cloning instance from {} to {}", from_ref, to_ref);
         let (mut to_instance, children, connections) = {
@@ -660,6 +954,13 @@ impl Evaluator {
             let mut to_instance = Instance::new(&from_instance.type_ref, from_instance.kind);
 
             to_instance.attributes = from_instance.attributes.clone();
+            to_instance.declarations = from_instance.declarations.clone();
+            to_instance.location = from_instance.location.clone();
+            to_instance.provenance = from_instance.provenance.clone();
+            to_instance.provenance.push(via.clone());
+            to_instance.assignments = from_instance.assignments.clone();
+            to_instance.asserts = from_instance.asserts.clone();
+            to_instance.dimensions = from_instance.dimensions.clone();
             (
                 to_instance,
                 from_instance.children.clone(),
@@ -677,7 +978,7 @@ impl Evaluator {
             let mut path = to_ref.instance_path.clone();
             path.push(k.clone());
             let transposed_ref = InstanceRef::new(&to_ref.module, path);
-            self.clone_instance(v, &transposed_ref)?;
+            self.clone_instance(v, &transposed_ref, via)?;
             to_instance.add_child(k, &transposed_ref);
         }
 
@@ -717,7 +1018,7 @@ impl Evaluator {
                 [to_ref.instance_path.clone(), right_relative_path].concat(),
             );
 
-            let connection = Connection::new(new_left.clone(), new_right.clone());
+            let connection = connection.transpose(new_left.clone(), new_right.clone(), via);
             to_instance.connections.push(connection);
         }
 
@@ -760,32 +1061,11 @@ impl Evaluator {
                 vec![Connection::new(
                     source.deref().clone(),
                     target.deref().clone(),
+                    assignment.location().clone(),
                 )]
             }
             (InstanceKind::Interface, InstanceKind::Interface) => {
-                if left_instance.type_ref != right_instance.type_ref {
-                    return Err(EvaluatorError::new(
-                        EvaluatorErrorKind::InvalidAssignment,
-                        assignment.location(),
-                    )
-                    .with_message(format!(
-                        "cannot connect interfaces of different type: `{}` and `{}`",
-                        left_instance.type_ref, right_instance.type_ref
-                    )));
-                }
-
-                let mut left_sorted: Vec<_> = left_instance.children.iter().collect();
-                let mut right_sorted: Vec<_> = right_instance.children.iter().collect();
-
-                // Sort by the key (Symbol)
-                left_sorted.sort_by(|a, b| a.0.cmp(b.0));
-                right_sorted.sort_by(|a, b| a.0.cmp(b.0));
-
-                left_sorted
-                    .into_iter()
-                    .zip(right_sorted)
-                    .map(|((_, l), (_, r))| Connection::new(l.clone(), r.clone()))
-                    .collect()
+                self.connect_interfaces(source, target, assignment.location())?
             }
             _ => {
                 return Err(EvaluatorError::new(
@@ -850,14 +1130,10 @@ impl Evaluator {
         source_ref: &InstanceRef,
         location: &Location,
     ) -> EvaluatorResult<()> {
-        debug!(
-            "Merging instance {} into {}",
-            source_ref.to_string(),
-            target_ref.to_string()
-        );
+        debug!("Merging instance {} into {}", source_ref, target_ref);
 
         // First collect all the data we need from the source instance
-        let (source_children, source_connections) = {
+        let (source_children, source_connections, source_asserts) = {
             let source_instance = self.resolve_instance(source_ref).ok_or_else(|| {
                 EvaluatorError::internal(
                     location,
@@ -867,6 +1143,7 @@ impl Evaluator {
             (
                 source_instance.children.clone(),
                 source_instance.connections.clone(),
+                source_instance.asserts.clone(),
             )
         };
 
@@ -904,7 +1181,7 @@ impl Evaluator {
                 let target_child_ref = InstanceRef::new(&target_ref.module, target_child_path);
 
                 // Clone the source child into the target's tree
-                self.clone_instance(source_child_ref, &target_child_ref)
+                self.clone_instance(source_child_ref, &target_child_ref, location)
                     .map_err(|e| {
                         EvaluatorError::internal(
                             location,
@@ -952,31 +1229,65 @@ impl Evaluator {
             // Add the transposed connection to the target instance
             target_instance
                 .connections
-                .push(Connection::new(new_left, new_right));
+                .push(connection.transpose(new_left, new_right, location));
         }
 
+        // The specialized type's asserts now apply to the target as well.
+        target_instance.asserts.extend(source_asserts);
+
         // Update target instance's type ref after processing children and connections
         target_instance.type_ref = source_type_ref;
+        target_instance.provenance.push(location.clone());
 
         Ok(())
     }
 
-    fn get_or_load_source(&mut self, path: &PathBuf) -> anyhow::Result<Arc<AtopileSource>> {
-        if let Some(source) = self.files.get(path) {
-            Ok(source.clone())
-        } else {
-            let content = std::fs::read_to_string(path)?;
-            let source = Arc::new(AtopileSource::new(content, path.to_path_buf()));
-            self.files.insert(path.to_path_buf(), source.clone());
-            Ok(source)
+    /// Check that the package an import is qualified with is installed, in a version the
+    /// project accepts.
+    fn check_package(
+        &mut self,
+        source: &AtopileSource,
+        import_path: &Spanned<String>,
+        package: &PackageImport,
+    ) -> EvaluatorResult<()> {
+        let location = import_path.span().to_location(source);
+        let dependency = &package.dependency;
+        if !self.db.is_installed(package) {
+            // Re-evaluate this file if the package is installed later.
+            self.graph.add_import(source.path(), &package.path);
+            return Err(
+                EvaluatorError::new(EvaluatorErrorKind::PackageNotFound, &location).with_message(
+                    format!(
+                        "package `{}` is not installed in `{}`",
+                        dependency.name,
+                        package.root.display()
+                    ),
+                ),
+            );
+        }
+
+        if let Some(version) = self.db.installed_version(package) {
+            if !dependency.accepts(&version) {
+                self.reporter.report(
+                    EvaluatorError::new(EvaluatorErrorKind::PackageVersionMismatch, &location)
+                        .with_message(format!(
+                            "package `{}` is installed at version {}, but `{}` is required",
+                            dependency.name,
+                            version,
+                            dependency.version_spec.as_deref().unwrap_or_default()
+                        ))
+                        .into(),
+                );
+            }
         }
+
+        Ok(())
     }
 
     fn evaluate_import(
         &mut self,
         source: &AtopileSource,
         import_stack: &[PathBuf],
-        file_scope: &mut FileScope,
         import_path: &Spanned<String>,
         import_symbols: &[Spanned<Symbol>],
     ) -> EvaluatorResult<()> {
@@ -986,16 +1297,22 @@ impl Evaluator {
             import_symbols.len()
         );
         debug!("Import stack depth: {}", import_stack.len());
+        if let Some(package) = self
+            .db
+            .package_import(source.path(), Path::new(import_path.deref()))
+        {
+            self.check_package(source, import_path, &package)?;
+        }
+
         // Fast path: check if we already evaluated this module.
         let mut load_file = false;
         for symbol in import_symbols {
             if let Some(resolved_path) =
-                resolve_import_path(source.path(), Path::new(import_path.deref()))
+                resolve_import_path(&self.db, source.path(), Path::new(import_path.deref()))
             {
+                self.graph.add_import(source.path(), &resolved_path);
                 let module_ref = ModuleRef::new(&resolved_path, symbol.deref());
-                if let Some(instance) = self.resolve_instance(&module_ref.into()) {
-                    file_scope.define(symbol.deref(), &instance.type_ref);
-                } else {
+                if self.resolve_instance(&module_ref.into()).is_none() {
                     load_file = true;
                 }
             } else {
@@ -1008,12 +1325,21 @@ impl Evaluator {
         }
 
         // Resolve the import path.
-        let path = resolve_import_path(source.path(), Path::new(import_path.deref()))
-            .with_context(
-                source,
-                |_| EvaluatorErrorKind::ImportPathNotFound,
-                import_path,
-            )?;
+        let resolved_path =
+            resolve_import_path(&self.db, source.path(), Path::new(import_path.deref()));
+        if resolved_path.is_none() {
+            // Re-evaluate this file if the missing file is created later.
+            self.graph.add_import(
+                source.path(),
+                &unresolved_import_path(source.path(), Path::new(import_path.deref())),
+            );
+        }
+        let path = resolved_path.with_context(
+            source,
+            |_| EvaluatorErrorKind::ImportPathNotFound,
+            import_path,
+        )?;
+        self.graph.add_import(source.path(), &path);
 
         // Check for cycles.
         if import_stack.iter().any(|p| p == &path) {
@@ -1024,7 +1350,7 @@ impl Evaluator {
         }
 
         // Load and evaluate the imported module.
-        let imported_source = self.get_or_load_source(&path).with_context(
+        let imported_source = self.db.source(&path).with_context(
             source,
             |_| EvaluatorErrorKind::ImportLoadFailed,
             import_path,
@@ -1035,20 +1361,29 @@ impl Evaluator {
 
         self.evaluate_inner(&imported_source, import_stack_vec);
 
-        // Define the imported symbols.
+        // The imported symbols are defined by the file scope, as long as the file declares them.
+        let declarations = self.db.block_declarations(&path);
         for imported_symbol in import_symbols {
-            let instance_ref = ModuleRef::new(&path, imported_symbol.deref()).into();
-
-            if let Some(instance) = self.resolve_instance(&instance_ref) {
-                file_scope.define(imported_symbol.deref(), &instance.type_ref);
-            } else {
-                self.reporter.report(
-                    EvaluatorError::new(
-                        EvaluatorErrorKind::ImportNotFound,
-                        &imported_symbol.span().to_location(source),
-                    )
-                    .into(),
-                );
+            if declarations.get(imported_symbol).is_none() {
+                let location = imported_symbol.span().to_location(source);
+                let error = EvaluatorError::new(EvaluatorErrorKind::ImportNotFound, &location);
+                let blocks = declarations.blocks.iter().map(|block| block.name.as_str());
+                let error = match closest_match(imported_symbol.as_str(), blocks) {
+                    Some(suggestion) => error
+                        .with_message(format!(
+                            "`{}` does not define `{}`; did you mean `{}`?",
+                            import_path.deref(),
+                            imported_symbol.deref(),
+                            suggestion
+                        ))
+                        .with_fix(Fix::replace(
+                            format!("Replace with `{}`", suggestion),
+                            &location,
+                            suggestion.to_string(),
+                        )),
+                    None => error,
+                };
+                self.reporter.report(error.into());
             }
         }
 
@@ -1096,12 +1431,9 @@ impl Evaluator {
 
                         // Get a reference to the module that we're creating.
                         let child_name = assign.target.deref().parts.last().unwrap();
-                        let type_module_ref = file_scope.resolve(type_name).ok_or_else(|| {
-                            EvaluatorError::new(
-                                EvaluatorErrorKind::TypeNotFound,
-                                &type_name.span().to_location(source),
-                            )
-                        })?;
+                        let type_module_ref = file_scope
+                            .resolve(type_name)
+                            .ok_or_else(|| type_not_found(source, file_scope, type_name))?;
 
                         // Cannot create a child that already exists.
                         if self.resolve_instance(&target_ref).is_some() {
@@ -1113,7 +1445,8 @@ impl Evaluator {
                         }
 
                         // Create the child instance.
-                        self.clone_instance(&type_module_ref.into(), &target_ref)
+                        let location = stmt.span().to_location(source);
+                        self.clone_instance(&type_module_ref.into(), &target_ref, &location)
                             .map_err(|e| {
                                 EvaluatorError::internal(
                                     &assign.target.span().to_location(source),
@@ -1124,10 +1457,16 @@ impl Evaluator {
                                 )
                             })?;
 
+                        // The child itself was created here, rather than copied from its type.
+                        if let Some(child) = self.resolve_instance_mut(&target_ref) {
+                            child.location = Some(location);
+                            child.provenance.clear();
+                        }
+
                         instance.add_child(&child_name.clone().deref().deref().into(), &target_ref);
                     }
                     _ => {
-                        // Handle attribute assignment using the new From<Expr> implementation
+                        // Handle attribute assignment, folding any arithmetic in the value.
                         let attr_name = target_ref.pop().ok_or_else(|| {
                             EvaluatorError::new(
                                 EvaluatorErrorKind::InvalidAssignment,
@@ -1136,20 +1475,85 @@ impl Evaluator {
                             .with_message("Cannot assign attribute to top-level module".to_string())
                         })?;
 
-                        let attr_value: AttributeValue = assign.value.deref().into();
+                        let attr_value = self.evaluate_expr(
+                            source,
+                            instance,
+                            &module_ref.into(),
+                            &assign.value,
+                        )?;
 
-                        if target_ref.len() == 0 {
-                            instance.add_attribute(&attr_name, attr_value);
+                        let found = attr_value.dimension();
+                        let target_instance = if target_ref.len() == 0 {
+                            instance
                         } else {
-                            let target_instance =
-                                self.resolve_instance_mut(&target_ref).ok_or_else(|| {
-                                    EvaluatorError::new(
-                                        EvaluatorErrorKind::InvalidAssignment,
-                                        &assign.value.span().to_location(source),
+                            self.state.instances.get_mut(&target_ref).ok_or_else(|| {
+                                EvaluatorError::new(
+                                    EvaluatorErrorKind::InvalidAssignment,
+                                    &assign.value.span().to_location(source),
+                                )
+                            })?
+                        };
+
+                        if target_ref.len() > 0 && !target_instance.accepts_attribute(&attr_name) {
+                            let error = EvaluatorError::new(
+                                EvaluatorErrorKind::UndeclaredAttribute,
+                                &assign.target.span().to_location(source),
+                            )
+                            .with_message(format!(
+                                "`{}` does not declare `{}`",
+                                target_instance.type_ref.module_name, attr_name
+                            ));
+                            let suggestion = closest_match(
+                                attr_name.as_str(),
+                                target_instance.declarations.keys().map(|s| s.as_str()),
+                            );
+                            return Err(match (suggestion, assign.target.parts.last()) {
+                                (Some(suggestion), Some(part)) => error.with_fix(Fix::replace(
+                                    format!("Replace with `{}`", suggestion),
+                                    &part.span().to_location(source),
+                                    suggestion.to_string(),
+                                )),
+                                _ => error,
+                            });
+                        }
+
+                        // `v: voltage = 5V` declares the attribute along with its value.
+                        if let Some(type_info) = &assign.type_info {
+                            let declaration = AttributeDeclaration::new(
+                                type_info.deref().as_str().into(),
+                                stmt.span().to_location(source),
+                            );
+                            target_instance
+                                .declare_attribute(&attr_name, declaration)
+                                .map_err(|existing| {
+                                    conflicting_declaration(
+                                        &attr_name,
+                                        existing,
+                                        &type_info.span().to_location(source),
                                     )
                                 })?;
+                        }
 
-                            target_instance.add_attribute(&attr_name, attr_value);
+                        // A value of the wrong dimension is still assigned, but flagged.
+                        if let (Some(expected), Some(found)) = (
+                            target_instance.assign_attribute(
+                                &attr_name,
+                                attr_value,
+                                &stmt.span().to_location(source),
+                            ),
+                            found,
+                        ) {
+                            self.reporter.report(
+                                EvaluatorError::new(
+                                    EvaluatorErrorKind::DimensionMismatch,
+                                    &assign.value.span().to_location(source),
+                                )
+                                .with_message(format!(
+                                    "`{}` expects a {}, found a {}",
+                                    attr_name, expected, found
+                                ))
+                                .into(),
+                            );
                         }
                     }
                 }
@@ -1159,7 +1563,7 @@ impl Evaluator {
                 debug!("Processing signal statement: {}", signal.name.deref());
                 let signal_name = signal.name.deref();
                 let signal_ref = InstanceRef::new(module_ref, vec![signal_name.clone()]);
-                self.add_instance(&signal_ref, Instance::port());
+                self.add_instance(&signal_ref, Instance::port(stmt.span().to_location(source)));
                 instance.add_child(signal_name, &signal_ref);
                 Ok(())
             }
@@ -1167,7 +1571,7 @@ impl Evaluator {
                 debug!("Processing pin statement: {}", pin.name.deref());
                 let pin_name = pin.name.deref();
                 let pin_ref = InstanceRef::new(module_ref, vec![pin_name.clone()]);
-                self.add_instance(&pin_ref, Instance::pin());
+                self.add_instance(&pin_ref, Instance::pin(stmt.span().to_location(source)));
                 instance.add_child(pin_name, &pin_ref);
                 Ok(())
             }
@@ -1182,7 +1586,10 @@ impl Evaluator {
                         let signal_symbol: Symbol = signal.deref().clone().into();
                         let instance_ref =
                             InstanceRef::new(module_ref, vec![signal_symbol.clone()]);
-                        self.add_instance(&instance_ref, Instance::port());
+                        self.add_instance(
+                            &instance_ref,
+                            Instance::port(signal.span().to_location(source)),
+                        );
                         instance.add_child(&signal_symbol, &instance_ref);
                         Some(instance_ref)
                     }
@@ -1197,7 +1604,10 @@ impl Evaluator {
                     Connectable::Pin(pin) => {
                         let pin_symbol: Symbol = pin.deref().clone().into();
                         let instance_ref = InstanceRef::new(module_ref, vec![pin_symbol.clone()]);
-                        self.add_instance(&instance_ref, Instance::pin());
+                        self.add_instance(
+                            &instance_ref,
+                            Instance::pin(pin.span().to_location(source)),
+                        );
                         instance.add_child(&pin_symbol, &instance_ref);
                         Some(instance_ref)
                     }
@@ -1208,7 +1618,10 @@ impl Evaluator {
                         let signal_symbol: Symbol = signal.deref().clone().into();
                         let instance_ref =
                             InstanceRef::new(module_ref, vec![signal_symbol.clone()]);
-                        self.add_instance(&instance_ref, Instance::port());
+                        self.add_instance(
+                            &instance_ref,
+                            Instance::port(signal.span().to_location(source)),
+                        );
                         instance.add_child(&signal_symbol, &instance_ref);
                         Some(instance_ref)
                     }
@@ -1223,7 +1636,10 @@ impl Evaluator {
                     Connectable::Pin(pin) => {
                         let pin_symbol: Symbol = pin.deref().clone().into();
                         let instance_ref = InstanceRef::new(module_ref, vec![pin_symbol.clone()]);
-                        self.add_instance(&instance_ref, Instance::pin());
+                        self.add_instance(
+                            &instance_ref,
+                            Instance::pin(pin.span().to_location(source)),
+                        );
                         instance.add_child(&pin_symbol, &instance_ref);
                         Some(instance_ref)
                     }
@@ -1255,12 +1671,9 @@ impl Evaluator {
                 );
 
                 // Get a reference to the module type we're specializing to
-                let source_module_ref = file_scope.resolve(&specialize.value).ok_or_else(|| {
-                    EvaluatorError::new(
-                        EvaluatorErrorKind::TypeNotFound,
-                        &specialize.value.span().to_location(source),
-                    )
-                })?;
+                let source_module_ref = file_scope
+                    .resolve(&specialize.value)
+                    .ok_or_else(|| type_not_found(source, file_scope, &specialize.value))?;
 
                 // Merge the source module into the target instance
                 self.merge_instance(
@@ -1271,6 +1684,33 @@ impl Evaluator {
 
                 Ok(())
             }
+            Stmt::Attribute(attribute) => {
+                debug!("Processing attribute statement: {}", attribute.name.deref());
+                let declaration = AttributeDeclaration::new(
+                    attribute.type_info.deref().clone(),
+                    stmt.span().to_location(source),
+                );
+                instance
+                    .declare_attribute(&attribute.name, declaration)
+                    .map_err(|existing| {
+                        conflicting_declaration(
+                            &attribute.name,
+                            existing,
+                            &attribute.type_info.span().to_location(source),
+                        )
+                    })
+            }
+            // Reported by `report_parse_errors`.
+            Stmt::ParseError(_) => Ok(()),
+            Stmt::Assert(assert) => {
+                debug!("Recording assert statement");
+                instance.asserts.push(Assertion::new(
+                    source,
+                    stmt.span().to_location(source),
+                    assert.expr.clone(),
+                ));
+                Ok(())
+            }
             _ => {
                 debug!("Skipping unhandled statement type");
                 Ok(())
@@ -1278,11 +1718,13 @@ impl Evaluator {
         }
     }
 
+    /// Evaluate the block `block`, declared by the statement at `location`.
     fn evaluate_block(
         &mut self,
         source: &AtopileSource,
-        file_scope: &mut FileScope,
+        file_scope: &FileScope,
         block: &BlockStmt,
+        location: &Location,
     ) -> EvaluatorResult<()> {
         let module_ref = ModuleRef::new(source.path(), block.name.deref());
         let instance_kind = match block.kind.deref() {
@@ -1290,25 +1732,30 @@ impl Evaluator {
             BlockKind::Component => InstanceKind::Component,
             BlockKind::Interface => InstanceKind::Interface,
         };
-
-        if let Some(parent) = &block.parent {
-            let parent_module_ref = file_scope.resolve(parent).ok_or_else(|| {
-                EvaluatorError::new(
-                    EvaluatorErrorKind::TypeNotFound,
+        self.graph.add_module(source.path(), &module_ref);
+
+        let parent_module_ref = if let Some(parent) = &block.parent {
+            let parent_module_ref = file_scope
+                .resolve(parent)
+                .ok_or_else(|| type_not_found(source, file_scope, parent))?
+                .clone();
+
+            self.clone_instance(
+                &(&parent_module_ref).into(),
+                &module_ref.clone().into(),
+                &parent.span().to_location(source),
+            )
+            .map_err(|_| {
+                EvaluatorError::internal(
                     &parent.span().to_location(source),
+                    "Failed to clone parent module".to_string(),
                 )
             })?;
-
-            self.clone_instance(&parent_module_ref.into(), &module_ref.clone().into())
-                .map_err(|_| {
-                    EvaluatorError::internal(
-                        &parent.span().to_location(source),
-                        "Failed to clone parent module".to_string(),
-                    )
-                })?;
+            Some(parent_module_ref)
         } else {
             let new_instance = Instance::new(&module_ref, instance_kind);
             self.add_instance(&module_ref.clone().into(), new_instance);
+            None
         };
 
         // Remove the instance so we can tinker with it before putting it back.
@@ -1319,11 +1766,13 @@ impl Evaluator {
                 &block.name.span().to_location(source),
             )
         })?;
+        instance.parent = parent_module_ref;
+        instance.location = Some(location.clone());
+        instance.provenance.clear();
 
         self.evaluate_block_on_instance(source, file_scope, block, &mut instance)?;
 
         self.add_instance(&instance_ref, instance);
-        file_scope.define(block.name.deref(), &module_ref);
 
         Ok(())
     }
@@ -1359,7 +1808,7 @@ impl Evaluator {
         &mut self,
         source: &AtopileSource,
         import_stack: &[PathBuf],
-        file_scope: &mut FileScope,
+        file_scope: &FileScope,
         stmt: &Spanned<Stmt>,
     ) -> EvaluatorResult<()> {
         debug!("Evaluating top-level statement");
@@ -1369,13 +1818,7 @@ impl Evaluator {
                     "Processing import statement from: {}",
                     import.from_path.deref()
                 );
-                self.evaluate_import(
-                    source,
-                    import_stack,
-                    file_scope,
-                    &import.from_path,
-                    &import.imports,
-                )
+                self.evaluate_import(source, import_stack, &import.from_path, &import.imports)
             }
             Stmt::DepImport(dep_import) => {
                 debug!(
@@ -1385,27 +1828,17 @@ impl Evaluator {
                 self.evaluate_import(
                     source,
                     import_stack,
-                    file_scope,
                     &dep_import.from_path,
-                    &[dep_import.name.clone()],
+                    std::slice::from_ref(&dep_import.name),
                 )
             }
             Stmt::Block(block) => {
                 debug!("Processing block statement: {}", block.name.deref());
-                self.evaluate_block(source, file_scope, block)
+                self.evaluate_block(source, file_scope, block, &stmt.span().to_location(source))
             }
             Stmt::Comment(_) => Ok(()),
-            Stmt::ParseError(err) => {
-                self.reporter.report(
-                    EvaluatorError::new(
-                        EvaluatorErrorKind::ParseError,
-                        &stmt.span().to_location(source),
-                    )
-                    .with_message(err.to_string())
-                    .into(),
-                );
-                Ok(())
-            }
+            // Reported by `report_parse_errors`.
+            Stmt::ParseError(_) => Ok(()),
             _ => Err(EvaluatorError::new(
                 EvaluatorErrorKind::UnexpectedStmt,
                 &stmt.span().to_location(source),
@@ -1413,69 +1846,6 @@ impl Evaluator {
         }
     }
 
-    fn collect_block_declarations(&mut self, source: &AtopileSource) -> Vec<BlockDeclaration> {
-        debug!(
-            "Collecting block declarations from source: {:?}",
-            source.path()
-        );
-        let mut declarations = Vec::new();
-        let mut seen_names = HashMap::new();
-
-        // Helper function to collect dependencies from a block's body
-        fn collect_dependencies(body: &[Spanned<Stmt>]) -> HashSet<Symbol> {
-            let mut deps = HashSet::new();
-            for stmt in body {
-                match stmt.deref() {
-                    // Handle new expressions like: x = new Module
-                    Stmt::Assign(assign) => {
-                        if let Expr::New(type_name) = assign.value.deref() {
-                            deps.insert(type_name.deref().clone());
-                        }
-                    }
-                    // Handle specialize statements like: x.y -> Module
-                    Stmt::Specialize(specialize) => {
-                        deps.insert(specialize.value.deref().clone());
-                    }
-                    // Handle nested blocks to find dependencies in their bodies
-                    Stmt::Block(block) => {
-                        deps.extend(collect_dependencies(&block.body));
-                    }
-                    _ => {}
-                }
-            }
-            deps
-        }
-
-        for stmt in source.ast() {
-            if let Stmt::Block(block) = stmt.deref() {
-                let location = stmt.span().to_location(source);
-                let name = block.name.deref();
-
-                // Check for duplicate declarations
-                if let Some(prev_loc) = seen_names.get(name) {
-                    self.reporter.report(
-                        EvaluatorError::new(EvaluatorErrorKind::DuplicateDeclaration, &location)
-                            .with_message(format!(
-                                "Block '{}' is already declared at {}",
-                                name, prev_loc
-                            ))
-                            .into(),
-                    );
-                    continue;
-                }
-
-                seen_names.insert(name.clone(), location.clone());
-
-                // Create declaration and collect its dependencies
-                let mut declaration = BlockDeclaration::new(block, location);
-                declaration.dependencies = collect_dependencies(&block.body);
-                declarations.push(declaration);
-            }
-        }
-
-        declarations
-    }
-
     fn sort_blocks<'a>(
         &mut self,
         declarations: &'a [BlockDeclaration],
@@ -1552,6 +1922,14 @@ impl Evaluator {
         sorted
     }
 
+    /// Report every syntax error in the file: those from the lexer and parser, and the statements
+    /// the parser skipped, including inside block bodies.
+    fn report_parse_errors(&self, source: &AtopileSource) {
+        for diagnostic in self.db.syntax_diagnostics(source.path()).iter() {
+            self.reporter.report(diagnostic.clone());
+        }
+    }
+
     fn evaluate_inner(&mut self, source: &AtopileSource, import_stack: Vec<PathBuf>) {
         if self.visited_files.contains(source.path()) {
             return;
@@ -1563,50 +1941,159 @@ impl Evaluator {
         debug!("Import stack depth: {}", import_stack.len());
         self.reporter.clear(source.path());
 
-        let mut file_scope = FileScope::new();
+        self.report_parse_errors(source);
 
-        // Phase 1: Collect block declarations
-        let block_declarations = self.collect_block_declarations(source);
+        // Phase 1: Collect block declarations and the names in scope
+        let block_declarations = self.db.block_declarations(source.path());
+        let file_scope = self.db.file_scope(source.path());
+        for (duplicate, first_location) in &block_declarations.duplicates {
+            self.reporter.report(
+                EvaluatorError::new(
+                    EvaluatorErrorKind::DuplicateDeclaration,
+                    &duplicate.location,
+                )
+                .with_message(format!(
+                    "Block '{}' is already declared at {}",
+                    duplicate.name, first_location
+                ))
+                .with_related(
+                    first_location,
+                    format!("'{}' is first declared here", duplicate.name),
+                )
+                .into(),
+            );
+        }
 
         // Phase 2: Sort blocks by inheritance dependencies
-        let sorted_blocks = self.sort_blocks(&block_declarations);
+        let sorted_blocks = self.sort_blocks(&block_declarations.blocks);
 
-        // Phase 3: Pre-register all blocks in scope
-        for block in &block_declarations {
-            let module_ref = ModuleRef::new(source.path(), &block.name);
-            file_scope.define(&block.name, &module_ref);
-        }
-
-        // Phase 4: Process all non-block statements
+        // Phase 3: Process all non-block statements
         for stmt in source.ast() {
             if !matches!(stmt.deref(), Stmt::Block(_)) {
-                if let Err(e) = self.evaluate_top_stmt(source, &import_stack, &mut file_scope, stmt)
-                {
+                if let Err(e) = self.evaluate_top_stmt(source, &import_stack, &file_scope, stmt) {
                     self.reporter.report(e.into());
                 }
             }
         }
 
-        // Phase 5: Evaluate blocks in dependency order
+        // Phase 4: Evaluate blocks in dependency order, leaving out the ones no build uses
+        let only_used = self.elaborates_only_used(source.path());
         for block in sorted_blocks {
-            if let Err(e) = self.evaluate_block(source, &mut file_scope, &block.stmt) {
+            if only_used
+                && !self
+                    .used_blocks
+                    .contains(&ModuleRef::new(source.path(), &block.name))
+            {
+                continue;
+            }
+            if let Err(e) = self.evaluate_block(source, &file_scope, &block.stmt, &block.location) {
                 self.reporter.report(e.into());
             }
         }
     }
 
+    pub fn db(&self) -> &Database<V> {
+        &self.db
+    }
+
     pub fn set_source(&mut self, path: &Path, source: Arc<AtopileSource>) {
-        self.files.insert(path.to_path_buf(), source);
-        self.evaluate();
+        if self.db.set_source(path, source) {
+            self.reevaluate(path);
+        }
     }
 
     pub fn remove_source(&mut self, path: &Path) {
-        self.files.remove(path);
-        self.evaluate();
+        self.db.remove_source(path);
+        self.reevaluate(path);
+    }
+
+    /// Re-evaluate `path` and every file that imports it, keeping the instances and diagnostics
+    /// of all other files.
+    fn reevaluate(&mut self, path: &Path) {
+        debug!("Evaluator re-evaluating {:?}", path);
+        let start = Instant::now();
+
+        // A change to the blocks the builds use can need blocks of files that aren't affected,
+        // so start over.
+        if let Some(entry_points) = &self.entry_points {
+            let used_blocks = self
+                .db
+                .used_blocks(entry_points.iter().map(|(_, module)| module));
+            if used_blocks != self.used_blocks {
+                self.evaluate_roots(entry_points.clone());
+                return;
+            }
+        }
+
+        let affected = self.graph.dependents(path);
+        let mut stale_modules = HashSet::new();
+        for file in &affected {
+            stale_modules.extend(self.graph.remove_file(file));
+            self.visited_files.remove(file);
+            self.reporter.clear(file);
+        }
+        self.state
+            .instances
+            .retain(|instance_ref, _| !stale_modules.contains(&instance_ref.module));
+
+        let sources: Vec<_> = self
+            .db
+            .paths()
+            .into_iter()
+            .filter(|file| affected.contains(file))
+            .filter_map(|file| self.db.source(&file).ok())
+            .collect();
+
+        let visited_before = self.visited_files.clone();
+        for source in sources {
+            self.evaluate_inner(&source, vec![]);
+        }
+        let evaluated = self
+            .visited_files
+            .difference(&visited_before)
+            .cloned()
+            .collect();
+        self.finish_evaluation(&evaluated);
+
+        debug!(
+            "Re-evaluated {} of {} files in {}ms",
+            evaluated.len(),
+            self.visited_files.len(),
+            start.elapsed().as_millis()
+        );
+    }
+
+    /// Bring everything derived from the whole state up to date once `files` have been
+    /// evaluated.
+    fn finish_evaluation(&mut self, files: &HashSet<PathBuf>) {
+        self.state.compute_nets(files);
+        self.reporter.clear_checks();
+        self.check_unconnected_interfaces();
+        self.check_asserts();
+        self.check_required_attributes();
+        self.check_projects();
+        self.check_unused_modules();
     }
 
     pub fn resolve_reference_designators(&mut self) {
-        self.state.resolve_reference_designators();
+        let netlist_paths: HashMap<_, _> = self
+            .builds()
+            .into_iter()
+            .filter_map(|build| Some((build.module, build.netlist_path?)))
+            .collect();
+        let errors = self
+            .state
+            .resolve_reference_designators(&self.db, &netlist_paths);
+
+        // Netlists are re-read on every call, so drop the errors from the last read, including
+        // those of netlists that have since been fixed or removed.
+        self.reporter.clear_netlists();
+        for error in errors {
+            self.reporter.report_netlist(error.into());
+        }
+
+        // Anything not pinned by a netlist gets a designator allocated here.
+        self.state.assign_reference_designators();
     }
 
     pub fn state(&self) -> &EvaluatorState {
@@ -1618,12 +2105,19 @@ impl Evaluator {
         let start = Instant::now();
         self.reset();
 
-        let files_to_evaluate: Vec<_> = self.files.values().cloned().collect();
+        let files_to_evaluate: Vec<_> = self
+            .db
+            .paths()
+            .into_iter()
+            .filter_map(|path| self.db.source(&path).ok())
+            .collect();
 
         for source in files_to_evaluate {
             self.evaluate_inner(&source, vec![]);
         }
 
+        self.finish_evaluation(&self.visited_files.clone());
+
         let duration = start.elapsed();
         debug!("Evaluation completed in {}ms", duration.as_millis());
         debug!(
@@ -0,0 +1,306 @@
+use std::{collections::HashSet, ops::Deref, path::PathBuf};
+
+use atopile_parser::{
+    parser::{BinaryOperator, Expr, PortRef, Symbol},
+    AtopileSource, Spanned,
+};
+use log::debug;
+
+use crate::{
+    diagnostics::{closest_match, Fix},
+    vfs::Vfs,
+    IntoLocation, Location,
+};
+
+use super::{
+    expr::Operand, AttributeValue, Evaluator, EvaluatorError, EvaluatorErrorKind, EvaluatorResult,
+    Instance, InstanceRef,
+};
+
+/// An `assert` statement recorded on an instance. Asserts are checked once evaluation has
+/// finished, so they see the final attribute values of every instance they were copied to.
+#[derive(Debug, Clone)]
+pub(super) struct Assertion {
+    source_path: PathBuf,
+    expr: Spanned<Expr>,
+    location: Location,
+}
+
+impl Assertion {
+    pub(super) fn new(
+        source: &AtopileSource,
+        stmt_location: Location,
+        expr: Spanned<Expr>,
+    ) -> Self {
+        Self {
+            source_path: source.path().to_path_buf(),
+            expr,
+            location: stmt_location,
+        }
+    }
+}
+
+impl<V: Vfs> Evaluator<V> {
+    /// Check the asserts of every instance and report the ones that fail. An assert copied to
+    /// several instances that fails the same way is only reported once.
+    pub(super) fn check_asserts(&self) {
+        let builds: HashSet<_> = self
+            .builds()
+            .into_iter()
+            .map(|build| build.module)
+            .collect();
+        let mut instance_refs: Vec<_> = self
+            .state
+            .instances
+            .iter()
+            .filter(|(_, instance)| !instance.asserts.is_empty())
+            .map(|(instance_ref, _)| instance_ref)
+            .collect();
+        instance_refs.sort_by_key(|instance_ref| instance_ref.to_string());
+
+        let mut reported: Vec<(Location, Option<String>)> = vec![];
+        for instance_ref in instance_refs {
+            let instance = &self.state.instances[instance_ref];
+            let in_build = builds.contains(&instance_ref.module);
+            for assertion in &instance.asserts {
+                let Err(e) = self.check_assert(instance, instance_ref, in_build, assertion) else {
+                    continue;
+                };
+                let key = (e.location.clone(), e.message.clone());
+                if !reported.contains(&key) {
+                    reported.push(key);
+                    self.reporter.report_check(e.into());
+                }
+            }
+        }
+    }
+
+    /// Check `assertion` on `instance`, which is part of a build if `in_build`.
+    fn check_assert(
+        &self,
+        instance: &Instance,
+        instance_ref: &InstanceRef,
+        in_build: bool,
+        assertion: &Assertion,
+    ) -> EvaluatorResult<()> {
+        let Ok(source) = self.db.source(&assertion.source_path) else {
+            return Err(EvaluatorError::internal(
+                &assertion.location,
+                format!(
+                    "Source `{}` of assert is not loaded",
+                    assertion.source_path.display()
+                ),
+            ));
+        };
+        let source = source.deref();
+
+        if let Some(port) = port_refs(&assertion.expr).into_iter().find(|port| {
+            self.resolve_attribute(instance, instance_ref, port)
+                .is_none()
+        }) {
+            return self.check_unresolved(source, instance, instance_ref, in_build, port);
+        }
+
+        let Expr::BinaryOp(binary) = assertion.expr.deref() else {
+            return Err(EvaluatorError::new(
+                EvaluatorErrorKind::InvalidExpression,
+                &assertion.expr.span().to_location(source),
+            )
+            .with_message("`assert` expects a comparison".to_string()));
+        };
+
+        if !is_comparison(&binary.op) {
+            return Err(EvaluatorError::new(
+                EvaluatorErrorKind::InvalidExpression,
+                &binary.op.span().to_location(source),
+            )
+            .with_message("`assert` expects a comparison".to_string()));
+        }
+
+        let left = self.evaluate_expr(source, instance, instance_ref, &binary.left)?;
+        let right = self.evaluate_expr(source, instance, instance_ref, &binary.right)?;
+
+        if let (Some(expected), Some(found)) = (left.dimension(), right.dimension()) {
+            if expected != found {
+                return Err(EvaluatorError::new(
+                    EvaluatorErrorKind::DimensionMismatch,
+                    &binary.right.span().to_location(source),
+                )
+                .with_message(format!("cannot compare a {} with a {}", expected, found)));
+            }
+        }
+
+        let holds = compare(&binary.op, &left, &right).map_err(|message| {
+            EvaluatorError::new(
+                EvaluatorErrorKind::InvalidExpression,
+                &binary.op.span().to_location(source),
+            )
+            .with_message(message)
+        })?;
+
+        if holds {
+            return Ok(());
+        }
+
+        Err(
+            EvaluatorError::new(EvaluatorErrorKind::AssertionFailed, &assertion.location)
+                .with_message(format!(
+                    "expected {} to be {} {} (in `{}`)",
+                    left,
+                    describe(&binary.op),
+                    right,
+                    instance_ref.address()
+                )),
+        )
+    }
+}
+
+impl<V: Vfs> Evaluator<V> {
+    /// Report an attribute an assert refers to that has no value on `instance`. References to
+    /// instances or attributes that don't exist are always errors. An attribute that could
+    /// still be assigned by a module using the instance (e.g. on the component definition
+    /// itself) only needs a value once the instance is part of a build.
+    fn check_unresolved(
+        &self,
+        source: &AtopileSource,
+        instance: &Instance,
+        instance_ref: &InstanceRef,
+        in_build: bool,
+        port: &Spanned<PortRef>,
+    ) -> EvaluatorResult<()> {
+        let location = port.span().to_location(source);
+        let Some((name, path)) = port.parts.split_last() else {
+            return Ok(());
+        };
+
+        let Some(owner) = self.attribute_owner(instance, instance_ref, path) else {
+            let path: Vec<_> = path.iter().map(|part| part.as_str()).collect();
+            return Err(
+                EvaluatorError::new(EvaluatorErrorKind::InvalidExpression, &location).with_message(
+                    format!(
+                        "`{}` is not an instance in `{}`",
+                        path.join("."),
+                        instance.type_ref.module_name
+                    ),
+                ),
+            );
+        };
+
+        let attribute = Symbol::from(name.as_str());
+        if !owner.accepts_attribute(&attribute) {
+            let error = EvaluatorError::new(EvaluatorErrorKind::UndeclaredAttribute, &location)
+                .with_message(format!(
+                    "`{}` does not declare `{}`",
+                    owner.type_ref.module_name, attribute
+                ));
+            let suggestion = closest_match(
+                attribute.as_str(),
+                owner.declarations.keys().map(|s| s.as_str()),
+            );
+            return Err(match suggestion {
+                Some(suggestion) => error.with_fix(Fix::replace(
+                    format!("Replace with `{}`", suggestion),
+                    &name.span().to_location(source),
+                    suggestion.to_string(),
+                )),
+                None => error,
+            });
+        }
+
+        if !in_build {
+            debug!(
+                "Skipping assert on {}: `{}` is not set",
+                instance_ref,
+                port.deref()
+            );
+            return Ok(());
+        }
+        Err(
+            EvaluatorError::new(EvaluatorErrorKind::MissingAttribute, &location).with_message(
+                format!(
+                    "cannot check assert: `{}` has no value (in `{}`)",
+                    port.deref(),
+                    instance_ref.address()
+                ),
+            ),
+        )
+    }
+}
+
+fn is_comparison(op: &BinaryOperator) -> bool {
+    matches!(
+        op,
+        BinaryOperator::Eq
+            | BinaryOperator::Neq
+            | BinaryOperator::Gt
+            | BinaryOperator::Gte
+            | BinaryOperator::Lt
+            | BinaryOperator::Lte
+            | BinaryOperator::Within
+    )
+}
+
+fn describe(op: &BinaryOperator) -> &'static str {
+    match op {
+        BinaryOperator::Eq => "equal to",
+        BinaryOperator::Neq => "different from",
+        BinaryOperator::Gt => "greater than",
+        BinaryOperator::Gte => "at least",
+        BinaryOperator::Lt => "less than",
+        BinaryOperator::Lte => "at most",
+        BinaryOperator::Within => "within",
+        _ => "",
+    }
+}
+
+/// Collect every attribute reference in an expression.
+fn port_refs(expr: &Expr) -> Vec<&Spanned<PortRef>> {
+    match expr {
+        Expr::Port(port) => vec![port],
+        Expr::BinaryOp(binary) => {
+            let mut refs = port_refs(&binary.left);
+            refs.extend(port_refs(&binary.right));
+            refs
+        }
+        _ => vec![],
+    }
+}
+
+/// Compare two values. Quantities with a tolerance only satisfy an ordering if every value
+/// in their range does, e.g. `5V ± 10%` is greater than `4V` but not greater than `4.6V`.
+fn compare(
+    op: &BinaryOperator,
+    left: &AttributeValue,
+    right: &AttributeValue,
+) -> Result<bool, String> {
+    let (Some(l), Some(r)) = (Operand::from_value(left), Operand::from_value(right)) else {
+        return match op {
+            BinaryOperator::Eq => Ok(left.to_string() == right.to_string()),
+            BinaryOperator::Neq => Ok(left.to_string() != right.to_string()),
+            _ => Err(format!("cannot compare `{}` and `{}`", left, right)),
+        };
+    };
+
+    let (l, r) = (l.quantity(), r.quantity());
+    let (l_min, l_max) = l.bounds();
+    let (r_min, r_max) = r.bounds();
+    let equal = approx_eq(l_min, r_min) && approx_eq(l_max, r_max);
+
+    Ok(match op {
+        BinaryOperator::Eq => equal,
+        BinaryOperator::Neq => !equal,
+        BinaryOperator::Gt => l_min > r_max && !approx_eq(l_min, r_max),
+        BinaryOperator::Gte => l_min > r_max || approx_eq(l_min, r_max),
+        BinaryOperator::Lt => l_max < r_min && !approx_eq(l_max, r_min),
+        BinaryOperator::Lte => l_max < r_min || approx_eq(l_max, r_min),
+        BinaryOperator::Within => {
+            (l_min > r_min || approx_eq(l_min, r_min)) && (l_max < r_max || approx_eq(l_max, r_max))
+        }
+        _ => false,
+    })
+}
+
+/// Values are parsed and scaled from decimal text, so allow for floating point rounding.
+fn approx_eq(a: f64, b: f64) -> bool {
+    (a - b).abs() <= 1e-9 * a.abs().max(b.abs())
+}
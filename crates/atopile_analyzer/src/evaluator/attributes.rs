@@ -0,0 +1,107 @@
+use std::collections::HashSet;
+
+use atopile_parser::parser::Symbol;
+use serde::Serialize;
+
+use crate::{units::Dimension, vfs::Vfs, Location};
+
+use super::{Evaluator, EvaluatorError, EvaluatorErrorKind, Instance, InstanceKind, InstanceRef};
+
+/// Attributes that can be assigned on any component, whether or not it declares them.
+pub(crate) const BUILTIN_ATTRIBUTES: &[&str] = &["designator_prefix", "footprint", "mpn", "value"];
+
+/// An attribute declared with `name: type`.
+#[derive(Debug, Clone, Serialize)]
+pub(super) struct AttributeDeclaration {
+    type_info: Symbol,
+    location: Location,
+}
+
+impl AttributeDeclaration {
+    pub(super) fn new(type_info: Symbol, location: Location) -> Self {
+        Self {
+            type_info,
+            location,
+        }
+    }
+
+    pub(super) fn type_info(&self) -> &Symbol {
+        &self.type_info
+    }
+
+    pub(super) fn location(&self) -> &Location {
+        &self.location
+    }
+}
+
+impl Instance {
+    /// Declare an attribute on this instance. Redeclaring an attribute with the same type is
+    /// allowed; otherwise the existing declaration is returned.
+    pub(super) fn declare_attribute(
+        &mut self,
+        name: &Symbol,
+        declaration: AttributeDeclaration,
+    ) -> Result<(), &AttributeDeclaration> {
+        if let Some(existing) = self.declarations.get(name) {
+            if existing.type_info != declaration.type_info {
+                return Err(&self.declarations[name]);
+            }
+            return Ok(());
+        }
+
+        if let Some(dimension) = Dimension::from_name(&declaration.type_info) {
+            self.dimensions.insert(name.clone(), dimension);
+        }
+        self.declarations.insert(name.clone(), declaration);
+        Ok(())
+    }
+
+    /// Components that declare their attributes only accept those (and the built-in ones) from
+    /// the modules that use them.
+    pub(super) fn accepts_attribute(&self, name: &Symbol) -> bool {
+        self.kind != InstanceKind::Component
+            || self.declarations.is_empty()
+            || self.declarations.contains_key(name)
+            || BUILTIN_ATTRIBUTES.contains(&name.as_str())
+    }
+}
+
+impl<V: Vfs> Evaluator<V> {
+    /// Report declared attributes that are never assigned within a build, including on its
+    /// root module.
+    pub(super) fn check_required_attributes(&self) {
+        let builds: HashSet<_> = self
+            .builds()
+            .into_iter()
+            .map(|build| build.module)
+            .collect();
+
+        let mut missing: Vec<(&InstanceRef, &Symbol, &AttributeDeclaration)> = self
+            .state
+            .instances
+            .iter()
+            .filter(|(instance_ref, _)| builds.contains(&instance_ref.module))
+            .flat_map(|(instance_ref, instance)| {
+                instance
+                    .declarations
+                    .iter()
+                    .filter(|(name, _)| !instance.attributes.contains_key(*name))
+                    .map(move |(name, declaration)| (instance_ref, name, declaration))
+            })
+            .collect();
+        missing.sort_by_key(|(instance_ref, name, _)| (instance_ref.to_string(), name.to_string()));
+
+        for (instance_ref, name, declaration) in missing {
+            self.reporter.report_check(
+                EvaluatorError::new(EvaluatorErrorKind::MissingAttribute, &declaration.location)
+                    .with_message(format!(
+                        "`{}.{}` is declared as `{}` but never assigned",
+                        instance_ref.address(),
+                        name,
+                        declaration.type_info
+                    ))
+                    .into(),
+            );
+        }
+    }
+}
@@ -0,0 +1,411 @@
+//! The builds being evaluated: the entry points declared in the `ato.yaml` of each project, or
+//! ones passed in explicitly.
+
+use std::{
+    collections::{BTreeMap, HashSet},
+    ops::Deref,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Instant,
+};
+
+use atopile_parser::parser::{BlockKind, Symbol};
+use log::{debug, warn};
+use serde::Serialize;
+
+use crate::{
+    diagnostics::closest_match,
+    project::{BuildConfig, EntryPoint, Project, CONFIG_FILE_NAME, DEFAULT_BUILD_NAME},
+    vfs::{normalize, Vfs},
+    IntoLocation,
+};
+
+use super::{
+    Evaluator, EvaluatorError, EvaluatorErrorKind, EvaluatorResult, EvaluatorState, InstanceKind,
+    InstanceRef, ModuleRef,
+};
+
+/// A root module that is built on its own.
+#[derive(Debug, Clone)]
+pub(super) struct BuildRoot {
+    pub(super) name: String,
+    pub(super) module: ModuleRef,
+    /// The netlist the build is written to, if it belongs to a project.
+    pub(super) netlist_path: Option<PathBuf>,
+}
+
+/// The elaborated instance tree of a single build.
+#[derive(Debug, Clone, Serialize)]
+pub struct Build {
+    pub name: String,
+    /// The root module, as `path/to/file.ato:Module`.
+    pub entry: String,
+    /// The instances and nets under the root module.
+    pub state: EvaluatorState,
+}
+
+impl EvaluatorState {
+    /// The part of the state under the root module `root`.
+    fn subtree(&self, root: &ModuleRef) -> EvaluatorState {
+        EvaluatorState {
+            instances: self
+                .instances
+                .iter()
+                .filter(|(instance_ref, _)| &instance_ref.module == root)
+                .map(|(instance_ref, instance)| (instance_ref.clone(), instance.clone()))
+                .collect(),
+            nets: self
+                .nets
+                .iter()
+                .filter(|(instance_ref, _)| &instance_ref.module == root)
+                .map(|(instance_ref, nets)| (instance_ref.clone(), nets.clone()))
+                .collect(),
+            designators: self
+                .designators
+                .iter()
+                .filter(|(instance_ref, _)| &instance_ref.module == root)
+                .map(|(instance_ref, designator)| (instance_ref.clone(), designator.clone()))
+                .collect(),
+        }
+    }
+}
+
+/// Whether `path` belongs to an installed package rather than to the project itself.
+pub(crate) fn is_dependency(path: &Path) -> bool {
+    path.components()
+        .any(|component| component.as_os_str() == ".ato")
+}
+
+impl<V: Vfs> Evaluator<V> {
+    /// Evaluate the builds of `entry_points`, keyed by build name, and return the elaborated
+    /// tree of each build. Entry points whose module can't be found are left out.
+    pub fn evaluate_entry_points(
+        &mut self,
+        entry_points: &BTreeMap<String, EntryPoint>,
+    ) -> Vec<Build> {
+        let roots = entry_points
+            .iter()
+            .map(|(name, entry)| {
+                let path = self
+                    .db
+                    .canonicalize(&entry.file)
+                    .unwrap_or_else(|_| normalize(&entry.file));
+                (
+                    name.clone(),
+                    ModuleRef::new(&path, &Symbol::from(entry.module.as_str())),
+                )
+            })
+            .collect();
+        self.evaluate_roots(roots);
+        self.project = None;
+        self.build_trees()
+    }
+
+    /// Evaluate the files declaring `roots`, the files they import and every other file that
+    /// has been loaded. In the projects of the roots and in installed packages, only the blocks
+    /// the roots are made of are elaborated.
+    pub(super) fn evaluate_roots(&mut self, roots: Vec<(String, ModuleRef)>) {
+        debug!("Evaluator starting evaluation of {} builds", roots.len());
+        let start = Instant::now();
+        let project = self.project.take();
+        self.reset();
+        self.project = project;
+
+        self.used_blocks = self.db.used_blocks(roots.iter().map(|(_, module)| module));
+        self.entry_points = Some(roots.clone());
+        for (name, module) in &roots {
+            match self.db.source(&module.source_path) {
+                Ok(source) => self.evaluate_inner(&source, vec![]),
+                Err(e) => warn!("can't load entry point {} of build {}: {}", module, name, e),
+            }
+        }
+
+        let others: Vec<_> = self
+            .db
+            .paths()
+            .into_iter()
+            .filter_map(|path| self.db.source(&path).ok())
+            .collect();
+        for source in others {
+            self.evaluate_inner(&source, vec![]);
+        }
+
+        self.finish_evaluation(&self.visited_files.clone());
+        debug!(
+            "Build evaluation completed in {}ms",
+            start.elapsed().as_millis()
+        );
+    }
+
+    /// Evaluate the builds declared in the `ato.yaml` of the project containing `path`.
+    pub fn evaluate_project(&mut self, path: &Path) -> Vec<Build> {
+        let project = self.db.project(path);
+        let entry_points = project
+            .as_ref()
+            .map(|project| {
+                project
+                    .config()
+                    .builds
+                    .iter()
+                    .map(|(name, build)| {
+                        let entry = EntryPoint {
+                            file: project.entry_path(build),
+                            module: build.entry.module.clone(),
+                        };
+                        (name.clone(), entry)
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        let builds = self.evaluate_entry_points(&entry_points);
+        self.project = project.map(|project| project.root().to_path_buf());
+        builds
+    }
+
+    /// Read the `ato.yaml` at `path` again after it has been created, changed or deleted, and
+    /// evaluate everything again: the builds and dependencies it declares decide what is built
+    /// and what imports resolve to. The builds of the project being evaluated are read again
+    /// too, falling back to evaluating every file if it no longer declares any.
+    pub fn reload_config(&mut self, path: &Path) {
+        debug!("Evaluator reloading {:?}", path);
+        self.db.reload_config(path);
+
+        if let Some(root) = self.project.clone() {
+            let config = root.join(CONFIG_FILE_NAME);
+            if self
+                .db
+                .project(&config)
+                .is_some_and(|project| !project.config().builds.is_empty())
+            {
+                self.evaluate_project(&config);
+            } else {
+                self.evaluate();
+            }
+            return;
+        }
+
+        match self.entry_points.clone() {
+            Some(entry_points) => self.evaluate_roots(entry_points),
+            None => {
+                self.evaluate();
+            }
+        }
+    }
+
+    /// The root of the project whose builds are being evaluated, if evaluation was started with
+    /// [`Evaluator::evaluate_project`].
+    pub fn evaluated_project(&self) -> Option<&Path> {
+        self.project.as_deref()
+    }
+
+    /// Whether only the blocks the entry points are made of are elaborated in the file at
+    /// `path`: it belongs to an installed package, or to the project of an entry point.
+    pub(super) fn elaborates_only_used(&self, path: &Path) -> bool {
+        let Some(entry_points) = &self.entry_points else {
+            return false;
+        };
+        is_dependency(path)
+            || entry_points.iter().any(|(_, root)| {
+                root.source_path == path
+                    || self
+                        .db
+                        .project(&root.source_path)
+                        .is_some_and(|project| path.starts_with(project.root()))
+            })
+    }
+
+    /// The elaborated tree of each build, ordered by name.
+    pub fn build_trees(&self) -> Vec<Build> {
+        let mut builds: Vec<_> = self
+            .builds()
+            .into_iter()
+            .map(|build| Build {
+                entry: build.module.to_string(),
+                state: self.state.subtree(&build.module),
+                name: build.name,
+            })
+            .collect();
+        builds.sort_by(|a, b| a.name.cmp(&b.name).then_with(|| a.entry.cmp(&b.entry)));
+        builds
+    }
+
+    /// The projects the evaluated sources belong to, ordered by root.
+    fn projects(&self) -> Vec<Arc<Project>> {
+        let mut projects: Vec<_> = self
+            .db
+            .paths()
+            .iter()
+            .filter_map(|path| self.db.project(path))
+            .collect();
+        projects.sort_by(|a, b| a.root().cmp(b.root()));
+        projects.dedup_by(|a, b| a.root() == b.root());
+        projects
+    }
+
+    /// The root modules that are built. These are the entry points being evaluated, if any,
+    /// and otherwise the builds declared by each project. A project that doesn't declare any
+    /// builds, or a file outside of any project, builds every root module that isn't used by
+    /// another module.
+    pub(super) fn builds(&self) -> Vec<BuildRoot> {
+        let exists = |module: &ModuleRef| {
+            self.state
+                .instances
+                .contains_key(&InstanceRef::from(module))
+        };
+
+        if let Some(entry_points) = &self.entry_points {
+            return entry_points
+                .iter()
+                .filter(|(_, module)| exists(module))
+                .map(|(name, module)| BuildRoot {
+                    name: name.clone(),
+                    module: module.clone(),
+                    netlist_path: self
+                        .db
+                        .project(&module.source_path)
+                        .map(|project| project.netlist_path(name)),
+                })
+                .collect();
+        }
+
+        let used_types: HashSet<_> = self
+            .state
+            .instances
+            .iter()
+            .filter(|(instance_ref, _)| instance_ref.len() > 0)
+            .map(|(_, instance)| &instance.type_ref)
+            .collect();
+
+        let mut builds = Vec::new();
+        for (instance_ref, instance) in &self.state.instances {
+            if instance_ref.len() > 0 || instance.kind != InstanceKind::Module {
+                continue;
+            }
+
+            let module = &instance_ref.module;
+            match self.db.project(&module.source_path) {
+                Some(project) if !project.config().builds.is_empty() => {
+                    for (name, build) in &project.config().builds {
+                        if project.entry_path(build) == module.source_path
+                            && build.entry.module == module.module_name.as_str()
+                        {
+                            builds.push(BuildRoot {
+                                name: name.clone(),
+                                module: module.clone(),
+                                netlist_path: Some(project.netlist_path(name)),
+                            });
+                        }
+                    }
+                }
+                project => {
+                    if !used_types.contains(module) {
+                        builds.push(BuildRoot {
+                            name: module.module_name.to_string(),
+                            module: module.clone(),
+                            netlist_path: project
+                                .map(|project| project.netlist_path(DEFAULT_BUILD_NAME)),
+                        });
+                    }
+                }
+            }
+        }
+        builds
+    }
+
+    /// Warn about the modules of the projects being built that none of the entry points use,
+    /// directly or as the type another module is declared `from`.
+    pub(super) fn check_unused_modules(&self) {
+        if self.entry_points.is_none() {
+            return;
+        }
+
+        let mut unused = vec![];
+        for path in &self.visited_files {
+            if is_dependency(path) || !self.elaborates_only_used(path) {
+                continue;
+            }
+            let Ok(source) = self.db.source(path) else {
+                continue;
+            };
+            for declaration in &self.db.block_declarations(path).blocks {
+                let module = ModuleRef::new(path, &declaration.name);
+                if *declaration.stmt.kind == BlockKind::Module
+                    && !self.used_blocks.contains(&module)
+                {
+                    unused.push((
+                        module,
+                        declaration.stmt.name.span().to_location(source.deref()),
+                    ));
+                }
+            }
+        }
+        unused.sort_by_key(|(module, _)| module.to_string());
+
+        for (module, location) in unused {
+            self.reporter.report_check(
+                EvaluatorError::new(EvaluatorErrorKind::UnusedModule, &location)
+                    .with_message(format!("`{}` isn't used by any build", module.module_name))
+                    .into(),
+            );
+        }
+    }
+
+    /// Report invalid `ato.yaml` files, and builds whose entry point doesn't exist.
+    pub(super) fn check_projects(&self) {
+        for project in self.projects() {
+            if let Some(error) = project.error() {
+                self.reporter.report_check(
+                    EvaluatorError::new(
+                        EvaluatorErrorKind::InvalidProjectConfig,
+                        &project.error_location(error),
+                    )
+                    .with_message(error.message.clone())
+                    .into(),
+                );
+                continue;
+            }
+
+            for build in project.config().builds.values() {
+                if let Err(e) = self.check_entry_point(&project, build) {
+                    self.reporter.report_check(e.into());
+                }
+            }
+        }
+    }
+
+    fn check_entry_point(&self, project: &Project, build: &BuildConfig) -> EvaluatorResult<()> {
+        let location = project.locate(&build.entry.to_string());
+        let path = project.entry_path(build);
+        if !self.db.is_file(&path) {
+            return Err(
+                EvaluatorError::new(EvaluatorErrorKind::BuildEntryNotFound, &location)
+                    .with_message(format!("`{}` does not exist", build.entry.file.display())),
+            );
+        }
+
+        let declarations = self.db.block_declarations(&path);
+        if declarations
+            .get(&Symbol::from(build.entry.module.as_str()))
+            .is_some()
+        {
+            return Ok(());
+        }
+
+        let message = format!(
+            "`{}` is not declared in `{}`",
+            build.entry.module,
+            build.entry.file.display()
+        );
+        let message = match closest_match(
+            &build.entry.module,
+            declarations.blocks.iter().map(|block| block.name.as_str()),
+        ) {
+            Some(suggestion) => format!("{}; did you mean `{}`?", message, suggestion),
+            None => message,
+        };
+        Err(
+            EvaluatorError::new(EvaluatorErrorKind::BuildEntryNotFound, &location)
+                .with_message(message),
+        )
+    }
+}
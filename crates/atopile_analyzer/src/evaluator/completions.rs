@@ -0,0 +1,60 @@
+//! The members of evaluated instances, offered as completions after a `.`.
+
+use atopile_parser::parser::Symbol;
+
+use super::{AttributeValue, EvaluatorState, InstanceKind, InstanceRef, ModuleRef};
+use crate::{Completion, CompletionKind};
+
+impl EvaluatorState {
+    /// The children and attributes of the instance `parts` (e.g. `r1` for `r1.`) of the
+    /// evaluated block `block`, ordered by name.
+    pub(crate) fn member_completions(&self, block: &ModuleRef, parts: &[&str]) -> Vec<Completion> {
+        let path = parts.iter().map(|part| Symbol::from(*part)).collect();
+        let Some(instance) = self.instances.get(&InstanceRef::new(block, path)) else {
+            return vec![];
+        };
+
+        let children = instance.children.iter().filter_map(|(name, child_ref)| {
+            let child = self.instances.get(child_ref)?;
+            let (kind, detail) = match child.kind {
+                InstanceKind::Port => (CompletionKind::Signal, None),
+                InstanceKind::Pin => (CompletionKind::Pin, None),
+                InstanceKind::Module | InstanceKind::Component | InstanceKind::Interface => (
+                    CompletionKind::Instance,
+                    Some(child.type_ref.module_name().to_string()),
+                ),
+            };
+            Some(Completion {
+                label: name.to_string(),
+                kind,
+                detail,
+                range: None,
+            })
+        });
+
+        let mut attributes: Vec<_> = instance
+            .attributes
+            .keys()
+            .chain(instance.declarations.keys())
+            .collect();
+        attributes.sort_by_key(|attribute| attribute.as_str());
+        attributes.dedup();
+        let attributes = attributes.into_iter().map(|name| {
+            let detail = match instance.attributes.get(name) {
+                Some(AttributeValue::String(s)) => format!("\"{}\"", s),
+                Some(value) => value.to_string(),
+                None => instance.declarations[name].type_info().to_string(),
+            };
+            Completion {
+                label: name.to_string(),
+                kind: CompletionKind::Attribute,
+                detail: Some(detail),
+                range: None,
+            }
+        });
+
+        let mut completions: Vec<_> = children.chain(attributes).collect();
+        completions.sort_by(|a, b| a.label.cmp(&b.label));
+        completions
+    }
+}
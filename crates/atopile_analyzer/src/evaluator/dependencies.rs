@@ -0,0 +1,53 @@
+//! Tracks which files import which, so that a change to one file only re-evaluates the files
+//! that can see it.
+
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+};
+
+use super::ModuleRef;
+
+#[derive(Debug, Default)]
+pub(super) struct FileGraph {
+    /// The files each file imports from.
+    imports: HashMap<PathBuf, HashSet<PathBuf>>,
+    /// The root modules each file defines.
+    modules: HashMap<PathBuf, HashSet<ModuleRef>>,
+}
+
+impl FileGraph {
+    pub(super) fn add_import(&mut self, path: &Path, imported: &Path) {
+        self.imports
+            .entry(path.to_path_buf())
+            .or_default()
+            .insert(imported.to_path_buf());
+    }
+
+    pub(super) fn add_module(&mut self, path: &Path, module_ref: &ModuleRef) {
+        self.modules
+            .entry(path.to_path_buf())
+            .or_default()
+            .insert(module_ref.clone());
+    }
+
+    /// Forget what `path` imports and defines, returning the modules it defined.
+    pub(super) fn remove_file(&mut self, path: &Path) -> HashSet<ModuleRef> {
+        self.imports.remove(path);
+        self.modules.remove(path).unwrap_or_default()
+    }
+
+    /// `path` and every file that imports it, directly or through other files.
+    pub(super) fn dependents(&self, path: &Path) -> HashSet<PathBuf> {
+        let mut dependents = HashSet::from([path.to_path_buf()]);
+        let mut queue = vec![path.to_path_buf()];
+        while let Some(file) = queue.pop() {
+            for (importer, imported) in &self.imports {
+                if imported.contains(&file) && dependents.insert(importer.clone()) {
+                    queue.push(importer.clone());
+                }
+            }
+        }
+        dependents
+    }
+}
@@ -0,0 +1,172 @@
+//! Deterministic allocation of reference designators (R1, C3, U2, ...) for components.
+
+use std::collections::{HashMap, HashSet};
+
+use super::{AttributeValue, EvaluatorState, Instance, InstanceKind, InstanceRef, ModuleRef};
+
+/// The attribute that overrides the designator prefix derived from a component's type.
+const DESIGNATOR_PREFIX_ATTRIBUTE: &str = "designator_prefix";
+
+/// Designator prefixes for common component types, matched against whole words of the type
+/// name, e.g. `PullupResistor` or `RedLED`.
+const TYPE_PREFIXES: &[(&str, &str)] = &[
+    ("resistor", "R"),
+    ("capacitor", "C"),
+    ("inductor", "L"),
+    ("ferrite", "FB"),
+    ("led", "D"),
+    ("diode", "D"),
+    ("transistor", "Q"),
+    ("mosfet", "Q"),
+    ("bjt", "Q"),
+    ("crystal", "Y"),
+    ("oscillator", "Y"),
+    ("fuse", "F"),
+    ("connector", "J"),
+    ("header", "J"),
+    ("switch", "SW"),
+    ("button", "SW"),
+    ("testpoint", "TP"),
+];
+
+/// The prefix used when nothing more specific is known about a component.
+const DEFAULT_PREFIX: &str = "U";
+
+/// Whether `pattern` (lowercase) appears in `name` as a whole CamelCase or `_`-separated word.
+fn contains_word(name: &str, pattern: &str) -> bool {
+    let bytes = name.as_bytes();
+    let is_boundary = |i: usize| {
+        i == 0
+            || i == bytes.len()
+            || bytes[i].is_ascii_uppercase()
+            || !bytes[i].is_ascii_alphabetic()
+            || !bytes[i - 1].is_ascii_alphabetic()
+    };
+
+    name.to_ascii_lowercase()
+        .match_indices(pattern)
+        .any(|(start, m)| is_boundary(start) && is_boundary(start + m.len()))
+}
+
+/// The number of `designator` if it is `prefix` followed by a number, e.g. 3 for `R3`.
+fn designator_index(designator: &str, prefix: &str) -> Option<usize> {
+    designator.strip_prefix(prefix)?.parse().ok()
+}
+
+/// A run of digits or of anything else within a string being sorted naturally.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub(super) enum NaturalChunk {
+    /// A run of digits, ordered by value (its length without leading zeros, then its digits)
+    /// and then by its leading zeros.
+    Number(usize, String, String),
+    Text(String),
+}
+
+/// A sort key for `s` that orders runs of digits by their value, so that `r2` comes before
+/// `r10` and `R9` before `R10`.
+pub(super) fn natural_sort_key(s: &str) -> Vec<NaturalChunk> {
+    let mut chunks = vec![];
+    let mut rest = s;
+    while let Some(first) = rest.chars().next() {
+        let is_digit = first.is_ascii_digit();
+        let end = rest
+            .find(|c: char| c.is_ascii_digit() != is_digit)
+            .unwrap_or(rest.len());
+        let (run, tail) = rest.split_at(end);
+        chunks.push(if is_digit {
+            let digits = run.trim_start_matches('0');
+            NaturalChunk::Number(digits.len(), digits.to_string(), run.to_string())
+        } else {
+            NaturalChunk::Text(run.to_string())
+        });
+        rest = tail;
+    }
+    chunks
+}
+
+fn designator_prefix(instance: &Instance) -> String {
+    if let Some(AttributeValue::String(prefix)) =
+        instance.attributes.get(&DESIGNATOR_PREFIX_ATTRIBUTE.into())
+    {
+        return prefix.clone();
+    }
+
+    let type_name = instance.type_ref.module_name.as_str();
+    TYPE_PREFIXES
+        .iter()
+        .find(|(pattern, _)| contains_word(type_name, pattern))
+        .map(|(_, prefix)| prefix.to_string())
+        .unwrap_or_else(|| DEFAULT_PREFIX.to_string())
+}
+
+impl EvaluatorState {
+    /// Assign a reference designator to every component that doesn't have one yet. Designators
+    /// are unique within each root module; ones that are already set (i.e. pinned by an
+    /// existing netlist) are kept and never reused. A component keeps the designator it was
+    /// last allocated here, as long as its prefix hasn't changed and nothing has pinned it
+    /// since, so adding or removing components doesn't renumber the others. Pinned designators
+    /// aren't remembered, so they go away with the netlist that pinned them. Components that
+    /// have never had one are given the lowest free number for their prefix, in order of their
+    /// address, with numbers in it compared by value.
+    pub(super) fn assign_reference_designators(&mut self) {
+        let mut components: HashMap<ModuleRef, Vec<InstanceRef>> = HashMap::new();
+        for (instance_ref, instance) in &self.instances {
+            if instance.kind == InstanceKind::Component && !instance_ref.instance_path.is_empty() {
+                components
+                    .entry(instance_ref.module.clone())
+                    .or_default()
+                    .push(instance_ref.clone());
+            }
+        }
+
+        let mut designators = HashMap::new();
+        for (_, mut component_refs) in components {
+            component_refs.sort_by_cached_key(|r| natural_sort_key(&r.to_string()));
+
+            let mut used: HashSet<String> = component_refs
+                .iter()
+                .filter_map(|r| self.instances[r].reference_designator.clone())
+                .collect();
+
+            // Components keep the designators they were last assigned.
+            let mut unassigned = vec![];
+            for component_ref in component_refs {
+                let instance = self.instances.get_mut(&component_ref).unwrap();
+                if instance.reference_designator.is_some() {
+                    continue;
+                }
+                let previous = self.designators.get(&component_ref).filter(|previous| {
+                    !used.contains(*previous)
+                        && designator_index(previous, &designator_prefix(instance)).is_some()
+                });
+                match previous {
+                    Some(previous) => {
+                        used.insert(previous.clone());
+                        instance.reference_designator = Some(previous.clone());
+                        designators.insert(component_ref, previous.clone());
+                    }
+                    None => unassigned.push(component_ref),
+                }
+            }
+
+            let mut next_index: HashMap<String, usize> = HashMap::new();
+            for component_ref in unassigned {
+                let instance = self.instances.get_mut(&component_ref).unwrap();
+                let prefix = designator_prefix(instance);
+                let index = next_index.entry(prefix.clone()).or_insert(1);
+                let designator = loop {
+                    let candidate = format!("{}{}", prefix, index);
+                    *index += 1;
+                    if !used.contains(&candidate) {
+                        break candidate;
+                    }
+                };
+
+                used.insert(designator.clone());
+                instance.reference_designator = Some(designator.clone());
+                designators.insert(component_ref, designator);
+            }
+        }
+        self.designators = designators;
+    }
+}
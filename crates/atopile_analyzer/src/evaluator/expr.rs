@@ -0,0 +1,209 @@
+use std::ops::Deref;
+
+use atopile_parser::{
+    parser::{BinaryOperator, Expr, PortRef, Symbol},
+    AtopileSource, Spanned,
+};
+
+use crate::{
+    units::{physical_text, Quantity, UnitError},
+    vfs::Vfs,
+    IntoLocation,
+};
+
+use super::{
+    AttributeValue, Evaluator, EvaluatorError, EvaluatorErrorKind, EvaluatorResult, Instance,
+    InstanceRef, PhysicalAttribute, ResultExt,
+};
+
+/// An intermediate value while folding an arithmetic expression. Numbers assigned before
+/// (`AttributeValue::Number`) are kept apart from physical quantities. Bare numbers in the
+/// source parse as physical values without a unit, so `2 * 3` evaluates to a dimensionless
+/// quantity, the same as `6`.
+pub(super) enum Operand {
+    Number(f64),
+    Quantity(Quantity),
+}
+
+impl Operand {
+    pub(super) fn from_value(value: &AttributeValue) -> Option<Self> {
+        match value {
+            AttributeValue::Number(n) => Some(Operand::Number(*n)),
+            AttributeValue::Physical(p) => Some(Operand::Quantity(p.quantity)),
+            _ => None,
+        }
+    }
+
+    pub(super) fn quantity(&self) -> Quantity {
+        match self {
+            Operand::Number(n) => Quantity::dimensionless(*n),
+            Operand::Quantity(q) => *q,
+        }
+    }
+}
+
+impl<V: Vfs> Evaluator<V> {
+    /// Evaluate the right-hand side of an attribute assignment. `instance` is the block
+    /// currently being evaluated, which has been taken out of the state while it is updated.
+    pub(super) fn evaluate_expr(
+        &self,
+        source: &AtopileSource,
+        instance: &Instance,
+        instance_ref: &InstanceRef,
+        expr: &Spanned<Expr>,
+    ) -> EvaluatorResult<AttributeValue> {
+        match expr.deref() {
+            Expr::String(s) => Ok(AttributeValue::String(s.deref().clone())),
+            Expr::Bool(b) => Ok(AttributeValue::Boolean(*b.deref())),
+            Expr::Number(n) => match n.deref().parse::<f64>() {
+                Ok(num) => Ok(AttributeValue::Number(num)),
+                // If parsing fails, store as string
+                Err(_) => Ok(AttributeValue::String(n.deref().clone())),
+            },
+            Expr::Physical(p) => Ok(AttributeValue::Physical(PhysicalAttribute {
+                text: physical_text(p),
+                quantity: Quantity::from_physical(p).with_context(
+                    source,
+                    |_| EvaluatorErrorKind::InvalidUnit,
+                    expr,
+                )?,
+            })),
+            // A bare reference is copied from the attribute it names, or kept as a reference
+            // to a port when no such attribute exists.
+            Expr::Port(port) => Ok(self
+                .resolve_attribute(instance, instance_ref, port)
+                .cloned()
+                .unwrap_or_else(|| AttributeValue::Port(port.deref().to_string()))),
+            Expr::BinaryOp(_) => Ok(
+                match self.evaluate_operand(source, instance, instance_ref, expr)? {
+                    Operand::Number(n) => AttributeValue::Number(n),
+                    Operand::Quantity(quantity) => AttributeValue::Physical(PhysicalAttribute {
+                        text: quantity.to_string(),
+                        quantity,
+                    }),
+                },
+            ),
+            Expr::New(_) => Err(EvaluatorError::new(
+                EvaluatorErrorKind::InvalidExpression,
+                &expr.span().to_location(source),
+            )
+            .with_message("`new` cannot be used inside an expression".to_string())),
+        }
+    }
+
+    fn evaluate_operand(
+        &self,
+        source: &AtopileSource,
+        instance: &Instance,
+        instance_ref: &InstanceRef,
+        expr: &Spanned<Expr>,
+    ) -> EvaluatorResult<Operand> {
+        let value = match expr.deref() {
+            Expr::BinaryOp(binary) => {
+                let left = self.evaluate_operand(source, instance, instance_ref, &binary.left)?;
+                let right = self.evaluate_operand(source, instance, instance_ref, &binary.right)?;
+                return self.apply_operator(source, expr, &binary.op, left, right);
+            }
+            Expr::Port(port) => self
+                .resolve_attribute(instance, instance_ref, port)
+                .cloned()
+                .ok_or_else(|| {
+                    EvaluatorError::new(
+                        EvaluatorErrorKind::InvalidExpression,
+                        &expr.span().to_location(source),
+                    )
+                    .with_message(format!("`{}` is not a known attribute", port.deref()))
+                })?,
+            _ => self.evaluate_expr(source, instance, instance_ref, expr)?,
+        };
+
+        Operand::from_value(&value).ok_or_else(|| {
+            EvaluatorError::new(
+                EvaluatorErrorKind::InvalidExpression,
+                &expr.span().to_location(source),
+            )
+            .with_message(format!("`{}` is not a number or physical value", value))
+        })
+    }
+
+    /// Apply `op` to two operands. Errors are reported on `expr`, the whole binary expression.
+    fn apply_operator(
+        &self,
+        source: &AtopileSource,
+        expr: &Spanned<Expr>,
+        op: &Spanned<BinaryOperator>,
+        left: Operand,
+        right: Operand,
+    ) -> EvaluatorResult<Operand> {
+        if let (Operand::Number(l), Operand::Number(r)) = (&left, &right) {
+            let result = match op.deref() {
+                BinaryOperator::Add => Some(l + r),
+                BinaryOperator::Sub => Some(l - r),
+                BinaryOperator::Mul => Some(l * r),
+                BinaryOperator::Div if *r != 0.0 => Some(l / r),
+                BinaryOperator::Pow => Some(l.powf(*r)),
+                _ => None,
+            };
+            if let Some(n) = result.filter(|n| n.is_finite()) {
+                return Ok(Operand::Number(n));
+            }
+        }
+
+        let (left, right) = (left.quantity(), right.quantity());
+        let result = match op.deref() {
+            BinaryOperator::Add => left.checked_add(&right),
+            BinaryOperator::Sub => left.checked_sub(&right),
+            BinaryOperator::Mul => left.checked_mul(&right),
+            BinaryOperator::Div => left.checked_div(&right),
+            BinaryOperator::Pow => left.checked_pow(&right),
+            _ => {
+                return Err(EvaluatorError::new(
+                    EvaluatorErrorKind::InvalidExpression,
+                    &op.span().to_location(source),
+                )
+                .with_message("comparisons can only be used in an `assert`".to_string()))
+            }
+        };
+
+        result.map(Operand::Quantity).with_context(
+            source,
+            |e| match e {
+                UnitError::IncompatibleDimensions(..) => EvaluatorErrorKind::DimensionMismatch,
+                _ => EvaluatorErrorKind::InvalidExpression,
+            },
+            expr,
+        )
+    }
+
+    /// Look up the attribute named by `port`, e.g. `vin` on the current block or `r1.value`
+    /// on one of its children.
+    pub(super) fn resolve_attribute<'a>(
+        &'a self,
+        instance: &'a Instance,
+        instance_ref: &InstanceRef,
+        port: &PortRef,
+    ) -> Option<&'a AttributeValue> {
+        let (attr_name, path) = port.parts.split_last()?;
+        self.attribute_owner(instance, instance_ref, path)?
+            .attributes
+            .get(&Symbol::from(attr_name.deref().as_str()))
+    }
+
+    /// The instance `path` leads to from `instance`, i.e. the one an attribute reference with
+    /// that path before its name belongs to.
+    pub(super) fn attribute_owner<'a>(
+        &'a self,
+        instance: &'a Instance,
+        instance_ref: &InstanceRef,
+        path: &[Spanned<String>],
+    ) -> Option<&'a Instance> {
+        if path.is_empty() {
+            return Some(instance);
+        }
+        let mut owner_path = instance_ref.instance_path.clone();
+        owner_path.extend(path.iter().map(|part| Symbol::from(part.deref().as_str())));
+        self.state
+            .instances
+            .get(&InstanceRef::new(&instance_ref.module, owner_path))
+    }
+}
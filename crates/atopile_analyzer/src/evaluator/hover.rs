@@ -0,0 +1,160 @@
+//! Markdown descriptions of blocks and evaluated instances, shown when hovering over their names.
+
+use std::{fmt::Write, ops::Deref};
+
+use atopile_parser::parser::{BlockKind, Stmt, Symbol};
+
+use super::{
+    nets::relative_path, AttributeValue, Evaluator, Instance, InstanceKind, InstanceRef, ModuleRef,
+};
+use crate::vfs::Vfs;
+
+impl<V: Vfs> Evaluator<V> {
+    /// Describe the block `block`: its kind, the blocks it inherits from and its doc comment.
+    pub(crate) fn describe_block(&self, block: &ModuleRef) -> Option<String> {
+        let ancestry = self.db.ancestry(block);
+        let (_, declarations) = ancestry.first()?;
+        let stmt = &declarations.get(block.module_name())?.stmt;
+
+        let kind = match stmt.kind.deref() {
+            BlockKind::Component => "component",
+            BlockKind::Module => "module",
+            BlockKind::Interface => "interface",
+        };
+        let mut description = format!("```ato\n{} {}\n```\n", kind, block.module_name());
+
+        if ancestry.len() > 1 {
+            let parents: Vec<_> = ancestry[1..]
+                .iter()
+                .map(|(parent, _)| format!("`{}`", parent.module_name()))
+                .collect();
+            let _ = write!(description, "\nInherits from {}\n", parents.join(" → "));
+        }
+
+        // The doc comment is the run of comments, or the `"""` docstring, the body starts with.
+        let doc: Vec<_> = stmt
+            .body
+            .iter()
+            .map_while(|stmt| match stmt.deref() {
+                Stmt::Comment(comment) => Some(comment.comment.trim()),
+                _ => None,
+            })
+            .collect();
+        if !doc.is_empty() {
+            let _ = write!(description, "\n{}\n", doc.join("\n"));
+        }
+
+        Some(description)
+    }
+
+    /// Describe the instance, signal, pin or attribute `parts` (e.g. `r1.p1`) of the evaluated
+    /// block `block`. Instances show their type and attributes, signals and pins the net they
+    /// are on, and attributes their value.
+    pub(crate) fn describe_port(&self, block: &ModuleRef, parts: &[&str]) -> Option<String> {
+        let root = InstanceRef::from(block);
+        let path: Vec<Symbol> = parts.iter().map(|part| Symbol::from(*part)).collect();
+        let instance_ref = InstanceRef::new(block, path.clone());
+
+        let Some(instance) = self.state.instances.get(&instance_ref) else {
+            let (attribute, parent_path) = path.split_last()?;
+            let parent = self
+                .state
+                .instances
+                .get(&InstanceRef::new(block, parent_path.to_vec()))?;
+            return describe_attribute(parent, attribute);
+        };
+
+        let name = parts.join(".");
+        match instance.kind {
+            InstanceKind::Port | InstanceKind::Pin => {
+                let keyword = match instance.kind {
+                    InstanceKind::Pin => "pin",
+                    _ => "signal",
+                };
+                let mut description = format!("```ato\n{} {}\n```\n", keyword, name);
+
+                let net = self
+                    .state
+                    .nets
+                    .get(&root)
+                    .into_iter()
+                    .flatten()
+                    .find(|net| net.members().contains(&instance_ref));
+                let others: Vec<_> = net
+                    .iter()
+                    .flat_map(|net| net.members())
+                    .filter(|member| **member != instance_ref)
+                    .collect();
+                match net {
+                    Some(net) if !others.is_empty() => {
+                        let _ = writeln!(description, "\nOn net `{}`, with:", net.name());
+                        for member in others {
+                            let _ = writeln!(description, "- `{}`", relative_path(member));
+                        }
+                    }
+                    _ => description.push_str("\nNot connected\n"),
+                }
+                Some(description)
+            }
+            InstanceKind::Module | InstanceKind::Component | InstanceKind::Interface => {
+                let mut description = format!(
+                    "```ato\n{}: {}\n```\n",
+                    name,
+                    instance.type_ref.module_name()
+                );
+
+                let mut attributes: Vec<_> = instance
+                    .attributes
+                    .keys()
+                    .chain(instance.declarations.keys())
+                    .collect();
+                attributes.sort_by_key(|attribute| attribute.as_str());
+                attributes.dedup();
+                if !attributes.is_empty() {
+                    description.push('\n');
+                }
+                for attribute in attributes {
+                    let _ = writeln!(
+                        description,
+                        "- `{}`: {}",
+                        attribute,
+                        attribute_value(instance, attribute)
+                    );
+                }
+                Some(description)
+            }
+        }
+    }
+}
+
+/// Describe the attribute `name` of `instance`, if it is declared or assigned.
+fn describe_attribute(instance: &Instance, name: &Symbol) -> Option<String> {
+    let declaration = instance.declarations.get(name);
+    if declaration.is_none() && !instance.attributes.contains_key(name) {
+        return None;
+    }
+
+    let mut description = match declaration {
+        Some(declaration) => format!("```ato\n{}: {}\n```\n", name, declaration.type_info()),
+        None => format!("```ato\n{}\n```\n", name),
+    };
+    let _ = writeln!(description, "\n{}", attribute_value(instance, name));
+    Some(description)
+}
+
+/// The value of the attribute `name` of `instance`, with its units and the name of its
+/// dimension if it is a physical value.
+fn attribute_value(instance: &Instance, name: &Symbol) -> String {
+    match instance.attributes.get(name) {
+        Some(AttributeValue::Physical(physical)) => match physical.quantity.dimension.name() {
+            Some(dimension) => format!("{} ({})", physical.quantity, dimension),
+            None => physical.quantity.to_string(),
+        },
+        Some(AttributeValue::String(s)) => format!("\"{}\"", s),
+        Some(value) => value.to_string(),
+        None => match instance.declarations.get(name) {
+            Some(declaration) => format!("{}, unassigned", declaration.type_info()),
+            None => "unassigned".to_string(),
+        },
+    }
+}
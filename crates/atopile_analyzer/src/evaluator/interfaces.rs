@@ -0,0 +1,236 @@
+use std::collections::{HashMap, HashSet};
+
+use atopile_parser::parser::Symbol;
+
+use crate::{
+    diagnostics::{
+        AnalyzerDiagnostic, AnalyzerDiagnosticKind, AnalyzerDiagnosticSeverity,
+        UnconnectedInterfaceDiagnostic,
+    },
+    vfs::Vfs,
+    Location,
+};
+
+use super::{
+    Connection, Evaluator, EvaluatorError, EvaluatorErrorKind, EvaluatorResult, Instance,
+    InstanceKind, InstanceRef, ModuleRef,
+};
+
+impl<V: Vfs> Evaluator<V> {
+    /// Warn about interfaces of sub-instances that the module using them never connects, e.g.
+    /// `power` in `ldo = new Regulator` when nothing in the module is connected to `ldo.power`.
+    /// Components and interfaces only declare the interfaces they are made of, so they aren't
+    /// checked.
+    pub(super) fn check_unconnected_interfaces(&self) {
+        let mut roots: Vec<_> = self
+            .state
+            .instances
+            .iter()
+            .filter(|(instance_ref, instance)| {
+                instance_ref.len() == 0 && instance.kind == InstanceKind::Module
+            })
+            .collect();
+        roots.sort_by_key(|(instance_ref, _)| instance_ref.to_string());
+
+        // Everything connected within each module's tree, as the paths of the connections'
+        // ends and of everything containing them.
+        let mut connected: HashMap<&ModuleRef, HashSet<&[Symbol]>> = HashMap::new();
+        for (instance_ref, instance) in &self.state.instances {
+            let paths = connected.entry(&instance_ref.module).or_default();
+            for connection in &instance.connections {
+                for end in [&connection.left, &connection.right] {
+                    if end.module == instance_ref.module {
+                        for len in 0..=end.instance_path.len() {
+                            paths.insert(&end.instance_path[..len]);
+                        }
+                    }
+                }
+            }
+        }
+
+        let nothing_connected = HashSet::new();
+        for (root_ref, root) in roots {
+            let connected = connected
+                .get(&root_ref.module)
+                .unwrap_or(&nothing_connected);
+            for diagnostic in self.unconnected_interfaces(root, connected) {
+                self.reporter.report_check(diagnostic);
+            }
+        }
+    }
+
+    /// Pair up the members of two connected interfaces by name, recursing into nested
+    /// interfaces. Interfaces connect if they share a base type, e.g. an `I2C` and an interface
+    /// declared `from I2C`; members only one side has are left unconnected, and reported unless
+    /// one side is simply a specialization of the other.
+    pub(super) fn connect_interfaces(
+        &self,
+        left_ref: &InstanceRef,
+        right_ref: &InstanceRef,
+        location: &Location,
+    ) -> EvaluatorResult<Vec<Connection>> {
+        let resolve = |instance_ref: &InstanceRef| {
+            self.resolve_instance(instance_ref).ok_or_else(|| {
+                EvaluatorError::invalid_connection(
+                    location,
+                    format!("`{}` does not exist", instance_ref),
+                )
+            })
+        };
+        let (left, right) = (resolve(left_ref)?, resolve(right_ref)?);
+
+        let left_types = self.type_ancestry(&left.type_ref);
+        if !self
+            .type_ancestry(&right.type_ref)
+            .iter()
+            .any(|t| left_types.contains(t))
+        {
+            return Err(
+                EvaluatorError::new(EvaluatorErrorKind::InvalidAssignment, location).with_message(
+                    format!(
+                        "cannot connect interfaces of different type: `{}` and `{}`",
+                        left.type_ref, right.type_ref
+                    ),
+                ),
+            );
+        }
+
+        let missing_from = |instance: &Instance, other: &Instance| {
+            let mut names: Vec<_> = other
+                .children
+                .keys()
+                .filter(|name| !instance.children.contains_key(*name))
+                .map(|name| format!("`{}`", name))
+                .collect();
+            names.sort();
+            names
+        };
+        let missing_from_left = missing_from(left, right);
+        let missing_from_right = missing_from(right, left);
+        if !missing_from_left.is_empty() && !missing_from_right.is_empty() {
+            let mut error = EvaluatorError::new(EvaluatorErrorKind::InterfaceMismatch, location)
+                .with_message(format!(
+                    "`{}` is missing {} and `{}` is missing {}; only their shared members are connected",
+                    left_ref.address(),
+                    missing_from_left.join(", "),
+                    right_ref.address(),
+                    missing_from_right.join(", ")
+                ));
+            for (instance_ref, instance) in [(left_ref, left), (right_ref, right)] {
+                if let Some(instance_location) = &instance.location {
+                    error = error.with_related(
+                        instance_location,
+                        format!("`{}` is created here", instance_ref.address()),
+                    );
+                }
+            }
+            self.reporter.report(error.into());
+        }
+
+        let mut shared: Vec<_> = left
+            .children
+            .iter()
+            .filter_map(|(name, l)| right.children.get(name).map(|r| (name, l, r)))
+            .collect();
+        shared.sort_by_key(|(name, _, _)| name.as_str());
+
+        let mut connections = vec![];
+        for (_, l, r) in shared {
+            let (l_kind, r_kind) = (resolve(l)?.kind, resolve(r)?.kind);
+            match (l_kind, r_kind) {
+                (
+                    InstanceKind::Port | InstanceKind::Pin,
+                    InstanceKind::Port | InstanceKind::Pin,
+                ) => connections.push(Connection::new(l.clone(), r.clone(), location.clone())),
+                (InstanceKind::Interface, InstanceKind::Interface) => {
+                    connections.extend(self.connect_interfaces(l, r, location)?)
+                }
+                _ => {
+                    return Err(EvaluatorError::new(
+                        EvaluatorErrorKind::InvalidAssignment,
+                        location,
+                    )
+                    .with_message(format!(
+                        "Cannot connect instances of different kind: `{}` and `{}`",
+                        l_kind, r_kind
+                    )))
+                }
+            }
+        }
+
+        Ok(connections)
+    }
+
+    /// `type_ref` followed by the types it was declared `from`, nearest first.
+    pub(super) fn type_ancestry(&self, type_ref: &ModuleRef) -> Vec<ModuleRef> {
+        let mut ancestry = vec![type_ref.clone()];
+        while let Some(parent) = self
+            .state
+            .instances
+            .get(&InstanceRef::from(ancestry.last().unwrap()))
+            .and_then(|instance| instance.parent.clone())
+        {
+            if ancestry.contains(&parent) {
+                break;
+            }
+            ancestry.push(parent);
+        }
+        ancestry
+    }
+
+    /// The interfaces of the children of the module `root` that nothing connects to, given
+    /// the `connected` paths within its tree. A connection anywhere in the module's subtree
+    /// counts, including inside the child itself.
+    fn unconnected_interfaces(
+        &self,
+        root: &Instance,
+        connected: &HashSet<&[Symbol]>,
+    ) -> Vec<AnalyzerDiagnostic> {
+        let is_connected = |interface_ref: &InstanceRef| {
+            connected.contains(interface_ref.instance_path.as_slice())
+        };
+
+        let mut children: Vec<_> = root.children.iter().collect();
+        children.sort_by_key(|(name, _)| name.as_str());
+
+        let mut diagnostics = vec![];
+        for (child_name, child_ref) in children {
+            let Some(child) = self.resolve_instance(child_ref) else {
+                continue;
+            };
+            let Some(instantiation_location) = &child.location else {
+                continue;
+            };
+
+            let mut interfaces: Vec<_> = child.children.iter().collect();
+            interfaces.sort_by_key(|(name, _)| name.as_str());
+
+            for (interface_name, interface_ref) in interfaces {
+                let Some(interface) = self.resolve_instance(interface_ref) else {
+                    continue;
+                };
+                if interface.kind != InstanceKind::Interface || is_connected(interface_ref) {
+                    continue;
+                }
+                let Some(interface_location) = &interface.location else {
+                    continue;
+                };
+
+                diagnostics.push(AnalyzerDiagnostic {
+                    severity: AnalyzerDiagnosticSeverity::Warning,
+                    kind: AnalyzerDiagnosticKind::UnconnectedInterface(
+                        UnconnectedInterfaceDiagnostic {
+                            instance_name: child_name.to_string(),
+                            interface_name: interface_name.to_string(),
+                            instantiation_location: instantiation_location.clone(),
+                            interface_location: interface_location.clone(),
+                        },
+                    ),
+                    file: instantiation_location.file.clone(),
+                });
+            }
+        }
+
+        diagnostics
+    }
+}
@@ -0,0 +1,183 @@
+//! Export of an evaluated root module as a KiCad s-expression netlist.
+
+use std::{collections::HashMap, path::Path};
+
+use super::{
+    designators::natural_sort_key, Evaluator, EvaluatorState, Instance, InstanceKind, InstanceRef,
+    ModuleRef,
+};
+use crate::{
+    sexpr::SExpr,
+    vfs::{normalize, Vfs},
+};
+
+impl EvaluatorState {
+    /// Render the root module `module_name` declared in `source_path` as a KiCad netlist.
+    /// Returns `None` if no such root module was evaluated. Components are referred to by
+    /// their reference designators, so these should be assigned first with
+    /// [`Evaluator::resolve_reference_designators`]; any that have none fall back to their
+    /// path within the root module.
+    pub fn kicad_netlist(&self, source_path: &Path, module_name: &str) -> Option<SExpr> {
+        let root: InstanceRef = ModuleRef::new(source_path, &module_name.into()).into();
+        self.instances.get(&root)?;
+
+        let mut components: Vec<(&InstanceRef, &Instance)> = self
+            .instances
+            .iter()
+            .filter(|(r, i)| {
+                r.module == root.module
+                    && !r.instance_path.is_empty()
+                    && i.kind == InstanceKind::Component
+            })
+            .collect();
+        components.sort_by_cached_key(|(r, i)| {
+            (
+                natural_sort_key(&designator(r, i)),
+                natural_sort_key(&r.sheet_path()),
+            )
+        });
+
+        let designators: HashMap<&InstanceRef, String> = components
+            .iter()
+            .map(|(r, i)| (*r, designator(r, i)))
+            .collect();
+
+        let comps = components.iter().map(|(r, i)| {
+            let mut fields = vec![
+                SExpr::field("ref", designators[r].clone()),
+                SExpr::field(
+                    "value",
+                    i.attributes
+                        .get(&"value".into())
+                        .map(|v| v.to_string())
+                        .unwrap_or_default(),
+                ),
+            ];
+            if let Some(footprint) = i.attributes.get(&"footprint".into()) {
+                fields.push(SExpr::field("footprint", footprint.to_string()));
+            }
+            fields.push(SExpr::node(
+                "sheetpath",
+                [
+                    SExpr::field("names", r.sheet_path()),
+                    SExpr::field("tstamps", "/"),
+                ],
+            ));
+            SExpr::node("comp", fields)
+        });
+
+        let mut nets = Vec::new();
+        for net in self.nets.get(&root).into_iter().flatten() {
+            let mut nodes: Vec<(String, String)> = net
+                .members()
+                .iter()
+                .filter(|m| self.instances.get(*m).map(|i| i.kind) == Some(InstanceKind::Pin))
+                .filter_map(|m| {
+                    let mut component_ref = m.clone();
+                    let pin = component_ref.pop()?;
+                    let designator = designators.get(&component_ref)?;
+                    Some((designator.clone(), pin.to_string()))
+                })
+                .collect();
+            if nodes.is_empty() {
+                continue;
+            }
+            nodes.sort();
+
+            let code = nets.len() + 1;
+            nets.push(SExpr::node(
+                "net",
+                [
+                    SExpr::field("code", code.to_string()),
+                    SExpr::field("name", net.name()),
+                ]
+                .into_iter()
+                .chain(nodes.into_iter().map(|(designator, pin)| {
+                    SExpr::node(
+                        "node",
+                        [SExpr::field("ref", designator), SExpr::field("pin", pin)],
+                    )
+                })),
+            ));
+        }
+
+        Some(SExpr::node(
+            "export",
+            [
+                SExpr::field("version", "E"),
+                SExpr::node(
+                    "design",
+                    [
+                        SExpr::field("source", source_path.display().to_string()),
+                        SExpr::field("tool", "atopile_analyzer"),
+                    ],
+                ),
+                SExpr::node("components", comps),
+                SExpr::node("nets", nets),
+            ],
+        ))
+    }
+}
+
+impl<V: Vfs> Evaluator<V> {
+    /// Write the KiCad netlist of the root module `module_name` declared in `source_path` to
+    /// `out`, once every component has been given a reference designator. `out` must be the
+    /// netlist path of one of the builds, or within the build directory of the project
+    /// `source_path` belongs to; nothing is written anywhere else.
+    pub fn write_kicad_netlist(
+        &mut self,
+        source_path: &Path,
+        module_name: &str,
+        out: &Path,
+    ) -> anyhow::Result<()> {
+        // The file may not exist yet, so resolve the directory it goes in.
+        let out = match (out.parent(), out.file_name()) {
+            (Some(dir), Some(name)) => self
+                .db
+                .canonicalize(dir)
+                .map(|dir| dir.join(name))
+                .unwrap_or_else(|_| normalize(out)),
+            _ => normalize(out),
+        };
+        let is_build_output = self
+            .builds()
+            .into_iter()
+            .any(|build| build.netlist_path.as_ref() == Some(&out))
+            || self
+                .db
+                .project(source_path)
+                .is_some_and(|project| out.starts_with(project.build_dir()));
+        if !is_build_output {
+            anyhow::bail!(
+                "netlists can only be written to the project's build directory, not {}",
+                out.display()
+            );
+        }
+
+        self.resolve_reference_designators();
+        let netlist = self
+            .state
+            .kicad_netlist(source_path, module_name)
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "module `{}` not found in {}",
+                    module_name,
+                    source_path.display()
+                )
+            })?;
+        std::fs::write(&out, format!("{}\n", netlist))?;
+        Ok(())
+    }
+}
+
+/// The reference designator of a component, falling back to its path within the root module.
+fn designator(instance_ref: &InstanceRef, instance: &Instance) -> String {
+    instance.reference_designator.clone().unwrap_or_else(|| {
+        instance_ref
+            .instance_path
+            .iter()
+            .map(|s| s.to_string())
+            .collect::<Vec<_>>()
+            .join(".")
+    })
+}
@@ -0,0 +1,208 @@
+//! Groups the pairwise connections recorded during evaluation into electrical nets.
+
+use std::{
+    collections::{HashMap, HashSet},
+    path::PathBuf,
+};
+
+use serde::Serialize;
+
+use super::{EvaluatorState, Instance, InstanceKind, InstanceRef, ModuleRef};
+use crate::Location;
+
+/// A set of pins and ports that are electrically connected within a root module.
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct Net {
+    /// An identifier for the net: the path of the left-hand side of the first `~` statement
+    /// forming it, in source order and preferring the root module's own statements over those
+    /// of its children, or of its only member if nothing is connected to it.
+    ///
+    /// It is stable against connecting more members with statements after that one, adding,
+    /// changing or removing other nets, and edits that move statements without reordering
+    /// them. It changes when a statement connecting to the net is inserted before its first
+    /// one, when that statement is removed or its left-hand side changed, or when the net is
+    /// merged into another.
+    id: String,
+    /// The display name of the net, preferring the shallowest signal over pins.
+    name: String,
+    /// All pins and ports on this net, sorted by path.
+    members: Vec<InstanceRef>,
+    /// The locations of the `~` statements that formed this net.
+    locations: Vec<Location>,
+}
+
+impl Net {
+    pub(crate) fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub(crate) fn members(&self) -> &[InstanceRef] {
+        &self.members
+    }
+}
+
+/// A union-find structure over instance references.
+#[derive(Default)]
+struct DisjointSet {
+    parents: HashMap<InstanceRef, InstanceRef>,
+}
+
+impl DisjointSet {
+    fn find(&mut self, instance_ref: &InstanceRef) -> InstanceRef {
+        let parent = match self.parents.get(instance_ref) {
+            Some(parent) if parent != instance_ref => parent.clone(),
+            Some(_) => return instance_ref.clone(),
+            None => {
+                self.parents
+                    .insert(instance_ref.clone(), instance_ref.clone());
+                return instance_ref.clone();
+            }
+        };
+
+        let root = self.find(&parent);
+        self.parents.insert(instance_ref.clone(), root.clone());
+        root
+    }
+
+    fn union(&mut self, a: &InstanceRef, b: &InstanceRef) {
+        let a = self.find(a);
+        let b = self.find(b);
+        if a != b {
+            self.parents.insert(a, b);
+        }
+    }
+}
+
+pub(super) fn relative_path(instance_ref: &InstanceRef) -> String {
+    instance_ref
+        .instance_path
+        .iter()
+        .map(|s| s.to_string())
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+impl EvaluatorState {
+    /// Recompute the nets of the root modules declared in `files` from the connections on their
+    /// instances, and drop the nets of root modules that no longer exist.
+    pub(super) fn compute_nets(&mut self, files: &HashSet<PathBuf>) {
+        let instances = &self.instances;
+        self.nets.retain(|root, _| instances.contains_key(root));
+
+        // Every instance belongs to the tree of the root module it was declared in.
+        let mut trees: HashMap<&ModuleRef, Vec<(&InstanceRef, &Instance)>> = HashMap::new();
+        for (instance_ref, instance) in instances {
+            if files.contains(&instance_ref.module.source_path) {
+                trees
+                    .entry(&instance_ref.module)
+                    .or_default()
+                    .push((instance_ref, instance));
+            }
+        }
+
+        let nets: Vec<(InstanceRef, Vec<Net>)> = trees
+            .into_values()
+            .filter_map(|tree| {
+                let (root, _) = tree.iter().find(|(r, _)| r.instance_path.is_empty())?;
+                Some(((*root).clone(), self.nets_for_tree(&tree)))
+            })
+            .collect();
+        self.nets.extend(nets);
+    }
+
+    /// Compute the nets of a root module from all the instances in its tree.
+    fn nets_for_tree(&self, tree: &[(&InstanceRef, &Instance)]) -> Vec<Net> {
+        let mut set = DisjointSet::default();
+        let mut connection_locations = Vec::new();
+
+        for &(instance_ref, instance) in tree {
+            if matches!(instance.kind, InstanceKind::Port | InstanceKind::Pin) {
+                set.find(instance_ref);
+            }
+
+            for connection in &instance.connections {
+                set.union(&connection.left, &connection.right);
+                connection_locations.push(connection);
+            }
+        }
+
+        // Group the pins and ports by the root of their set.
+        let mut groups: HashMap<InstanceRef, Vec<InstanceRef>> = HashMap::new();
+        let members: Vec<InstanceRef> = set.parents.keys().cloned().collect();
+        for member in members {
+            let is_electrical = self
+                .instances
+                .get(&member)
+                .is_some_and(|i| matches!(i.kind, InstanceKind::Port | InstanceKind::Pin));
+            if is_electrical {
+                let net_root = set.find(&member);
+                groups.entry(net_root).or_default().push(member);
+            }
+        }
+
+        let location_key =
+            |l: &Location| (l.file.clone(), l.range.start.line, l.range.start.column);
+
+        let mut locations: HashMap<InstanceRef, Vec<Location>> = HashMap::new();
+        // The left-hand side of the first `~` statement of each net, which names its id. The
+        // root module's own statements come before those copied in from its children.
+        let mut first_connections: HashMap<InstanceRef, (usize, &Location, String)> =
+            HashMap::new();
+        for connection in connection_locations {
+            let net_root = set.find(&connection.left);
+            let first = (
+                connection.provenance.len(),
+                &connection.location,
+                relative_path(&connection.left),
+            );
+            let is_first = first_connections.get(&net_root).is_none_or(|current| {
+                (first.0, location_key(first.1), &first.2)
+                    < (current.0, location_key(current.1), &current.2)
+            });
+            if is_first {
+                first_connections.insert(net_root.clone(), first);
+            }
+
+            let net_locations = locations.entry(net_root).or_default();
+            if !net_locations.contains(&connection.location) {
+                net_locations.push(connection.location.clone());
+            }
+        }
+
+        let mut nets: Vec<Net> = groups
+            .into_iter()
+            .map(|(net_root, mut members)| {
+                members.sort_by_key(relative_path);
+
+                let id = first_connections
+                    .remove(&net_root)
+                    .map(|(_, _, path)| path)
+                    .unwrap_or_else(|| relative_path(&members[0]));
+                let name = members
+                    .iter()
+                    .min_by_key(|m| {
+                        let is_pin = self
+                            .instances
+                            .get(*m)
+                            .is_some_and(|i| i.kind == InstanceKind::Pin);
+                        (is_pin, m.len(), relative_path(m))
+                    })
+                    .map(relative_path)
+                    .unwrap_or_else(|| id.clone());
+
+                let mut locations = locations.remove(&net_root).unwrap_or_default();
+                locations.sort_by_key(location_key);
+
+                Net {
+                    id,
+                    name,
+                    members,
+                    locations,
+                }
+            })
+            .collect();
+
+        nets.sort_by(|a, b| a.id.cmp(&b.id));
+        nets
+    }
+}
@@ -1,28 +1,38 @@
+mod completions;
+pub mod db;
 pub mod diagnostics;
 pub mod evaluator;
+pub mod netlist;
+pub mod project;
+mod references;
+pub mod sexpr;
+pub mod units;
+pub mod vfs;
 
 use std::{
-    collections::HashMap,
     fmt::{Debug, Display},
     ops::Deref,
     path::{Path, PathBuf},
-    sync::{Arc, Mutex},
+    sync::Arc,
 };
 
 use anyhow::{Context, Result};
 use atopile_parser::{
+    lexer::KEYWORDS,
     parser::{BlockStmt, Connectable, Expr, PortRef, Stmt, Symbol},
     AtopileSource, Position, Span, Spanned,
 };
-use evaluator::{resolve_import_path, Evaluator};
+use evaluator::{resolve_import_path, Evaluator, ModuleRef};
 use log::{debug, info, warn};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use vfs::{normalize, OsVfs, Vfs};
 
 use diagnostics::*;
 
+pub use crate::completions::{Completion, CompletionKind};
 pub use crate::evaluator::EvaluatorState;
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 pub struct Location {
     // TODO: Windows and Unix paths don't play nice together in snapshot tests, so just skip
     // serialization for now.
@@ -37,12 +47,21 @@ impl Display for Location {
     }
 }
 
-#[derive(Debug, Clone, Copy, Serialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Range {
     pub start: Position,
     pub end: Position,
 }
 
+impl Range {
+    /// Whether `position` is within the range, including its end, so that a cursor right after
+    /// a name is still on it.
+    pub fn contains(&self, position: Position) -> bool {
+        let key = |position: Position| (position.line, position.column);
+        key(self.start) <= key(position) && key(position) <= key(self.end)
+    }
+}
+
 #[derive(Debug)]
 pub struct Located<T>(T, Location);
 
@@ -138,6 +157,14 @@ pub struct GotoDefinitionResult {
     pub target_selection_range: Range,
 }
 
+/// A result from a hover request.
+#[derive(Debug)]
+pub struct Hover {
+    /// A Markdown description of whatever is under the cursor.
+    pub contents: String,
+    pub range: Range,
+}
+
 trait AtopileSourceExt {
     #[allow(clippy::only_used_in_recursion)]
     fn port_ref_at_in_expr<'a>(
@@ -147,9 +174,15 @@ trait AtopileSourceExt {
     ) -> Option<&'a Spanned<PortRef>>;
 
     /// Returns a PortRef that is at the given index into the source file, if there is one.
-    #[allow(dead_code)]
     fn port_ref_at(&self, index: usize) -> Option<&Spanned<PortRef>>;
 
+    /// Returns the top-level block that the given index into the source file is in, if any.
+    fn block_at(&self, index: usize) -> Option<&BlockStmt>;
+
+    /// Returns the name of the signal, pin or attribute declared at the given index into the
+    /// source file, if there is one.
+    fn member_name_at(&self, index: usize) -> Option<(&str, &Span)>;
+
     #[allow(clippy::only_used_in_recursion)]
     fn symbol_name_at_in_expr<'a>(
         &self,
@@ -210,28 +243,47 @@ impl AtopileSourceExt for AtopileSource {
                     None
                 }
             }
-            Stmt::Connect(connect) => {
-                if let Connectable::Port(port) = &connect.left.deref() {
-                    if port.span().contains(&index) {
-                        Some(port)
-                    } else {
-                        None
-                    }
-                } else if let Connectable::Port(port) = &connect.right.deref() {
-                    if port.span().contains(&index) {
-                        Some(port)
-                    } else {
-                        None
-                    }
-                } else {
-                    None
-                }
-            }
+            Stmt::Connect(connect) => [&connect.left, &connect.right].into_iter().find_map(
+                |connectable| match connectable.deref() {
+                    Connectable::Port(port) if port.span().contains(&index) => Some(port),
+                    _ => None,
+                },
+            ),
             Stmt::Assert(assert) => self.port_ref_at_in_expr(index, &assert.expr),
             _ => None,
         }
     }
 
+    fn block_at(&self, index: usize) -> Option<&BlockStmt> {
+        self.ast().iter().find_map(|stmt| match stmt.deref() {
+            Stmt::Block(block) if stmt.span().contains(&index) => Some(block),
+            _ => None,
+        })
+    }
+
+    fn member_name_at(&self, index: usize) -> Option<(&str, &Span)> {
+        let stmt = self.stmt_at(index)?;
+        let (name, span) = match &stmt.deref() {
+            Stmt::Signal(signal) => (signal.name.as_str(), signal.name.span()),
+            Stmt::Pin(pin) => (pin.name.as_str(), pin.name.span()),
+            Stmt::Attribute(attribute) => (attribute.name.as_str(), attribute.name.span()),
+            Stmt::Connect(connect) => {
+                [&connect.left, &connect.right]
+                    .into_iter()
+                    .find_map(|connectable| match connectable.deref() {
+                        Connectable::Signal(name) | Connectable::Pin(name)
+                            if name.span().contains(&index) =>
+                        {
+                            Some((name.as_str(), name.span()))
+                        }
+                        _ => None,
+                    })?
+            }
+            _ => return None,
+        };
+        span.contains(&index).then_some((name, span))
+    }
+
     #[allow(clippy::only_used_in_recursion)]
     fn symbol_name_at_in_expr<'a>(
         &self,
@@ -291,62 +343,23 @@ impl AtopileSourceExt for AtopileSource {
         }
     }
 }
-pub(crate) struct FileCache {
-    files: Mutex<HashMap<PathBuf, FileCacheEntry>>,
-}
 
-#[derive(Clone)]
-struct FileCacheEntry {
-    source: Arc<AtopileSource>,
+/// The index of the part of `port` under the given index into the source file, or of the one
+/// before it if the index is on a `.`.
+fn part_at(port: &PortRef, index: usize) -> Option<usize> {
+    port.parts
+        .iter()
+        .rposition(|part| part.span().start <= index)
 }
 
-impl FileCacheEntry {
-    pub fn new(source: Arc<AtopileSource>) -> Self {
-        Self { source }
-    }
-}
-
-impl FileCache {
-    pub fn new() -> Self {
-        Self {
-            files: Mutex::new(HashMap::new()),
-        }
-    }
-
-    pub fn get(&self, path: &Path) -> Option<Arc<AtopileSource>> {
-        self.files
-            .lock()
-            .unwrap()
-            .get(path)
-            .map(|e| e.source.clone())
-    }
-
-    pub fn insert(&self, path: PathBuf, source: Arc<AtopileSource>) {
-        self.files
-            .lock()
-            .unwrap()
-            .insert(path, FileCacheEntry::new(source));
-    }
-
-    pub fn remove(&self, path: &Path) {
-        self.files.lock().unwrap().remove(path);
-    }
-}
-
-pub struct AtopileAnalyzer {
-    files: Arc<FileCache>,
-    evaluator: Evaluator,
+pub struct AtopileAnalyzer<V: Vfs = OsVfs> {
+    evaluator: Evaluator<V>,
     open_files: std::collections::HashSet<PathBuf>,
 }
 
 impl AtopileAnalyzer {
     pub fn new() -> Self {
-        let files = Arc::new(FileCache::new());
-        Self {
-            files: files.clone(),
-            evaluator: Evaluator::default(),
-            open_files: std::collections::HashSet::new(),
-        }
+        Self::with_vfs(OsVfs)
     }
 }
 
@@ -356,7 +369,15 @@ impl Default for AtopileAnalyzer {
     }
 }
 
-impl AtopileAnalyzer {
+impl<V: Vfs> AtopileAnalyzer<V> {
+    /// Create an analyzer that reads files which aren't open in the editor from `vfs`.
+    pub fn with_vfs(vfs: V) -> Self {
+        Self {
+            evaluator: Evaluator::with_vfs(vfs),
+            open_files: std::collections::HashSet::new(),
+        }
+    }
+
     #[cfg(test)]
     pub fn evaluate_source_for_test(&self, source: &AtopileSource) -> EvaluatorState {
         let mut evaluator = Evaluator::default();
@@ -364,48 +385,92 @@ impl AtopileAnalyzer {
         evaluator.state().clone()
     }
 
-    /// Load the source file at the given path. Will first check the in-memory cache maintained by
-    /// `set_source`, and if not found, read from the filesystem.
+    /// Load the source file at the given path, as seen by the analysis database.
     fn load_source(&self, path: &PathBuf) -> Result<Arc<AtopileSource>> {
         debug!("loading source: {:?}", path);
-        let path = path.canonicalize()?;
-
-        if let Some(source) = self.files.get(&path) {
-            debug!("source already loaded: {:?}", path);
-            return Ok(source.clone());
+        let path = self.canonicalize(path);
+        self.evaluator
+            .db()
+            .source(&path)
+            .context("Failed to read source file")
+    }
+
+    /// The canonical form of `path`, or `path` with `.` and `..` resolved if it doesn't exist,
+    /// e.g. for a new file that hasn't been saved yet.
+    fn canonicalize(&self, path: &Path) -> PathBuf {
+        self.evaluator
+            .db()
+            .canonicalize(path)
+            .unwrap_or_else(|_| normalize(path))
+    }
+
+    /// Convert `position` in the file at `path`, whose column counts UTF-16 code units as the
+    /// editor's do, to one whose column is a byte offset, as the analyzer uses.
+    pub fn position_from_utf16(&self, path: &Path, position: Position) -> Position {
+        match self.load_source(&path.to_path_buf()) {
+            Ok(source) => source.position_from_utf16(position),
+            Err(_) => position,
         }
+    }
 
-        debug!("loading source from disk: {:?}", path);
-        let content = std::fs::read_to_string(&path).context("Failed to read source file")?;
-        let source = AtopileSource::new(content, path.to_path_buf());
-        Ok(Arc::new(source))
+    /// Convert `position` in the file at `path`, whose column is a byte offset, to one whose
+    /// column counts UTF-16 code units, as the editor's do.
+    pub fn position_to_utf16(&self, path: &Path, position: Position) -> Position {
+        match self.load_source(&path.to_path_buf()) {
+            Ok(source) => source.position_to_utf16(position),
+            Err(_) => position,
+        }
     }
 
     /// Set the source file at the given path.
     pub fn set_source(&mut self, path: &Path, source: Arc<AtopileSource>) -> Result<()> {
-        let path = path.canonicalize()?;
-        self.files.insert(path.clone(), source.clone());
-        self.evaluator.set_source(&path, source.clone());
+        let path = self.canonicalize(path);
+        self.evaluator.set_source(&path, source);
+        self.evaluate_project_builds(&path);
+        Ok(())
+    }
+
+    /// Evaluate only the builds of the project of the file at `path` if its `ato.yaml`
+    /// declares any, unless they already are.
+    fn evaluate_project_builds(&mut self, path: &Path) {
+        let Some(project) = self.evaluator.db().project(path) else {
+            return;
+        };
+        if project.config().builds.is_empty()
+            || self.evaluator.evaluated_project() == Some(project.root())
+        {
+            return;
+        }
+        info!("evaluating the builds of {:?}", project.root());
+        self.evaluator.evaluate_project(path);
+    }
+
+    /// Read the `ato.yaml` at the given path again after it has been created, changed or
+    /// deleted.
+    pub fn reload_config(&mut self, path: &Path) -> Result<()> {
+        let path = self.canonicalize(path);
+        self.evaluator.reload_config(&path);
+        self.evaluate_project_builds(&path);
         Ok(())
     }
 
     /// Remove the source file at the given path.
     pub fn remove_source(&mut self, path: &Path) -> Result<()> {
-        self.files.remove(&path.canonicalize()?);
-        self.open_files.remove(&path.canonicalize()?);
-        self.evaluator.remove_source(path);
+        let path = self.canonicalize(path);
+        self.open_files.remove(&path);
+        self.evaluator.remove_source(&path);
         Ok(())
     }
 
     /// Mark a file as open in the editor.
     pub fn mark_file_open(&mut self, path: &Path) -> Result<()> {
-        self.open_files.insert(path.canonicalize()?);
+        self.open_files.insert(self.canonicalize(path));
         Ok(())
     }
 
     /// Mark a file as closed in the editor.
     pub fn mark_file_closed(&mut self, path: &Path) -> Result<()> {
-        self.open_files.remove(&path.canonicalize()?);
+        self.open_files.remove(&self.canonicalize(path));
         Ok(())
     }
 
@@ -430,74 +495,6 @@ impl AtopileAnalyzer {
         Ok(diagnostics)
     }
 
-    /// Find the BlockStmt that defines the given name, traversing through imports as necessary.
-    fn find_definition(
-        &self,
-        source: &AtopileSource,
-        name: &str,
-    ) -> Result<Option<Located<BlockStmt>>> {
-        if let Some(block) = self.find_definition_in_source(source, name) {
-            // The definition is in this file, so just return it.
-            Ok(Some(Located::from_spanned(
-                block.to_owned(),
-                source,
-                source.path(),
-            )))
-        } else {
-            // Let's see if we import this symbol, then recurse.
-            debug!("looking for import for {:?}", name);
-            let imported_file = source
-                .traverse_all_stmts()
-                .filter_map(|(stmt, _)| match stmt.deref() {
-                    Stmt::Import(import) => Some((
-                        import.from_path.deref(),
-                        import.imports.iter().map(|i| i.deref().clone()).collect(),
-                    )),
-                    Stmt::DepImport(import) => {
-                        Some((import.from_path.deref(), vec![import.name.deref().clone()]))
-                    }
-                    _ => None,
-                })
-                .find(|(_import_path, imports)| imports.iter().any(|i| i.deref() == name))
-                .map(|(import_path, _imports)| {
-                    let path = resolve_import_path(source.path(), Path::new(import_path));
-                    debug!("resolved import path: {:?}", path);
-                    path
-                })
-                .context(format!("failed to resolve import path for {:?}", name))?
-                .map(|import| self.load_source(&import))
-                .transpose()
-                .context(format!("failed to load source for import {:?}", name))?;
-
-            if let Some(imported_file) = imported_file {
-                debug!("found imported file: {:?}", imported_file.path());
-                self.find_definition(&imported_file, name)
-            } else {
-                warn!(
-                    "can't find definition for {:?}: no matching import found",
-                    name
-                );
-                Ok(None)
-            }
-        }
-    }
-
-    /// Find where a block with the given name is defined in this source file.
-    fn find_definition_in_source<'a>(
-        &self,
-        source: &'a AtopileSource,
-        name: &str,
-    ) -> Option<Spanned<&'a BlockStmt>> {
-        source
-            .ast()
-            .iter()
-            .filter_map(|s| match s.deref() {
-                Stmt::Block(block) => Some((block, s.span().clone()).into()),
-                _ => None,
-            })
-            .find(|b: &Spanned<&BlockStmt>| b.deref().name.deref().to_string() == name)
-    }
-
     /// Create a GotoDefinitionResult for the path component of an import, e.g. `path/to/file.ato`
     /// here:
     /// ```ato
@@ -512,11 +509,15 @@ impl AtopileAnalyzer {
         let source_range_start = source.index_to_position(path_token.span().start);
         let source_range_end = source.index_to_position(path_token.span().end);
 
-        let resolved_path = resolve_import_path(source_path, Path::new(path_token.deref()))
-            .context(format!(
-                "failed to resolve import path for {:?}",
-                path_token
-            ))?;
+        let resolved_path = resolve_import_path(
+            self.evaluator.db(),
+            source_path,
+            Path::new(path_token.deref()),
+        )
+        .context(format!(
+            "failed to resolve import path for {:?}",
+            path_token
+        ))?;
 
         Ok(Some(GotoDefinitionResult {
             file: resolved_path,
@@ -540,21 +541,60 @@ impl AtopileAnalyzer {
         source: &AtopileSource,
         symbol: &Spanned<Symbol>,
     ) -> Result<Option<GotoDefinitionResult>> {
-        let def = self.find_definition(source, symbol.deref())?;
-
-        if let Some(def) = def {
-            Ok(Some(GotoDefinitionResult {
-                file: def.location().file.clone(),
-                source_range: Range {
-                    start: source.index_to_position(symbol.span().start),
-                    end: source.index_to_position(symbol.span().end),
-                },
-                target_range: def.location().range,
-                target_selection_range: def.location().range,
-            }))
-        } else {
-            Ok(None)
-        }
+        let Some(def) = self.evaluator.db().definition(source.path(), symbol) else {
+            warn!("can't find definition for {:?}", symbol.deref());
+            return Ok(None);
+        };
+
+        Ok(Some(GotoDefinitionResult {
+            file: def.file.clone(),
+            source_range: Range {
+                start: source.index_to_position(symbol.span().start),
+                end: source.index_to_position(symbol.span().end),
+            },
+            target_range: def.range,
+            target_selection_range: def.range,
+        }))
+    }
+
+    /// Create a GotoDefinitionResult for the part of a port reference under the cursor, e.g. `p1`
+    /// here:
+    /// ```ato
+    /// r1.p1 ~ power.vcc
+    /// ```
+    fn handle_goto_definition_for_port(
+        &self,
+        source: &AtopileSource,
+        index: usize,
+        port: &Spanned<PortRef>,
+    ) -> Result<Option<GotoDefinitionResult>> {
+        let Some(block) = source.block_at(index) else {
+            return Ok(None);
+        };
+        let Some(end) = part_at(port, index) else {
+            return Ok(None);
+        };
+        let parts: Vec<_> = port.parts[..=end]
+            .iter()
+            .map(|part| part.as_str())
+            .collect();
+
+        let block = ModuleRef::new(source.path(), &block.name);
+        let Some(def) = self.evaluator.db().port_definition(&block, &parts) else {
+            warn!("can't find definition for {:?}", parts);
+            return Ok(None);
+        };
+
+        let part = &port.parts[end];
+        Ok(Some(GotoDefinitionResult {
+            file: def.file.clone(),
+            source_range: Range {
+                start: source.index_to_position(part.span().start),
+                end: source.index_to_position(part.span().end),
+            },
+            target_range: def.range,
+            target_selection_range: def.range,
+        }))
     }
 
     /// Attempt to resolve a goto definition request at the given position.
@@ -567,11 +607,15 @@ impl AtopileAnalyzer {
 
         let index = source.position_to_index(position);
         let symbol = source.symbol_name_at(index);
+        let port = source.port_ref_at(index);
         let file_path = source.file_path_at(index);
 
         if let Some(symbol) = symbol {
             info!("goto definition for symbol: {:?}", symbol);
             self.handle_goto_definition_for_symbol(&source, symbol)
+        } else if let Some(port) = port {
+            info!("goto definition for port: {:?}", port);
+            self.handle_goto_definition_for_port(&source, index, port)
         } else if let Some(file_path) = file_path {
             info!("goto definition for path: {:?}", file_path);
             self.handle_goto_definition_path(&source, path, file_path)
@@ -581,8 +625,241 @@ impl AtopileAnalyzer {
         }
     }
 
+    /// Describe the block, instance, signal, pin or attribute at the given position, as it was
+    /// last evaluated.
+    pub fn hover(&self, path: &Path, position: Position) -> Result<Option<Hover>> {
+        let source = self.load_source(&path.to_path_buf())?;
+        let index = source.position_to_index(position);
+        let block = source.block_at(index);
+        let range = |span: &Span| Range {
+            start: source.index_to_position(span.start),
+            end: source.index_to_position(span.end),
+        };
+
+        // Block names, where they're declared or used as a type.
+        let symbol = source.symbol_name_at(index).or_else(|| {
+            block
+                .map(|block| &block.name)
+                .filter(|name| name.span().contains(&index))
+        });
+        if let Some(symbol) = symbol {
+            let scope = self.evaluator.db().file_scope(source.path());
+            return Ok(scope
+                .resolve(symbol)
+                .and_then(|module| self.evaluator.describe_block(module))
+                .map(|contents| Hover {
+                    contents,
+                    range: range(symbol.span()),
+                }));
+        }
+
+        let Some(block) = block else {
+            return Ok(None);
+        };
+        let block = ModuleRef::new(source.path(), &block.name);
+
+        // Parts of port references, up to the one under the cursor.
+        if let Some(port) = source.port_ref_at(index) {
+            let Some(end) = part_at(port, index) else {
+                return Ok(None);
+            };
+            let parts: Vec<_> = port.parts[..=end]
+                .iter()
+                .map(|part| part.as_str())
+                .collect();
+            return Ok(self
+                .evaluator
+                .describe_port(&block, &parts)
+                .map(|contents| Hover {
+                    contents,
+                    range: range(port.parts[end].span()),
+                }));
+        }
+
+        // Signals, pins and attributes where they're declared.
+        if let Some((name, span)) = source.member_name_at(index) {
+            return Ok(self
+                .evaluator
+                .describe_port(&block, &[name])
+                .map(|contents| Hover {
+                    contents,
+                    range: range(span),
+                }));
+        }
+
+        Ok(None)
+    }
+
+    /// The name at the given position, if it refers to something declared.
+    fn occurrence_at(&self, path: &Path, position: Position) -> Option<references::Occurrence> {
+        self.evaluator
+            .db()
+            .occurrences(&self.canonicalize(path))
+            .into_iter()
+            .find(|occurrence| occurrence.location.range.contains(position))
+    }
+
+    /// The files to search for references from the files at `paths`: every file of their
+    /// projects, along with every file the analyzer has loaded.
+    fn files_to_search(&self, paths: &[&Path]) -> Vec<PathBuf> {
+        let db = self.evaluator.db();
+        let mut files = db.paths();
+        for project in paths.iter().filter_map(|path| db.project(path)) {
+            files.extend(db.find_sources(project.root()));
+        }
+        files.sort();
+        files.dedup();
+        files
+    }
+
+    /// Find every reference to the block, instance, signal, pin or attribute at the given
+    /// position, optionally including its declaration.
+    pub fn find_references(
+        &self,
+        path: &Path,
+        position: Position,
+        include_declaration: bool,
+    ) -> Result<Vec<Location>> {
+        let Some(target) = self.occurrence_at(path, position) else {
+            return Ok(vec![]);
+        };
+
+        let db = self.evaluator.db();
+        let path = self.canonicalize(path);
+        Ok(self
+            .files_to_search(&[&path, &target.definition.file])
+            .iter()
+            .flat_map(|path| db.occurrences(path))
+            .filter(|occurrence| {
+                occurrence.definition == target.definition
+                    && (include_declaration || !occurrence.is_declaration)
+            })
+            .map(|occurrence| occurrence.location)
+            .collect())
+    }
+
+    /// The name at the given position, if it can be renamed: names of things declared by
+    /// installed packages and builtin attributes are left alone.
+    fn renameable_at(&self, path: &Path, position: Position) -> Option<references::Occurrence> {
+        let occurrence = self.occurrence_at(path, position)?;
+        if evaluator::is_dependency(&occurrence.definition.file) {
+            return None;
+        }
+
+        let source = self.load_source(&occurrence.location.file).ok()?;
+        let range = occurrence.location.range;
+        let name = &source.text()
+            [source.position_to_index(range.start)..source.position_to_index(range.end)];
+        if evaluator::BUILTIN_ATTRIBUTES.contains(&name) {
+            return None;
+        }
+        Some(occurrence)
+    }
+
+    /// Why renaming `target` to `new_name` would clash with another name, if it would. A block
+    /// clashes with the blocks declared or imported by its own file and every file referring
+    /// to it (`references`); an instance, signal, pin or attribute clashes with the other
+    /// members of the block declaring it, including inherited ones.
+    fn rename_conflict(
+        &self,
+        target: &references::Occurrence,
+        new_name: &str,
+        references: &[Location],
+    ) -> Option<String> {
+        let db = self.evaluator.db();
+        let new_name = Symbol::from(new_name);
+        let definition = &target.definition;
+        let declarations = db.block_declarations(&definition.file);
+
+        if let Some(block) = declarations
+            .blocks
+            .iter()
+            .find(|declaration| &declaration.location == definition)
+        {
+            let block_ref = ModuleRef::new(&definition.file, &block.name);
+            let mut files: Vec<&Path> = references
+                .iter()
+                .map(|location| location.file.as_path())
+                .collect();
+            files.push(&definition.file);
+            files.sort();
+            files.dedup();
+
+            return files.into_iter().find_map(|file| {
+                let existing = db.file_scope(file).resolve(&new_name)?.clone();
+                (existing != block_ref).then(|| {
+                    format!(
+                        "`{}` is already declared or imported in {}",
+                        new_name,
+                        file.display()
+                    )
+                })
+            });
+        }
+
+        let block = declarations.blocks.iter().find(|declaration| {
+            declaration.location.range.contains(definition.range.start)
+                && declaration.location.range.contains(definition.range.end)
+        })?;
+        let block_ref = ModuleRef::new(&definition.file, &block.name);
+        let existing = db.port_definition(&block_ref, &[new_name.as_str()])?;
+        (&existing != definition)
+            .then(|| format!("`{}` already has a member named `{}`", block.name, new_name))
+    }
+
+    /// The range of the name at the given position, if it can be renamed.
+    pub fn prepare_rename(&self, path: &Path, position: Position) -> Result<Option<Range>> {
+        Ok(self
+            .renameable_at(path, position)
+            .map(|occurrence| occurrence.location.range))
+    }
+
+    /// Rename the block, instance, signal, pin or attribute at the given position, returning
+    /// the edits to make to every file that refers to it.
+    pub fn rename(&self, path: &Path, position: Position, new_name: &str) -> Result<Vec<TextEdit>> {
+        let is_identifier = new_name
+            .chars()
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+            && new_name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !is_identifier || KEYWORDS.contains(&new_name) {
+            anyhow::bail!("`{}` is not a valid name", new_name);
+        }
+        let Some(target) = self.renameable_at(path, position) else {
+            anyhow::bail!("This can't be renamed");
+        };
+
+        let references = self.find_references(path, position, true)?;
+        if let Some(conflict) = self.rename_conflict(&target, new_name, &references) {
+            anyhow::bail!(conflict);
+        }
+
+        Ok(references
+            .into_iter()
+            .map(|location| TextEdit {
+                location,
+                new_text: new_name.to_string(),
+            })
+            .collect())
+    }
+
     pub fn get_netlist(&mut self) -> &EvaluatorState {
         self.evaluator.resolve_reference_designators();
         self.evaluator.state()
     }
+
+    /// The elaborated tree of each build, for showing a design from its top level.
+    pub fn get_builds(&mut self) -> Vec<evaluator::Build> {
+        self.evaluator.resolve_reference_designators();
+        self.evaluator.build_trees()
+    }
+
+    /// Write the KiCad netlist of the module `module_name` declared in `path` to `out`, which
+    /// must be in the project's build directory.
+    pub fn write_netlist(&mut self, path: &Path, module_name: &str, out: &Path) -> Result<()> {
+        let path = self.canonicalize(path);
+        self.evaluator.write_kicad_netlist(&path, module_name, out)
+    }
 }
@@ -0,0 +1,117 @@
+//! A typed reader for KiCad s-expression netlists, such as `build/default.net`.
+
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use crate::sexpr::{self, SExpr, SExprError};
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct KicadNetlist {
+    pub components: Vec<KicadComponent>,
+    pub nets: Vec<KicadNet>,
+    /// The index into `components` of the first component at each sheetpath, as read.
+    #[serde(skip)]
+    by_sheetpath: HashMap<String, usize>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct KicadComponent {
+    pub reference: String,
+    pub value: Option<String>,
+    pub footprint: Option<String>,
+    /// The atopile address of the component, e.g. `path/to/file.ato:Root::r1`.
+    pub sheetpath: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct KicadNet {
+    pub code: Option<String>,
+    pub name: String,
+    pub nodes: Vec<KicadNode>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct KicadNode {
+    pub reference: String,
+    pub pin: String,
+}
+
+impl KicadNetlist {
+    pub fn parse(input: &str) -> Result<Self, SExprError> {
+        let root = sexpr::parse(input)?;
+        if root.name() != Some("export") {
+            return Err(SExprError {
+                message: "expected `(export ...)` netlist".to_string(),
+                position: atopile_parser::Position { line: 0, column: 0 },
+            });
+        }
+
+        Ok(Self::from_sexpr(&root))
+    }
+
+    /// Read the components and nets of an `(export ...)` expression. Entries without a
+    /// reference or name are skipped.
+    pub fn from_sexpr(root: &SExpr) -> Self {
+        let components: Vec<KicadComponent> = root
+            .children("components")
+            .flat_map(|c| c.children("comp"))
+            .filter_map(|comp| {
+                Some(KicadComponent {
+                    reference: comp.field_value("ref")?.to_string(),
+                    value: comp.field_value("value").map(str::to_string),
+                    footprint: comp.field_value("footprint").map(str::to_string),
+                    sheetpath: comp
+                        .child("sheetpath")
+                        .and_then(|s| s.field_value("names"))
+                        .map(str::to_string),
+                })
+            })
+            .collect();
+
+        let nets = root
+            .children("nets")
+            .flat_map(|n| n.children("net"))
+            .filter_map(|net| {
+                Some(KicadNet {
+                    code: net.field_value("code").map(str::to_string),
+                    name: net.field_value("name")?.to_string(),
+                    nodes: net
+                        .children("node")
+                        .filter_map(|node| {
+                            Some(KicadNode {
+                                reference: node.field_value("ref")?.to_string(),
+                                pin: node.field_value("pin")?.to_string(),
+                            })
+                        })
+                        .collect(),
+                })
+            })
+            .collect();
+
+        let mut by_sheetpath = HashMap::new();
+        for (i, component) in components.iter().enumerate() {
+            if let Some(sheetpath) = &component.sheetpath {
+                by_sheetpath.entry(sheetpath.clone()).or_insert(i);
+            }
+        }
+
+        Self {
+            components,
+            nets,
+            by_sheetpath,
+        }
+    }
+
+    /// Find the component at the given atopile address, among the components as read.
+    pub fn component_at(&self, sheetpath: &str) -> Option<&KicadComponent> {
+        self.components.get(*self.by_sheetpath.get(sheetpath)?)
+    }
+
+    /// All nets that have a node on the given component.
+    pub fn nets_of<'a>(&'a self, reference: &'a str) -> impl Iterator<Item = &'a KicadNet> + 'a {
+        self.nets
+            .iter()
+            .filter(move |n| n.nodes.iter().any(|node| node.reference == reference))
+    }
+}
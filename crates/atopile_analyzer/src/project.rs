@@ -0,0 +1,350 @@
+//! The `ato.yaml` configuration of a project.
+//!
+//! The directory containing `ato.yaml` is the project root; every path in the configuration is
+//! relative to it.
+
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Display},
+    path::{Path, PathBuf},
+};
+
+use atopile_parser::Position;
+use semver::{Version, VersionReq};
+use serde::Deserialize;
+
+use crate::{vfs::normalize, Location, Range};
+
+pub const CONFIG_FILE_NAME: &str = "ato.yaml";
+
+/// The name of the build used for projects that don't declare any.
+pub const DEFAULT_BUILD_NAME: &str = "default";
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ProjectConfig {
+    /// The versions of atopile the project can be built with, e.g. `^0.3.0`.
+    #[serde(default)]
+    pub requires_atopile: Option<String>,
+    /// Set when the project is itself a package that others can depend on.
+    #[serde(default)]
+    pub package: Option<PackageInfo>,
+    #[serde(default)]
+    pub paths: ProjectPaths,
+    #[serde(default)]
+    pub builds: BTreeMap<String, BuildConfig>,
+    #[serde(default)]
+    pub dependencies: Vec<Dependency>,
+}
+
+impl ProjectConfig {
+    pub fn parse(text: &str) -> Result<Self, ConfigError> {
+        // An empty file only marks the project root.
+        if text.trim().is_empty() {
+            return Ok(Self::default());
+        }
+
+        serde_yaml::from_str(text).map_err(|e| ConfigError {
+            message: e.to_string(),
+            position: e.location().map(|location| Position {
+                line: location.line().saturating_sub(1),
+                column: location.column().saturating_sub(1),
+            }),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct PackageInfo {
+    #[serde(alias = "identifier")]
+    pub name: Option<String>,
+    pub version: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct ProjectPaths {
+    /// Where the project's own source files live.
+    pub src: PathBuf,
+    /// Where build outputs, such as netlists, are written.
+    pub build: PathBuf,
+    pub layout: Option<PathBuf>,
+}
+
+impl Default for ProjectPaths {
+    fn default() -> Self {
+        Self {
+            src: PathBuf::from("."),
+            build: PathBuf::from("build"),
+            layout: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct BuildConfig {
+    pub entry: EntryPoint,
+    #[serde(default)]
+    pub targets: Vec<String>,
+}
+
+/// The root module of a build, written `path/to/file.ato:Module`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct EntryPoint {
+    pub file: PathBuf,
+    pub module: String,
+}
+
+impl TryFrom<String> for EntryPoint {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        match value.rsplit_once(':') {
+            Some((file, module)) if !file.is_empty() && !module.is_empty() => Ok(Self {
+                file: PathBuf::from(file),
+                module: module.to_string(),
+            }),
+            _ => Err(format!(
+                "invalid entry point `{}`, expected `path/to/file.ato:Module`",
+                value
+            )),
+        }
+    }
+}
+
+impl Display for EntryPoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.file.display(), self.module)
+    }
+}
+
+/// A package the project depends on, written either as `name` followed by an optional version
+/// spec (e.g. `generics ^1.0.0`), or as a mapping with `name`, `version-spec` and `path`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(from = "DependencySpec")]
+pub struct Dependency {
+    pub name: String,
+    pub version_spec: Option<String>,
+    /// Where the package is installed, if not in `.ato/modules/<name>`.
+    pub path: Option<PathBuf>,
+}
+
+impl Dependency {
+    /// Whether `version` of the package satisfies the version spec. Specs and versions that
+    /// aren't valid semver are accepted, since there's no telling whether they match.
+    pub fn accepts(&self, version: &str) -> bool {
+        let Some(spec) = &self.version_spec else {
+            return true;
+        };
+        match (
+            VersionReq::parse(&strip_v_prefix(spec)),
+            Version::parse(&strip_v_prefix(version)),
+        ) {
+            (Ok(spec), Ok(version)) => spec.matches(&version),
+            _ => true,
+        }
+    }
+}
+
+/// Drop the `v` of versions written like `v1.2.0`, which semver doesn't allow.
+fn strip_v_prefix(spec: &str) -> String {
+    let mut stripped = String::with_capacity(spec.len());
+    let mut prev = None;
+    let mut chars = spec.trim().chars().peekable();
+    while let Some(c) = chars.next() {
+        let is_prefix = c == 'v'
+            && !prev.is_some_and(char::is_alphanumeric)
+            && chars.peek().is_some_and(char::is_ascii_digit);
+        if !is_prefix {
+            stripped.push(c);
+        }
+        prev = Some(c);
+    }
+    stripped
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum DependencySpec {
+    Short(String),
+    #[serde(rename_all = "kebab-case")]
+    Full {
+        name: String,
+        #[serde(default, alias = "version_spec")]
+        version_spec: Option<String>,
+        #[serde(default)]
+        path: Option<PathBuf>,
+    },
+}
+
+impl From<DependencySpec> for Dependency {
+    fn from(spec: DependencySpec) -> Self {
+        match spec {
+            DependencySpec::Short(spec) => {
+                let spec = spec.trim();
+                let (name, version_spec) = spec
+                    .find(|c: char| c.is_whitespace() || "@^~<>=!".contains(c))
+                    .map_or((spec, ""), |i| spec.split_at(i));
+                let version_spec = version_spec.trim().trim_start_matches('@').trim();
+                Self {
+                    name: name.to_string(),
+                    version_spec: (!version_spec.is_empty()).then(|| version_spec.to_string()),
+                    path: None,
+                }
+            }
+            DependencySpec::Full {
+                name,
+                version_spec,
+                path,
+            } => Self {
+                name,
+                version_spec,
+                path,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigError {
+    pub message: String,
+    pub position: Option<Position>,
+}
+
+/// A project: the directory containing an `ato.yaml`, along with its parsed configuration.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Project {
+    root: PathBuf,
+    text: String,
+    /// The configuration, or the default one if `ato.yaml` is invalid.
+    config: ProjectConfig,
+    error: Option<ConfigError>,
+}
+
+impl Project {
+    pub fn new(root: &Path, text: String) -> Self {
+        let (config, error) = match ProjectConfig::parse(&text) {
+            Ok(config) => (config, None),
+            Err(error) => (ProjectConfig::default(), Some(error)),
+        };
+        Self {
+            root: root.to_path_buf(),
+            text,
+            config,
+            error,
+        }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn config_path(&self) -> PathBuf {
+        self.root.join(CONFIG_FILE_NAME)
+    }
+
+    pub fn config(&self) -> &ProjectConfig {
+        &self.config
+    }
+
+    pub fn error(&self) -> Option<&ConfigError> {
+        self.error.as_ref()
+    }
+
+    pub fn src_dir(&self) -> PathBuf {
+        normalize(&self.root.join(&self.config.paths.src))
+    }
+
+    /// The file declaring the root module of `build`.
+    pub fn entry_path(&self, build: &BuildConfig) -> PathBuf {
+        normalize(&self.root.join(&build.entry.file))
+    }
+
+    /// The directory build outputs are written to.
+    pub fn build_dir(&self) -> PathBuf {
+        normalize(&self.root.join(&self.config.paths.build))
+    }
+
+    /// Where the netlist of the build named `build_name` is written.
+    pub fn netlist_path(&self, build_name: &str) -> PathBuf {
+        self.build_dir().join(format!("{}.net", build_name))
+    }
+
+    /// The directory packages are installed into. Installed packages are themselves projects,
+    /// and share the `.ato/modules` of the project that installed them.
+    pub fn modules_dir(&self) -> PathBuf {
+        self.root
+            .ancestors()
+            .find(|dir| dir.file_name().is_some_and(|name| name == ".ato"))
+            .and_then(Path::parent)
+            .unwrap_or(&self.root)
+            .join(".ato")
+            .join("modules")
+    }
+
+    /// Where the package `dependency` is installed.
+    pub fn package_root(&self, dependency: &Dependency) -> PathBuf {
+        match &dependency.path {
+            Some(path) => normalize(&self.root.join(path)),
+            None => self.modules_dir().join(&dependency.name),
+        }
+    }
+
+    /// The dependency an import path is qualified with, e.g. `generics` for
+    /// `generics/resistors.ato`, and the rest of the path within the package.
+    pub fn dependency_of<'a>(&self, import_path: &'a Path) -> Option<(&Dependency, &'a Path)> {
+        self.config
+            .dependencies
+            .iter()
+            .filter_map(|dependency| {
+                let rest = import_path.strip_prefix(&dependency.name).ok()?;
+                Some((dependency, rest))
+            })
+            .max_by_key(|(dependency, _)| dependency.name.len())
+    }
+
+    /// The location in `ato.yaml` of the first occurrence of `needle`, or of the start of the
+    /// file if it doesn't occur.
+    pub(crate) fn locate(&self, needle: &str) -> Location {
+        let (start, end) = self
+            .text
+            .find(needle)
+            .map_or((0, 0), |start| (start, start + needle.len()));
+        Location {
+            file: self.config_path(),
+            range: Range {
+                start: self.position_at(start),
+                end: self.position_at(end),
+            },
+        }
+    }
+
+    /// The location in `ato.yaml` of a configuration error, up to the end of its line.
+    pub(crate) fn error_location(&self, error: &ConfigError) -> Location {
+        let start = error.position.unwrap_or(Position { line: 0, column: 0 });
+        let line_len = self
+            .text
+            .lines()
+            .nth(start.line)
+            .map_or(start.column, str::len);
+        Location {
+            file: self.config_path(),
+            range: Range {
+                start,
+                end: Position {
+                    line: start.line,
+                    column: line_len.max(start.column),
+                },
+            },
+        }
+    }
+
+    fn position_at(&self, offset: usize) -> Position {
+        let before = &self.text[..offset];
+        let line = before.matches('\n').count();
+        let column = offset - before.rfind('\n').map_or(0, |i| i + 1);
+        Position { line, column }
+    }
+}
@@ -0,0 +1,149 @@
+//! The names used in a source file and what each refers to, for finding references and
+//! renaming.
+
+use std::{ops::Deref, path::Path};
+
+use atopile_parser::{
+    parser::{Connectable, Expr, PortRef, Stmt, Symbol},
+    AtopileSource, Span, Spanned,
+};
+
+use crate::{db::Database, evaluator::ModuleRef, vfs::Vfs, IntoLocation, Location};
+
+/// A name in a source file that refers to a block, instance, signal, pin or attribute.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Occurrence {
+    /// The name itself.
+    pub(crate) location: Location,
+    /// Where the thing it refers to is declared.
+    pub(crate) definition: Location,
+    /// Whether this is the name in the declaration itself.
+    pub(crate) is_declaration: bool,
+}
+
+impl<V: Vfs> Database<V> {
+    /// Every name in the file at `path` that refers to something declared, in source order.
+    /// Names that can't be resolved are left out.
+    pub(crate) fn occurrences(&self, path: &Path) -> Vec<Occurrence> {
+        let Ok(source) = self.source(path) else {
+            return vec![];
+        };
+
+        let mut occurrences = Occurrences {
+            db: self,
+            source: &source,
+            occurrences: vec![],
+        };
+        for stmt in source.ast() {
+            match stmt.deref() {
+                Stmt::Import(import) => {
+                    for symbol in &import.imports {
+                        occurrences.symbol(symbol, false);
+                    }
+                }
+                Stmt::DepImport(import) => occurrences.symbol(&import.name, false),
+                Stmt::Block(block) => {
+                    occurrences.symbol(&block.name, true);
+                    if let Some(parent) = &block.parent {
+                        occurrences.symbol(parent, false);
+                    }
+                    let block_ref = ModuleRef::new(path, &block.name);
+                    for stmt in &block.body {
+                        occurrences.block_stmt(&block_ref, stmt);
+                    }
+                }
+                _ => {}
+            }
+        }
+        occurrences.occurrences
+    }
+}
+
+struct Occurrences<'a, V: Vfs> {
+    db: &'a Database<V>,
+    source: &'a AtopileSource,
+    occurrences: Vec<Occurrence>,
+}
+
+impl<V: Vfs> Occurrences<'_, V> {
+    fn push(&mut self, location: Location, definition: Option<Location>, is_declaration: bool) {
+        if let Some(definition) = definition {
+            self.occurrences.push(Occurrence {
+                location,
+                definition,
+                is_declaration,
+            });
+        }
+    }
+
+    /// The name of a block, in its declaration or wherever it is used as a type.
+    fn symbol(&mut self, symbol: &Spanned<Symbol>, is_declaration: bool) {
+        let definition = self.db.definition(self.source.path(), symbol);
+        self.push(
+            symbol.span().to_location(self.source),
+            definition,
+            is_declaration,
+        );
+    }
+
+    /// The name of a member of `block` in the statement declaring it.
+    fn member(&mut self, block: &ModuleRef, name: &str, span: &Span) {
+        let location = span.to_location(self.source);
+        let definition = self.db.port_definition(block, &[name]);
+        let is_declaration = definition.as_ref() == Some(&location);
+        self.push(location, definition, is_declaration);
+    }
+
+    /// Each part of a port reference, e.g. both `r1` and `p1` in `r1.p1`.
+    fn port(&mut self, block: &ModuleRef, port: &PortRef) {
+        let parts: Vec<_> = port.parts.iter().map(|part| part.as_str()).collect();
+        for (i, part) in port.parts.iter().enumerate() {
+            let location = part.span().to_location(self.source);
+            let definition = self.db.port_definition(block, &parts[..=i]);
+            let is_declaration = definition.as_ref() == Some(&location);
+            self.push(location, definition, is_declaration);
+        }
+    }
+
+    fn expr(&mut self, block: &ModuleRef, expr: &Expr) {
+        match expr {
+            Expr::New(symbol) => self.symbol(symbol, false),
+            Expr::Port(port) => self.port(block, port),
+            Expr::BinaryOp(binary_op) => {
+                self.expr(block, &binary_op.left);
+                self.expr(block, &binary_op.right);
+            }
+            _ => {}
+        }
+    }
+
+    fn block_stmt(&mut self, block: &ModuleRef, stmt: &Spanned<Stmt>) {
+        match stmt.deref() {
+            Stmt::Assign(assign) => {
+                self.port(block, &assign.target);
+                self.expr(block, &assign.value);
+            }
+            Stmt::Specialize(specialize) => {
+                self.port(block, &specialize.port);
+                self.symbol(&specialize.value, false);
+            }
+            Stmt::Connect(connect) => {
+                for connectable in [&connect.left, &connect.right] {
+                    match connectable.deref() {
+                        Connectable::Port(port) => self.port(block, port),
+                        Connectable::Signal(name) | Connectable::Pin(name) => {
+                            self.member(block, name, name.span())
+                        }
+                    }
+                }
+            }
+            Stmt::Signal(signal) => self.member(block, &signal.name, signal.name.span()),
+            Stmt::Pin(pin) => self.member(block, &pin.name, pin.name.span()),
+            Stmt::Attribute(attribute) => {
+                self.member(block, &attribute.name, attribute.name.span())
+            }
+            Stmt::Assert(assert) => self.expr(block, &assert.expr),
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,276 @@
+//! A minimal s-expression reader and writer, used for KiCad netlists.
+
+use std::{
+    fmt::{self, Display},
+    iter::Peekable,
+    str::CharIndices,
+};
+
+use atopile_parser::Position;
+use thiserror::Error;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SExpr {
+    /// A bare symbol, e.g. `comp` or `ref`.
+    Atom(String),
+    /// A quoted string, e.g. `"R1"`.
+    String(String),
+    List(Vec<SExpr>),
+}
+
+impl SExpr {
+    pub fn atom(s: impl Into<String>) -> Self {
+        SExpr::Atom(s.into())
+    }
+
+    pub fn string(s: impl Into<String>) -> Self {
+        SExpr::String(s.into())
+    }
+
+    /// Create a list whose first element is the atom `name`, e.g. `(name ...)`.
+    pub fn node(name: &str, children: impl IntoIterator<Item = SExpr>) -> Self {
+        SExpr::List(std::iter::once(SExpr::atom(name)).chain(children).collect())
+    }
+
+    /// Shorthand for a node with a single string value, e.g. `(ref "R1")`.
+    pub fn field(name: &str, value: impl Into<String>) -> Self {
+        Self::node(name, [SExpr::string(value)])
+    }
+
+    /// The head atom of a list, e.g. `comp` for `(comp (ref "R1"))`.
+    pub fn name(&self) -> Option<&str> {
+        match self {
+            SExpr::List(items) => match items.first() {
+                Some(SExpr::Atom(name)) => Some(name),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// The text of an atom or string.
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            SExpr::Atom(s) | SExpr::String(s) => Some(s),
+            SExpr::List(_) => None,
+        }
+    }
+
+    /// All child lists with the given head atom.
+    pub fn children<'a, 'n>(&'a self, name: &'n str) -> impl Iterator<Item = &'a SExpr> + 'n
+    where
+        'a: 'n,
+    {
+        let items = match self {
+            SExpr::List(items) => &items[..],
+            _ => &[],
+        };
+        items.iter().filter(move |i| i.name() == Some(name))
+    }
+
+    /// The first child list with the given head atom.
+    pub fn child(&self, name: &str) -> Option<&SExpr> {
+        self.children(name).next()
+    }
+
+    /// The value of a field like `(ref "R1")`, i.e. the text of its second element.
+    pub fn value(&self) -> Option<&str> {
+        match self {
+            SExpr::List(items) => items.get(1).and_then(|i| i.as_str()),
+            _ => None,
+        }
+    }
+
+    /// The value of the first child field with the given name.
+    pub fn field_value(&self, name: &str) -> Option<&str> {
+        self.child(name).and_then(|c| c.value())
+    }
+
+    fn depth(&self) -> usize {
+        match self {
+            SExpr::List(items) => 1 + items.iter().map(|i| i.depth()).max().unwrap_or(0),
+            _ => 0,
+        }
+    }
+
+    fn write_pretty(&self, f: &mut fmt::Formatter<'_>, indent: usize) -> fmt::Result {
+        let items = match self {
+            SExpr::List(items) if self.depth() > 2 => items,
+            _ => return write!(f, "{}", Inline(self)),
+        };
+
+        // Leading atoms stay on the header line; nested lists each get their own line.
+        write!(f, "(")?;
+        let header_len = items
+            .iter()
+            .take_while(|i| !matches!(i, SExpr::List(_)))
+            .count();
+        for (i, item) in items[..header_len].iter().enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{}", Inline(item))?;
+        }
+        for item in &items[header_len..] {
+            write!(f, "\n{:width$}", "", width = indent + 2)?;
+            item.write_pretty(f, indent + 2)?;
+        }
+        write!(f, ")")
+    }
+}
+
+/// Formats an expression on a single line.
+struct Inline<'a>(&'a SExpr);
+
+impl Display for Inline<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            SExpr::Atom(s) => write!(f, "{}", s),
+            SExpr::String(s) => {
+                write!(f, "\"")?;
+                for c in s.chars() {
+                    match c {
+                        '"' => write!(f, "\\\"")?,
+                        '\\' => write!(f, "\\\\")?,
+                        '\n' => write!(f, "\\n")?,
+                        c => write!(f, "{}", c)?,
+                    }
+                }
+                write!(f, "\"")
+            }
+            SExpr::List(items) => {
+                write!(f, "(")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, " ")?;
+                    }
+                    write!(f, "{}", Inline(item))?;
+                }
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+impl Display for SExpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write_pretty(f, 0)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Error)]
+#[error("{message} at {position}")]
+pub struct SExprError {
+    pub message: String,
+    pub position: Position,
+}
+
+/// How deeply lists may be nested. KiCad netlists need a handful of levels; anything deeper
+/// is corrupt, and would otherwise overflow the stack while reading.
+const MAX_DEPTH: usize = 64;
+
+/// Parse a single s-expression from `input`. Trailing whitespace is allowed, anything else
+/// after the expression is an error.
+pub fn parse(input: &str) -> Result<SExpr, SExprError> {
+    let mut reader = Reader {
+        input,
+        chars: input.char_indices().peekable(),
+        depth: 0,
+    };
+
+    let expr = reader.read()?;
+    reader.skip_whitespace();
+    if let Some(&(offset, c)) = reader.chars.peek() {
+        return Err(reader.error(offset, format!("unexpected `{}` after expression", c)));
+    }
+
+    Ok(expr)
+}
+
+struct Reader<'a> {
+    input: &'a str,
+    chars: Peekable<CharIndices<'a>>,
+    /// The number of lists currently open.
+    depth: usize,
+}
+
+impl Reader<'_> {
+    fn error(&self, offset: usize, message: String) -> SExprError {
+        let before = &self.input[..offset];
+        let line = before.matches('\n').count();
+        let column = before.len() - before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+        SExprError {
+            message,
+            position: Position { line, column },
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.chars.next_if(|(_, c)| c.is_whitespace()).is_some() {}
+    }
+
+    fn read(&mut self) -> Result<SExpr, SExprError> {
+        self.skip_whitespace();
+        match self.chars.next() {
+            None => Err(self.error(self.input.len(), "unexpected end of input".to_string())),
+            Some((offset, '(')) => {
+                if self.depth == MAX_DEPTH {
+                    return Err(self.error(offset, "lists are nested too deeply".to_string()));
+                }
+                self.depth += 1;
+                let list = self.read_list();
+                self.depth -= 1;
+                list
+            }
+            Some((offset, ')')) => Err(self.error(offset, "unexpected `)`".to_string())),
+            Some((offset, '"')) => self.read_string(offset),
+            Some((offset, _)) => Ok(self.read_atom(offset)),
+        }
+    }
+
+    fn read_list(&mut self) -> Result<SExpr, SExprError> {
+        let mut items = Vec::new();
+        loop {
+            self.skip_whitespace();
+            match self.chars.peek() {
+                Some((_, ')')) => {
+                    self.chars.next();
+                    return Ok(SExpr::List(items));
+                }
+                None => {
+                    return Err(self.error(self.input.len(), "unclosed `(`".to_string()));
+                }
+                Some(_) => items.push(self.read()?),
+            }
+        }
+    }
+
+    fn read_string(&mut self, start: usize) -> Result<SExpr, SExprError> {
+        let mut value = String::new();
+        loop {
+            match self.chars.next() {
+                Some((_, '"')) => return Ok(SExpr::String(value)),
+                Some((_, '\\')) => match self.chars.next() {
+                    Some((_, 'n')) => value.push('\n'),
+                    Some((_, c)) => value.push(c),
+                    None => break,
+                },
+                Some((_, c)) => value.push(c),
+                None => break,
+            }
+        }
+        Err(self.error(start, "unterminated string".to_string()))
+    }
+
+    fn read_atom(&mut self, start: usize) -> SExpr {
+        let mut end = self.input.len();
+        while let Some(&(offset, c)) = self.chars.peek() {
+            if c.is_whitespace() || matches!(c, '(' | ')' | '"') {
+                end = offset;
+                break;
+            }
+            self.chars.next();
+        }
+        SExpr::Atom(self.input[start..end].to_string())
+    }
+}
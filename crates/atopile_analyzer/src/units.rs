@@ -0,0 +1,520 @@
+//! Physical quantities with units, SI prefixes and tolerances.
+
+use std::{
+    fmt::{self, Display},
+    ops::Deref,
+};
+
+use atopile_parser::parser::{PhysicalValue, Tolerance};
+use serde::{Serialize, Serializer};
+use thiserror::Error;
+
+/// The exponents of the SI base dimensions that a quantity is made of, in the order mass,
+/// length, time, current, temperature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Dimension([i8; 5]);
+
+impl Dimension {
+    pub const DIMENSIONLESS: Self = Self([0, 0, 0, 0, 0]);
+    pub const MASS: Self = Self([1, 0, 0, 0, 0]);
+    pub const LENGTH: Self = Self([0, 1, 0, 0, 0]);
+    pub const TIME: Self = Self([0, 0, 1, 0, 0]);
+    pub const CURRENT: Self = Self([0, 0, 0, 1, 0]);
+    pub const TEMPERATURE: Self = Self([0, 0, 0, 0, 1]);
+    pub const FREQUENCY: Self = Self([0, 0, -1, 0, 0]);
+    pub const CHARGE: Self = Self([0, 0, 1, 1, 0]);
+    pub const ENERGY: Self = Self([1, 2, -2, 0, 0]);
+    pub const POWER: Self = Self([1, 2, -3, 0, 0]);
+    pub const VOLTAGE: Self = Self([1, 2, -3, -1, 0]);
+    pub const RESISTANCE: Self = Self([1, 2, -3, -2, 0]);
+    pub const CONDUCTANCE: Self = Self([-1, -2, 3, 2, 0]);
+    pub const CAPACITANCE: Self = Self([-1, -2, 4, 2, 0]);
+    pub const INDUCTANCE: Self = Self([1, 2, -2, -2, 0]);
+
+    pub fn is_dimensionless(&self) -> bool {
+        *self == Self::DIMENSIONLESS
+    }
+
+    /// Raise this dimension to an integer power. Returns `None` if an exponent overflows.
+    pub fn checked_powi(self, exponent: i8) -> Option<Self> {
+        let mut exponents = self.0;
+        for e in exponents.iter_mut() {
+            *e = e.checked_mul(exponent)?;
+        }
+        Some(Self(exponents))
+    }
+
+    /// The dimension of the product of quantities of these dimensions, which adds their
+    /// exponents. Returns `None` if an exponent overflows.
+    pub fn checked_mul(self, rhs: Self) -> Option<Self> {
+        let mut exponents = self.0;
+        for (e, r) in exponents.iter_mut().zip(rhs.0) {
+            *e = e.checked_add(r)?;
+        }
+        Some(Self(exponents))
+    }
+
+    /// The dimension of the quotient of quantities of these dimensions, which subtracts their
+    /// exponents. Returns `None` if an exponent overflows.
+    pub fn checked_div(self, rhs: Self) -> Option<Self> {
+        let mut exponents = self.0;
+        for (e, r) in exponents.iter_mut().zip(rhs.0) {
+            *e = e.checked_sub(r)?;
+        }
+        Some(Self(exponents))
+    }
+
+    /// Look up a dimension by the name used in attribute declarations, e.g. `voltage`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        NAMED_DIMENSIONS
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, d)| *d)
+    }
+
+    /// The well-known name of this dimension, if it has one.
+    pub fn name(&self) -> Option<&'static str> {
+        NAMED_DIMENSIONS
+            .iter()
+            .find(|(_, d)| d == self)
+            .map(|(n, _)| *n)
+    }
+}
+
+impl Display for Dimension {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(name) = self.name() {
+            return write!(f, "{}", name);
+        }
+
+        let parts: Vec<String> = ["kg", "m", "s", "A", "K"]
+            .iter()
+            .zip(self.0)
+            .filter(|(_, e)| *e != 0)
+            .map(|(symbol, e)| match e {
+                1 => symbol.to_string(),
+                e => format!("{}^{}", symbol, e),
+            })
+            .collect();
+        write!(f, "{}", parts.join("·"))
+    }
+}
+
+impl Serialize for Dimension {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+const NAMED_DIMENSIONS: &[(&str, Dimension)] = &[
+    ("dimensionless", Dimension::DIMENSIONLESS),
+    ("mass", Dimension::MASS),
+    ("length", Dimension::LENGTH),
+    ("time", Dimension::TIME),
+    ("current", Dimension::CURRENT),
+    ("temperature", Dimension::TEMPERATURE),
+    ("frequency", Dimension::FREQUENCY),
+    ("charge", Dimension::CHARGE),
+    ("energy", Dimension::ENERGY),
+    ("power", Dimension::POWER),
+    ("voltage", Dimension::VOLTAGE),
+    ("resistance", Dimension::RESISTANCE),
+    ("conductance", Dimension::CONDUCTANCE),
+    ("capacitance", Dimension::CAPACITANCE),
+    ("inductance", Dimension::INDUCTANCE),
+];
+
+/// Units that may follow a number, with their scale relative to SI base units. The first unit
+/// listed for a dimension is the one used when formatting.
+const UNITS: &[(&str, f64, Dimension)] = &[
+    ("V", 1.0, Dimension::VOLTAGE),
+    ("A", 1.0, Dimension::CURRENT),
+    ("ohm", 1.0, Dimension::RESISTANCE),
+    ("Ω", 1.0, Dimension::RESISTANCE),
+    ("F", 1.0, Dimension::CAPACITANCE),
+    ("H", 1.0, Dimension::INDUCTANCE),
+    ("Hz", 1.0, Dimension::FREQUENCY),
+    ("W", 1.0, Dimension::POWER),
+    ("J", 1.0, Dimension::ENERGY),
+    ("C", 1.0, Dimension::CHARGE),
+    ("Ah", 3600.0, Dimension::CHARGE),
+    ("S", 1.0, Dimension::CONDUCTANCE),
+    ("s", 1.0, Dimension::TIME),
+    ("m", 1.0, Dimension::LENGTH),
+    ("g", 1e-3, Dimension::MASS),
+    ("K", 1.0, Dimension::TEMPERATURE),
+    ("ppm", 1e-6, Dimension::DIMENSIONLESS),
+];
+
+/// SI prefixes, longest first so that `da` is tried before `d`.
+const PREFIXES: &[(&str, f64)] = &[
+    ("da", 1e1),
+    ("Y", 1e24),
+    ("Z", 1e21),
+    ("E", 1e18),
+    ("P", 1e15),
+    ("T", 1e12),
+    ("G", 1e9),
+    ("M", 1e6),
+    ("k", 1e3),
+    ("h", 1e2),
+    ("d", 1e-1),
+    ("c", 1e-2),
+    ("m", 1e-3),
+    ("u", 1e-6),
+    ("µ", 1e-6),
+    ("n", 1e-9),
+    ("p", 1e-12),
+    ("f", 1e-15),
+    ("a", 1e-18),
+    ("z", 1e-21),
+    ("y", 1e-24),
+];
+
+/// Prefixes used when formatting a value, from largest to smallest.
+const DISPLAY_PREFIXES: &[(&str, f64)] = &[
+    ("T", 1e12),
+    ("G", 1e9),
+    ("M", 1e6),
+    ("k", 1e3),
+    ("", 1.0),
+    ("m", 1e-3),
+    ("u", 1e-6),
+    ("n", 1e-9),
+    ("p", 1e-12),
+    ("f", 1e-15),
+];
+
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum UnitError {
+    #[error("unknown unit `{0}`")]
+    UnknownUnit(String),
+    #[error("invalid number `{0}`")]
+    InvalidNumber(String),
+    #[error("tolerance `{0}` is not a {1}")]
+    ToleranceMismatch(String, Dimension),
+    #[error("cannot combine {0} and {1}")]
+    IncompatibleDimensions(Dimension, Dimension),
+    #[error("division by zero")]
+    DivisionByZero,
+    #[error("exponent must be a dimensionless integer without tolerance")]
+    InvalidExponent,
+    #[error("result is out of range")]
+    OutOfRange,
+}
+
+/// Parse a unit with an optional SI prefix, e.g. `kohm`, into its scale and dimension.
+pub fn parse_unit(unit: &str) -> Result<(f64, Dimension), UnitError> {
+    if let Some((_, scale, dimension)) = UNITS.iter().find(|(u, _, _)| *u == unit) {
+        return Ok((*scale, *dimension));
+    }
+
+    PREFIXES
+        .iter()
+        .filter_map(|(prefix, factor)| {
+            let rest = unit.strip_prefix(prefix)?;
+            let (_, scale, dimension) = UNITS.iter().find(|(u, _, _)| *u == rest)?;
+            Some((factor * scale, *dimension))
+        })
+        .next()
+        .ok_or_else(|| UnitError::UnknownUnit(unit.to_string()))
+}
+
+/// The units a number may be followed by, each with the prefixes used when formatting, e.g.
+/// `kohm`, along with their dimension.
+pub fn units() -> Vec<(String, Dimension)> {
+    UNITS
+        .iter()
+        .flat_map(|(unit, _, dimension)| {
+            let prefixes = if dimension.is_dimensionless() {
+                &[("", 1.0)][..]
+            } else {
+                DISPLAY_PREFIXES
+            };
+            prefixes
+                .iter()
+                .map(move |(prefix, _)| (format!("{}{}", prefix, unit), *dimension))
+        })
+        .collect()
+}
+
+fn parse_number(number: &str) -> Result<f64, UnitError> {
+    number
+        .parse()
+        .map_err(|_| UnitError::InvalidNumber(number.to_string()))
+}
+
+/// Render a physical value the way it was written, e.g. `10kohm ± 5%` or `1V to 2V`.
+pub fn physical_text(physical: &PhysicalValue) -> String {
+    let unit = physical
+        .unit
+        .as_ref()
+        .map(|u| u.as_str())
+        .unwrap_or_default();
+    let mut text = format!("{}{}", physical.value.deref(), unit);
+    match physical.tolerance.as_deref() {
+        Some(Tolerance::Bilateral { value, unit }) => {
+            let tol_unit = unit.as_ref().map(|u| u.as_str()).unwrap_or("%");
+            text.push_str(&format!(" ± {}{}", value.deref(), tol_unit));
+        }
+        Some(Tolerance::Bound { max, .. }) => {
+            text.push_str(&format!(" to {}{}", max.deref(), unit));
+        }
+        None => {}
+    }
+    text
+}
+
+/// A value in SI base units with a dimension and optional tolerance bounds.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Quantity {
+    pub value: f64,
+    pub dimension: Dimension,
+    /// The lowest and highest value allowed by the tolerance, in SI base units.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max: Option<f64>,
+}
+
+impl Quantity {
+    pub fn new(value: f64, dimension: Dimension) -> Self {
+        Self {
+            value,
+            dimension,
+            min: None,
+            max: None,
+        }
+    }
+
+    pub fn dimensionless(value: f64) -> Self {
+        Self::new(value, Dimension::DIMENSIONLESS)
+    }
+
+    pub fn with_bounds(mut self, min: f64, max: f64) -> Self {
+        self.min = Some(min.min(max));
+        self.max = Some(min.max(max));
+        self
+    }
+
+    pub fn has_tolerance(&self) -> bool {
+        self.min.is_some() || self.max.is_some()
+    }
+
+    /// The range of values this quantity covers; just the value itself without a tolerance.
+    pub fn bounds(&self) -> (f64, f64) {
+        (
+            self.min.unwrap_or(self.value),
+            self.max.unwrap_or(self.value),
+        )
+    }
+
+    /// Combine two quantities, propagating tolerances with interval arithmetic. `op` is
+    /// applied to the values and to every combination of bounds.
+    fn combine(&self, rhs: &Self, dimension: Dimension, op: impl Fn(f64, f64) -> f64) -> Self {
+        let quantity = Self::new(op(self.value, rhs.value), dimension);
+        if !self.has_tolerance() && !rhs.has_tolerance() {
+            return quantity;
+        }
+
+        let (a_min, a_max) = self.bounds();
+        let (b_min, b_max) = rhs.bounds();
+        let candidates = [
+            op(a_min, b_min),
+            op(a_min, b_max),
+            op(a_max, b_min),
+            op(a_max, b_max),
+        ];
+        quantity.with_bounds(
+            candidates.iter().copied().fold(f64::INFINITY, f64::min),
+            candidates.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+        )
+    }
+
+    /// Fail with [`UnitError::OutOfRange`] if the value or either bound isn't finite.
+    fn finite(self) -> Result<Self, UnitError> {
+        let (min, max) = self.bounds();
+        if self.value.is_finite() && min.is_finite() && max.is_finite() {
+            Ok(self)
+        } else {
+            Err(UnitError::OutOfRange)
+        }
+    }
+
+    pub fn checked_add(&self, rhs: &Self) -> Result<Self, UnitError> {
+        if self.dimension != rhs.dimension {
+            return Err(UnitError::IncompatibleDimensions(
+                self.dimension,
+                rhs.dimension,
+            ));
+        }
+        self.combine(rhs, self.dimension, |a, b| a + b).finite()
+    }
+
+    pub fn checked_sub(&self, rhs: &Self) -> Result<Self, UnitError> {
+        if self.dimension != rhs.dimension {
+            return Err(UnitError::IncompatibleDimensions(
+                self.dimension,
+                rhs.dimension,
+            ));
+        }
+        self.combine(rhs, self.dimension, |a, b| a - b).finite()
+    }
+
+    pub fn checked_mul(&self, rhs: &Self) -> Result<Self, UnitError> {
+        let dimension = self
+            .dimension
+            .checked_mul(rhs.dimension)
+            .ok_or(UnitError::OutOfRange)?;
+        self.combine(rhs, dimension, |a, b| a * b).finite()
+    }
+
+    pub fn checked_div(&self, rhs: &Self) -> Result<Self, UnitError> {
+        let (min, max) = rhs.bounds();
+        if min <= 0.0 && max >= 0.0 {
+            return Err(UnitError::DivisionByZero);
+        }
+        let dimension = self
+            .dimension
+            .checked_div(rhs.dimension)
+            .ok_or(UnitError::OutOfRange)?;
+        self.combine(rhs, dimension, |a, b| a / b).finite()
+    }
+
+    /// Raise this quantity to a dimensionless power. Quantities with a dimension can only be
+    /// raised to integer powers.
+    pub fn checked_pow(&self, rhs: &Self) -> Result<Self, UnitError> {
+        if !rhs.dimension.is_dimensionless() || rhs.has_tolerance() {
+            return Err(UnitError::InvalidExponent);
+        }
+
+        let dimension = if self.dimension.is_dimensionless() {
+            self.dimension
+        } else if rhs.value.fract() == 0.0 && rhs.value.abs() <= i8::MAX as f64 {
+            self.dimension
+                .checked_powi(rhs.value as i8)
+                .ok_or(UnitError::OutOfRange)?
+        } else {
+            return Err(UnitError::InvalidExponent);
+        };
+
+        let result = self.combine(rhs, dimension, f64::powf);
+        if result.value.is_nan() {
+            return Err(UnitError::InvalidExponent);
+        }
+        result.finite()
+    }
+
+    /// Parse a physical value from the AST, e.g. `10kohm +/- 5%` or `1V to 2V`. The parser
+    /// doesn't keep the unit of the upper end of a range, so it takes the unit of the value.
+    pub fn from_physical(physical: &PhysicalValue) -> Result<Self, UnitError> {
+        let (scale, dimension) = match &physical.unit {
+            Some(unit) => parse_unit(unit)?,
+            None => (1.0, Dimension::DIMENSIONLESS),
+        };
+        let value = parse_number(&physical.value)? * scale;
+        let quantity = Self::new(value, dimension);
+
+        let Some(tolerance) = &physical.tolerance else {
+            return Ok(quantity);
+        };
+
+        match tolerance.deref() {
+            Tolerance::Bilateral { value: tol, unit } => {
+                let tol_value = parse_number(tol)?;
+                let deviation = match unit {
+                    None => (value * tol_value / 100.0).abs(),
+                    Some(unit) => {
+                        let (tol_scale, tol_dimension) = parse_unit(unit)?;
+                        if tol_dimension.is_dimensionless() {
+                            (value * tol_value * tol_scale).abs()
+                        } else if tol_dimension == dimension {
+                            (tol_value * tol_scale).abs()
+                        } else {
+                            return Err(UnitError::ToleranceMismatch(
+                                format!("{}{}", tol.deref(), unit.deref()),
+                                dimension,
+                            ));
+                        }
+                    }
+                };
+                Ok(quantity.with_bounds(value - deviation, value + deviation))
+            }
+            Tolerance::Bound { max, .. } => {
+                let max = parse_number(max)? * scale;
+                Ok(Self {
+                    value: (value + max) / 2.0,
+                    ..quantity.with_bounds(value, max)
+                })
+            }
+        }
+    }
+
+    /// The symbol used to format a quantity of this dimension, if there is one.
+    fn unit_symbol(&self) -> Option<&'static str> {
+        UNITS
+            .iter()
+            .find(|(_, scale, d)| *d == self.dimension && *scale == 1.0)
+            .map(|(u, _, _)| *u)
+    }
+}
+
+/// Format `value` with an SI prefix and `unit`, e.g. `4.7kohm`.
+fn format_with_prefix(value: f64, unit: &str) -> String {
+    let (prefix, factor) = if value == 0.0 || unit.is_empty() {
+        ("", 1.0)
+    } else {
+        DISPLAY_PREFIXES
+            .iter()
+            .find(|(_, factor)| value.abs() >= factor * (1.0 - 1e-9))
+            .copied()
+            .unwrap_or(("f", 1e-15))
+    };
+    let scaled = format!("{:.4}", value / factor);
+    let scaled = scaled.trim_end_matches('0').trim_end_matches('.');
+    format!("{}{}{}", scaled, prefix, unit)
+}
+
+impl Display for Quantity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let unit = match self.unit_symbol() {
+            Some(unit) => unit.to_string(),
+            None if self.dimension.is_dimensionless() => String::new(),
+            None => format!(" {}", self.dimension),
+        };
+
+        match (self.min, self.max) {
+            (Some(min), Some(max)) if self.value != 0.0 => {
+                // Symmetric tolerances that are a round percentage are shown as `± x%`,
+                // anything else as a range.
+                let deviation = (max - self.value).abs();
+                let percent = deviation / self.value.abs() * 100.0;
+                let is_symmetric = ((self.value - min).abs() - deviation).abs() <= deviation * 1e-9;
+                let is_round = ((percent * 1000.0).round() - percent * 1000.0).abs() < 1e-6;
+                if is_symmetric && is_round {
+                    let percent = format!("{:.3}", percent);
+                    write!(
+                        f,
+                        "{} ± {}%",
+                        format_with_prefix(self.value, &unit),
+                        percent.trim_end_matches('0').trim_end_matches('.')
+                    )
+                } else {
+                    write!(
+                        f,
+                        "{} to {}",
+                        format_with_prefix(min, &unit),
+                        format_with_prefix(max, &unit)
+                    )
+                }
+            }
+            (Some(min), Some(max)) => write!(
+                f,
+                "{} to {}",
+                format_with_prefix(min, &unit),
+                format_with_prefix(max, &unit)
+            ),
+            _ => write!(f, "{}", format_with_prefix(self.value, &unit)),
+        }
+    }
+}
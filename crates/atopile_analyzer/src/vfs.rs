@@ -0,0 +1,166 @@
+//! The file systems sources are loaded from.
+//!
+//! The analyzer never touches the disk directly: it reads through a [`Vfs`], so that it can run
+//! against unsaved buffers or entirely in memory. Sources set on the analyzer are overlaid on
+//! top of the [`Vfs`] and always take precedence over it.
+
+use std::{
+    collections::HashMap,
+    io,
+    path::{Component, Path, PathBuf},
+};
+
+pub trait Vfs {
+    /// Read the whole file at `path`.
+    fn read_to_string(&self, path: &Path) -> io::Result<String>;
+
+    /// Whether there is a file at `path`.
+    fn is_file(&self, path: &Path) -> bool;
+
+    /// The canonical form of `path`, which must be an existing file or directory.
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf>;
+
+    /// The `.ato` files under `dir`, leaving out hidden directories such as `.ato` and `.git`.
+    fn find_sources(&self, dir: &Path) -> Vec<PathBuf>;
+}
+
+/// The file system of the operating system.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct OsVfs;
+
+impl Vfs for OsVfs {
+    fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        std::fs::read_to_string(path)
+    }
+
+    fn is_file(&self, path: &Path) -> bool {
+        path.is_file()
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        path.canonicalize()
+    }
+
+    fn find_sources(&self, dir: &Path) -> Vec<PathBuf> {
+        let mut sources = vec![];
+        let mut dirs = vec![dir.to_path_buf()];
+        while let Some(dir) = dirs.pop() {
+            let Ok(entries) = std::fs::read_dir(&dir) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                let path = entry.path();
+                match entry.file_type() {
+                    Ok(file_type) if file_type.is_dir() && !is_hidden(&path) => dirs.push(path),
+                    Ok(file_type) if file_type.is_file() && is_source(&path) => sources.push(path),
+                    _ => {}
+                }
+            }
+        }
+        sources
+    }
+}
+
+/// A file system that only exists in memory. Directories exist implicitly as the ancestors of
+/// its files.
+#[derive(Debug, Default, Clone)]
+pub struct MemoryVfs {
+    files: HashMap<PathBuf, String>,
+}
+
+impl MemoryVfs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create or overwrite the file at `path`.
+    pub fn insert(&mut self, path: impl AsRef<Path>, contents: impl Into<String>) {
+        self.files.insert(normalize(path.as_ref()), contents.into());
+    }
+
+    pub fn remove(&mut self, path: &Path) -> Option<String> {
+        self.files.remove(&normalize(path))
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        self.files.keys().any(|file| file.starts_with(path))
+    }
+}
+
+impl Vfs for MemoryVfs {
+    fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        self.files
+            .get(&normalize(path))
+            .cloned()
+            .ok_or_else(|| not_found(path))
+    }
+
+    fn is_file(&self, path: &Path) -> bool {
+        self.files.contains_key(&normalize(path))
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        let path = normalize(path);
+        if self.files.contains_key(&path) || self.is_dir(&path) {
+            Ok(path)
+        } else {
+            Err(not_found(&path))
+        }
+    }
+
+    fn find_sources(&self, dir: &Path) -> Vec<PathBuf> {
+        let dir = normalize(dir);
+        self.files
+            .keys()
+            .filter(|path| is_source_under(&dir, path))
+            .cloned()
+            .collect()
+    }
+}
+
+/// Resolve `.` and `..` components of `path` without looking at any file system.
+pub fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => match normalized.components().next_back() {
+                Some(Component::Normal(_)) => {
+                    normalized.pop();
+                }
+                // `..` of the root is the root itself.
+                Some(Component::RootDir | Component::Prefix(_)) => {}
+                _ => normalized.push(component),
+            },
+            _ => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+fn is_hidden(path: &Path) -> bool {
+    path.file_name()
+        .is_some_and(|name| name.to_string_lossy().starts_with('.'))
+}
+
+fn is_source(path: &Path) -> bool {
+    path.extension().is_some_and(|extension| extension == "ato")
+}
+
+/// Whether `path` is an `.ato` file under `dir`, outside of any hidden directory.
+pub(crate) fn is_source_under(dir: &Path, path: &Path) -> bool {
+    let Ok(relative) = path.strip_prefix(dir) else {
+        return false;
+    };
+    is_source(path)
+        && relative
+            .parent()
+            .is_none_or(|parent| !parent.ancestors().any(is_hidden))
+}
+
+fn not_found(path: &Path) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("{} does not exist", path.display()),
+    )
+}
@@ -0,0 +1,565 @@
+use atopile_analyzer::vfs::MemoryVfs;
+use atopile_analyzer::AtopileAnalyzer;
+use atopile_parser::{AtopileSource, Position};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+const PARTS: &str = r#"interface Power:
+    signal vcc
+    signal gnd
+
+component Resistor:
+    """A two-terminal resistor."""
+    signal p1 ~ pin 1
+    signal p2 ~ pin 2
+    resistance: resistance
+
+component BigResistor from Resistor:
+    footprint = "R0805"
+
+module Regulator:
+    power = new Power
+    r = new Resistor
+"#;
+
+const BOARD: &str = r#"from "parts.ato" import Resistor, BigResistor, Regulator, Power
+
+module Board:
+    power = new Power
+    r1 = new Resistor
+    reg = new Regulator
+    reg.r -> BigResistor
+    r1.p1 ~ power.vcc
+    r1.resistance = 10kohm
+    assert r1.resistance within 5%
+    reg.r.footprint = "R0603"
+"#;
+
+fn analyzer() -> AtopileAnalyzer<MemoryVfs> {
+    let mut vfs = MemoryVfs::new();
+    vfs.insert("/project/ato.yaml", "");
+    vfs.insert("/project/parts.ato", PARTS);
+    vfs.insert("/project/board.ato", BOARD);
+    AtopileAnalyzer::with_vfs(vfs)
+}
+
+/// The position of the first occurrence of `needle` in `text`, after the first occurrence of
+/// `after`.
+fn position_of(text: &str, after: &str, needle: &str) -> Position {
+    let start = text.find(after).unwrap();
+    let offset = start + text[start..].find(needle).unwrap();
+    let line = text[..offset].matches('\n').count();
+    let column = offset - text[..offset].rfind('\n').map_or(0, |i| i + 1);
+    Position { line, column }
+}
+
+/// Where goto definition at `needle` in the line starting with `line` of the board leads, as
+/// `file:position`.
+#[track_caller]
+fn goto_definition(analyzer: &AtopileAnalyzer<MemoryVfs>, line: &str, needle: &str) -> String {
+    let result = analyzer
+        .goto_definition(
+            &PathBuf::from("/project/board.ato"),
+            position_of(BOARD, line, needle),
+        )
+        .unwrap()
+        .expect("definition should be found");
+    format!(
+        "{}:{}",
+        result.file.file_name().unwrap().to_string_lossy(),
+        result.target_range.start
+    )
+}
+
+fn location(file: &str, text: &str, after: &str, needle: &str) -> String {
+    format!("{}:{}", file, position_of(text, after, needle))
+}
+
+#[test]
+fn goto_port_definition() {
+    let analyzer = analyzer();
+
+    // Instances declared in the same block.
+    assert_eq!(
+        goto_definition(&analyzer, "    r1.p1 ~", "r1"),
+        location("board.ato", BOARD, "    r1 = new", "r1")
+    );
+
+    // Signals of the instance's type, in another file.
+    assert_eq!(
+        goto_definition(&analyzer, "    r1.p1 ~", "p1"),
+        location("parts.ato", PARTS, "signal p1", "p1")
+    );
+    assert_eq!(
+        goto_definition(&analyzer, "    r1.p1 ~", "vcc"),
+        location("parts.ato", PARTS, "signal vcc", "vcc")
+    );
+
+    // Attributes, in assignments and asserts.
+    assert_eq!(
+        goto_definition(&analyzer, "    r1.resistance =", "resistance"),
+        location("parts.ato", PARTS, "    resistance:", "resistance")
+    );
+    assert_eq!(
+        goto_definition(&analyzer, "    assert", "resistance"),
+        location("parts.ato", PARTS, "    resistance:", "resistance")
+    );
+
+    // Through an instance of a module, specialized to a type that inherits.
+    assert_eq!(
+        goto_definition(&analyzer, "    reg.r.footprint", "r."),
+        location("parts.ato", PARTS, "    r = new", "r")
+    );
+    assert_eq!(
+        goto_definition(&analyzer, "    reg.r.footprint", "footprint"),
+        location("parts.ato", PARTS, "    footprint", "footprint")
+    );
+}
+
+/// The references to `needle` in the line starting with `line` of the board, as
+/// `file:position`, in the order they're found.
+#[track_caller]
+fn references(analyzer: &AtopileAnalyzer<MemoryVfs>, line: &str, needle: &str) -> Vec<String> {
+    analyzer
+        .find_references(
+            &PathBuf::from("/project/board.ato"),
+            position_of(BOARD, line, needle),
+            true,
+        )
+        .unwrap()
+        .into_iter()
+        .map(|location| {
+            format!(
+                "{}:{}",
+                location.file.file_name().unwrap().to_string_lossy(),
+                location.range.start
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn find_references() {
+    let analyzer = analyzer();
+
+    // Blocks, wherever they're imported, instantiated, inherited from or specialized to.
+    assert_eq!(
+        references(&analyzer, "    r1 = new", "Resistor"),
+        vec![
+            location("board.ato", BOARD, "from", "Resistor"),
+            location("board.ato", BOARD, "    r1 = new", "Resistor"),
+            location("parts.ato", PARTS, "component Resistor", "Resistor"),
+            location("parts.ato", PARTS, "from Resistor", "Resistor"),
+            location("parts.ato", PARTS, "    r = new", "Resistor"),
+        ]
+    );
+    assert_eq!(
+        references(&analyzer, "    reg.r ->", "BigResistor"),
+        vec![
+            location("board.ato", BOARD, "from", "BigResistor"),
+            location("board.ato", BOARD, "    reg.r ->", "BigResistor"),
+            location("parts.ato", PARTS, "component BigResistor", "BigResistor"),
+        ]
+    );
+
+    // Signals, through the instances of their block in other files.
+    assert_eq!(
+        references(&analyzer, "    r1.p1 ~", "p1"),
+        vec![
+            location("board.ato", BOARD, "    r1.p1 ~", "p1"),
+            location("parts.ato", PARTS, "signal p1", "p1"),
+        ]
+    );
+
+    // Instances within their block.
+    assert_eq!(
+        references(&analyzer, "    r1 = new", "r1"),
+        vec![
+            location("board.ato", BOARD, "    r1 = new", "r1"),
+            location("board.ato", BOARD, "    r1.p1 ~", "r1"),
+            location("board.ato", BOARD, "    r1.resistance =", "r1"),
+            location("board.ato", BOARD, "    assert", "r1"),
+        ]
+    );
+
+    // Attributes, including where they're declared.
+    assert_eq!(
+        references(&analyzer, "    assert", "resistance"),
+        vec![
+            location("board.ato", BOARD, "    r1.resistance =", "resistance"),
+            location("board.ato", BOARD, "    assert", "resistance"),
+            location("parts.ato", PARTS, "    resistance:", "resistance"),
+        ]
+    );
+
+    // The declaration itself can be left out.
+    let references = analyzer
+        .find_references(
+            &PathBuf::from("/project/board.ato"),
+            position_of(BOARD, "    r1.p1 ~", "p1"),
+            false,
+        )
+        .unwrap();
+    assert_eq!(references.len(), 1);
+    assert_eq!(references[0].file, PathBuf::from("/project/board.ato"));
+}
+
+#[test]
+fn rename() {
+    let mut vfs = MemoryVfs::new();
+    vfs.insert("/project/ato.yaml", "");
+    vfs.insert("/project/parts.ato", PARTS);
+    vfs.insert("/project/board.ato", BOARD);
+    // Files of the project that haven't been opened are renamed too.
+    vfs.insert(
+        "/project/other.ato",
+        "from \"parts.ato\" import Power\n\nmodule Other:\n    power = new Power\n",
+    );
+    let analyzer = AtopileAnalyzer::with_vfs(vfs);
+    let path = PathBuf::from("/project/parts.ato");
+    let position = position_of(PARTS, "interface", "Power");
+
+    assert_eq!(
+        analyzer.prepare_rename(&path, position).unwrap(),
+        Some(atopile_analyzer::Range {
+            start: position,
+            end: Position {
+                line: position.line,
+                column: position.column + "Power".len(),
+            },
+        })
+    );
+    assert_eq!(
+        analyzer
+            .prepare_rename(&path, position_of(PARTS, "signal vcc", "signal"))
+            .unwrap(),
+        None
+    );
+
+    let mut edits: Vec<_> = analyzer
+        .rename(&path, position, "Supply")
+        .unwrap()
+        .into_iter()
+        .map(|edit| {
+            assert_eq!(edit.new_text, "Supply");
+            format!(
+                "{}:{}",
+                edit.location.file.file_name().unwrap().to_string_lossy(),
+                edit.location.range.start
+            )
+        })
+        .collect();
+    edits.sort();
+    assert_eq!(
+        edits,
+        vec![
+            location("board.ato", BOARD, "from", "Power"),
+            location("board.ato", BOARD, "    power = new", "Power"),
+            "other.ato:0:24".to_string(),
+            "other.ato:3:16".to_string(),
+            location("parts.ato", PARTS, "interface", "Power"),
+            location("parts.ato", PARTS, "    power = new", "Power"),
+        ]
+    );
+
+    let err = analyzer.rename(&path, position, "not a name").unwrap_err();
+    assert_eq!(err.to_string(), "`not a name` is not a valid name");
+    let err = analyzer.rename(&path, position, "signal").unwrap_err();
+    assert_eq!(err.to_string(), "`signal` is not a valid name");
+
+    // Names that would clash with another in any of the files renamed, or in the block.
+    let err = analyzer.rename(&path, position, "Resistor").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "`Resistor` is already declared or imported in /project/board.ato"
+    );
+    let err = analyzer.rename(&path, position, "Other").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "`Other` is already declared or imported in /project/other.ato"
+    );
+    assert!(analyzer.rename(&path, position, "Power").is_ok());
+
+    let board = PathBuf::from("/project/board.ato");
+    let position = position_of(BOARD, "    r1 = new", "r1");
+    let err = analyzer.rename(&board, position, "reg").unwrap_err();
+    assert_eq!(err.to_string(), "`Board` already has a member named `reg`");
+    assert!(analyzer.rename(&board, position, "r2").is_ok());
+
+    let position = position_of(PARTS, "signal p1", "p1");
+    let err = analyzer.rename(&path, position, "p2").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "`Resistor` already has a member named `p2`"
+    );
+}
+
+#[test]
+fn rename_leaves_packages_and_builtins_alone() {
+    let board = r#"from "generics/resistors.ato" import Resistor
+
+module Board:
+    r1 = new Resistor
+    c1 = new Capacitor
+    c1.footprint = "C0603"
+
+component Capacitor:
+    footprint = "C0402"
+"#;
+    let mut vfs = MemoryVfs::new();
+    vfs.insert("/project/ato.yaml", "dependencies:\n  - generics\n");
+    vfs.insert("/project/board.ato", board);
+    vfs.insert(
+        "/project/.ato/modules/generics/resistors.ato",
+        "component Resistor:\n    signal p1\n",
+    );
+    let analyzer = AtopileAnalyzer::with_vfs(vfs);
+    let path = PathBuf::from("/project/board.ato");
+
+    // Blocks declared by an installed package.
+    let position = position_of(board, "    r1 = new", "Resistor");
+    assert!(analyzer
+        .find_references(&path, position, true)
+        .unwrap()
+        .iter()
+        .any(|location| location.file.starts_with("/project/.ato")));
+    assert_eq!(analyzer.prepare_rename(&path, position).unwrap(), None);
+    let err = analyzer.rename(&path, position, "Res").unwrap_err();
+    assert_eq!(err.to_string(), "This can't be renamed");
+
+    // Builtin attributes.
+    let position = position_of(board, "    c1.footprint", "footprint");
+    assert!(!analyzer
+        .find_references(&path, position, true)
+        .unwrap()
+        .is_empty());
+    assert_eq!(analyzer.prepare_rename(&path, position).unwrap(), None);
+    assert!(analyzer.rename(&path, position, "package").is_err());
+
+    // Instances declared in the project itself can still be renamed.
+    let position = position_of(board, "    r1 = new", "r1");
+    assert!(analyzer.prepare_rename(&path, position).unwrap().is_some());
+}
+
+/// The hover shown at `needle` in the line starting with `line` of the board, once it has been
+/// evaluated.
+#[track_caller]
+fn hover(analyzer: &AtopileAnalyzer<MemoryVfs>, line: &str, needle: &str) -> String {
+    analyzer
+        .hover(
+            &PathBuf::from("/project/board.ato"),
+            position_of(BOARD, line, needle),
+        )
+        .unwrap()
+        .expect("hover should be shown")
+        .contents
+}
+
+#[test]
+fn hover_description() {
+    let mut analyzer = analyzer();
+    let path = PathBuf::from("/project/board.ato");
+    analyzer
+        .set_source(
+            &path,
+            Arc::new(AtopileSource::new(BOARD.to_string(), path.clone())),
+        )
+        .unwrap();
+
+    // Blocks show their kind, what they inherit from and their doc comment.
+    assert_eq!(
+        hover(&analyzer, "    r1 = new", "Resistor"),
+        "```ato\ncomponent Resistor\n```\n\nA two-terminal resistor.\n"
+    );
+    assert_eq!(
+        hover(&analyzer, "    reg.r ->", "BigResistor"),
+        "```ato\ncomponent BigResistor\n```\n\nInherits from `Resistor`\n"
+    );
+
+    // Instances show their type and attributes, as specialized.
+    assert_eq!(
+        hover(&analyzer, "    r1.p1 ~", "r1"),
+        "```ato\nr1: Resistor\n```\n\n- `resistance`: 10kohm (resistance)\n"
+    );
+    assert_eq!(
+        hover(&analyzer, "    reg.r.footprint", "r."),
+        "```ato\nreg.r: BigResistor\n```\n\n- `footprint`: \"R0603\"\n- `resistance`: resistance, unassigned\n"
+    );
+
+    // Signals show the net they're on.
+    assert_eq!(
+        hover(&analyzer, "    r1.p1 ~", "vcc"),
+        "```ato\nsignal power.vcc\n```\n\nOn net `power.vcc`, with:\n- `r1.1`\n- `r1.p1`\n"
+    );
+
+    // Attributes show their value.
+    assert_eq!(
+        hover(&analyzer, "    assert", "resistance"),
+        "```ato\nresistance: resistance\n```\n\n10kohm (resistance)\n"
+    );
+}
+
+/// The completions at the end of the line starting with `line`, once the board has been changed
+/// to `text`.
+#[track_caller]
+fn completions(
+    analyzer: &mut AtopileAnalyzer<MemoryVfs>,
+    text: &str,
+    line: &str,
+) -> Vec<atopile_analyzer::Completion> {
+    let path = PathBuf::from("/project/board.ato");
+    analyzer
+        .set_source(
+            &path,
+            Arc::new(AtopileSource::new(text.to_string(), path.clone())),
+        )
+        .unwrap();
+    analyzer
+        .completions(&path, position_of(text, line, "\n"))
+        .unwrap()
+}
+
+fn labels(completions: &[atopile_analyzer::Completion]) -> Vec<&str> {
+    completions
+        .iter()
+        .map(|completion| completion.label.as_str())
+        .collect()
+}
+
+#[test]
+fn complete() {
+    let mut vfs = MemoryVfs::new();
+    vfs.insert("/project/ato.yaml", "dependencies:\n  - generics\n");
+    vfs.insert("/project/parts.ato", PARTS);
+    vfs.insert(
+        "/project/.ato/modules/generics/resistors.ato",
+        "component Resistor:\n    pass\n",
+    );
+    let mut analyzer = AtopileAnalyzer::with_vfs(vfs);
+
+    // Blocks in scope after `new` and `->`.
+    let text = format!("{}    x = new \n", BOARD);
+    assert_eq!(
+        labels(&completions(&mut analyzer, &text, "    x = new")),
+        vec!["BigResistor", "Board", "Power", "Regulator", "Resistor"]
+    );
+    let text = format!("{}    reg.r -> Big\n", BOARD);
+    let completion = &completions(&mut analyzer, &text, "    reg.r -> Big\n")[0];
+    assert_eq!(completion.kind, atopile_analyzer::CompletionKind::Component);
+    assert_eq!(completion.detail.as_deref(), Some("parts.ato"));
+
+    // The blocks a file declares, after `import`.
+    let text = format!("from \"parts.ato\" import \n{}", BOARD);
+    assert_eq!(
+        labels(&completions(
+            &mut analyzer,
+            &text,
+            "from \"parts.ato\" import"
+        )),
+        vec!["Power", "Resistor", "BigResistor", "Regulator"]
+    );
+
+    // Files inside an import's string, including those of packages, replacing what has been
+    // typed of the path.
+    let text = format!("from \"ge\n{}", BOARD);
+    let files = completions(&mut analyzer, &text, "from \"ge");
+    assert_eq!(labels(&files), vec!["generics/resistors.ato", "parts.ato"]);
+    assert_eq!(
+        files[0].range.unwrap().start,
+        Position { line: 0, column: 6 }
+    );
+
+    // The children and attributes of an instance, after `.`.
+    let text = format!("{}    r1.\n", BOARD);
+    assert_eq!(
+        labels(&completions(&mut analyzer, &text, "    r1.\n")),
+        vec!["1", "2", "p1", "p2", "resistance"]
+    );
+    let text = format!("{}    reg.r.\n", BOARD);
+    let members = completions(&mut analyzer, &text, "    reg.r.\n");
+    let footprint = members
+        .iter()
+        .find(|completion| completion.label == "footprint")
+        .unwrap();
+    assert_eq!(footprint.detail.as_deref(), Some("\"R0603\""));
+
+    // Units after a number, replacing the unit typed so far.
+    let text = format!("{}    r1.resistance = 10k\n", BOARD);
+    let units = completions(&mut analyzer, &text, "    r1.resistance = 10k");
+    let kohm = units
+        .iter()
+        .find(|completion| completion.label == "kohm")
+        .unwrap();
+    assert_eq!(kohm.detail.as_deref(), Some("resistance"));
+    assert_eq!(
+        kohm.range.unwrap().start,
+        position_of(&text, "    r1.resistance = 10k", "k")
+    );
+
+    // But not after pin numbers.
+    let text = format!("{}    signal p3 ~ pin 3\n", BOARD);
+    assert_eq!(
+        completions(&mut analyzer, &text, "    signal p3 ~ pin 3"),
+        vec![]
+    );
+}
+
+#[test]
+fn complete_after_non_ascii() {
+    let mut analyzer = analyzer();
+    let path = PathBuf::from("/project/board.ato");
+    let text = format!("{}    r1.resistance = 10kohm ± 5p\n", BOARD);
+    analyzer
+        .set_source(
+            &path,
+            Arc::new(AtopileSource::new(text.clone(), path.clone())),
+        )
+        .unwrap();
+
+    // Columns from the editor count UTF-16 code units, which are converted to the byte columns
+    // used everywhere else, and back.
+    let line = BOARD.lines().count();
+    let utf16 = |prefix: &str| Position {
+        line,
+        column: prefix.encode_utf16().count(),
+    };
+    let at = |prefix: &str| Position {
+        line,
+        column: prefix.len(),
+    };
+    let prefix = "    r1.resistance = 10kohm ± 5p";
+    assert_eq!(
+        analyzer.position_from_utf16(&path, utf16(prefix)),
+        at(prefix)
+    );
+    assert_eq!(analyzer.position_to_utf16(&path, at(prefix)), utf16(prefix));
+
+    // A column within `±` doesn't split it.
+    analyzer
+        .completions(&path, utf16("    r1.resistance = 10kohm ±"))
+        .unwrap();
+
+    let units = analyzer.completions(&path, at(prefix)).unwrap();
+    let pf = units
+        .iter()
+        .find(|completion| completion.label == "pF")
+        .unwrap();
+    assert_eq!(
+        pf.range.unwrap().start,
+        at("    r1.resistance = 10kohm ± 5")
+    );
+
+    // Positions past the end of the line or file are clamped to it.
+    analyzer
+        .completions(&path, Position { line, column: 100 })
+        .unwrap();
+    analyzer
+        .completions(
+            &path,
+            Position {
+                line: line + 10,
+                column: 0,
+            },
+        )
+        .unwrap();
+}
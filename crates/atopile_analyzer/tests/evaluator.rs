@@ -2,8 +2,11 @@ use atopile_analyzer::diagnostics::{
     AnalyzerDiagnostic, AnalyzerDiagnosticKind, AnalyzerDiagnosticSeverity,
 };
 use atopile_analyzer::evaluator::Evaluator;
+use atopile_analyzer::netlist::KicadNetlist;
+use atopile_analyzer::vfs::MemoryVfs;
 use atopile_parser::AtopileSource;
 use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
 use std::path::PathBuf;
 use std::sync::Arc;
@@ -11,8 +14,13 @@ use std::sync::Arc;
 #[derive(Debug, Serialize)]
 struct DiagnosticInfo {
     severity: String,
+    code: String,
     kind: String,
     file: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    related: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    fixes: Vec<String>,
 }
 
 impl From<&AnalyzerDiagnostic> for DiagnosticInfo {
@@ -23,14 +31,45 @@ impl From<&AnalyzerDiagnostic> for DiagnosticInfo {
         };
 
         let kind = match &diag.kind {
-            AnalyzerDiagnosticKind::UnconnectedInterface(_) => "UnconnectedInterface",
+            AnalyzerDiagnosticKind::UnconnectedInterface(diag) => &format!(
+                "UnconnectedInterface: {}.{}",
+                diag.instance_name, diag.interface_name
+            ),
             AnalyzerDiagnosticKind::Evaluator(err) => &format!("Evaluator: {}", err),
+            AnalyzerDiagnosticKind::Syntax(diag) => {
+                &format!("Syntax: {} at {}", diag.error, diag.location.range.start)
+            }
         };
 
+        let related = diag
+            .related()
+            .iter()
+            .map(|related| format!("{} at {}", related.message, related.location.range.start))
+            .collect();
+
+        let fixes = diag
+            .fixes()
+            .iter()
+            .flat_map(|fix| {
+                fix.edits.iter().map(move |edit| {
+                    format!(
+                        "{}: {}..{} -> {:?}",
+                        fix.title,
+                        edit.location.range.start,
+                        edit.location.range.end,
+                        edit.new_text
+                    )
+                })
+            })
+            .collect();
+
         Self {
             severity: severity.to_string(),
+            code: diag.code().to_string(),
             kind: kind.to_string(),
             file: diag.file.to_string_lossy().to_string(),
+            related,
+            fixes,
         }
     }
 }
@@ -95,3 +134,429 @@ create_evaluator_test!(duplicate_declaration);
 create_evaluator_test!(pin_connections);
 create_evaluator_test!(dependency_ordering);
 create_evaluator_test!(specialize);
+create_evaluator_test!(nets);
+create_evaluator_test!(physical_values);
+create_evaluator_test!(expressions);
+create_evaluator_test!(asserts);
+create_evaluator_test!(dimensions);
+create_evaluator_test!(attribute_declarations);
+create_evaluator_test!(unconnected_interfaces);
+create_evaluator_test!(parse_errors);
+create_evaluator_test!(diagnostic_fixes);
+create_evaluator_test!(interface_connections);
+
+/// The state and diagnostics of an evaluator, in a form that doesn't depend on map ordering.
+fn evaluator_json(evaluator: &Evaluator) -> serde_json::Value {
+    let diagnostics: BTreeMap<_, Vec<_>> = evaluator
+        .reporter()
+        .diagnostics()
+        .iter()
+        .map(|(file, diags)| {
+            (
+                file.clone(),
+                diags.iter().map(DiagnosticInfo::from).collect(),
+            )
+        })
+        .collect();
+
+    serde_json::json!({
+        "state": evaluator.state(),
+        "diagnostics": diagnostics,
+    })
+}
+
+#[test]
+fn incremental_evaluation() {
+    let dir = PathBuf::from("tests/resources/incremental")
+        .canonicalize()
+        .unwrap();
+    let lib_path = dir.join("lib.ato");
+    let board_path = dir.join("board.ato");
+    let other_path = dir.join("other.ato");
+
+    let mut texts: HashMap<PathBuf, String> = [&lib_path, &board_path, &other_path]
+        .into_iter()
+        .map(|path| {
+            (
+                path.clone(),
+                fs::read_to_string(path).unwrap().replace("\r\n", "\n"),
+            )
+        })
+        .collect();
+    let source = |texts: &HashMap<PathBuf, String>, path: &PathBuf| {
+        Arc::new(AtopileSource::new(texts[path].clone(), path.clone()))
+    };
+
+    let mut evaluator = Evaluator::default();
+    for path in [&lib_path, &board_path, &other_path] {
+        evaluator.set_source(path, source(&texts, path));
+    }
+
+    let edits = [
+        (
+            &lib_path,
+            "    signal p3 ~ pin 3\n    colour: voltage\n    signal p3 ~ pin 4\n",
+        ),
+        (&board_path, "    r2 = new Resistor\n    r2.colour = 5V\n"),
+        (&other_path, "    y = new Other\n"),
+    ];
+
+    for (path, added) in edits {
+        texts.get_mut(path).unwrap().push_str(added);
+        evaluator.set_source(path, source(&texts, path));
+
+        // Evaluating everything from scratch must give the same result.
+        let mut fresh = Evaluator::default();
+        for path in [&lib_path, &board_path, &other_path] {
+            fresh.set_source(path, source(&texts, path));
+        }
+        fresh.evaluate();
+
+        assert_eq!(evaluator_json(&evaluator), evaluator_json(&fresh));
+    }
+
+    evaluator.remove_source(&other_path);
+    assert!(!evaluator.reporter().diagnostics().contains_key(&other_path));
+}
+
+#[test]
+fn in_memory_sources() {
+    let lib_path = PathBuf::from("/project/parts/lib.ato");
+    let board_path = PathBuf::from("/project/elec/board.ato");
+    let missing_path = PathBuf::from("/project/elec/missing.ato");
+
+    let mut vfs = MemoryVfs::new();
+    vfs.insert("/project/ato.yaml", "");
+    vfs.insert(&lib_path, "component Resistor:\n    signal p1 ~ pin 1\n");
+
+    let error_kinds = |evaluator: &Evaluator<MemoryVfs>| -> Vec<String> {
+        evaluator
+            .reporter()
+            .diagnostics()
+            .get(&board_path)
+            .map_or_else(Vec::new, |diags| {
+                diags
+                    .iter()
+                    .filter_map(|diag| match &diag.kind {
+                        AnalyzerDiagnosticKind::Evaluator(err) => Some(format!("{:?}", err.kind)),
+                        _ => None,
+                    })
+                    .collect()
+            })
+    };
+
+    // Imports resolve against the project root, which only exists in memory.
+    let mut evaluator = Evaluator::with_vfs(vfs);
+    evaluator.set_source(
+        &board_path,
+        Arc::new(AtopileSource::new(
+            "from \"parts/lib.ato\" import Resistor\nfrom \"missing.ato\" import Thing\n\nmodule Board:\n    r1 = new Resistor\n".to_string(),
+            board_path.clone(),
+        )),
+    );
+    assert_eq!(error_kinds(&evaluator), vec!["ImportPathNotFound"]);
+
+    // A source that has been set takes precedence over the file system.
+    evaluator.set_source(
+        &lib_path,
+        Arc::new(AtopileSource::new(
+            "component Capacitor:\n    signal p1 ~ pin 1\n".to_string(),
+            lib_path.clone(),
+        )),
+    );
+    assert_eq!(
+        error_kinds(&evaluator),
+        vec!["ImportNotFound", "ImportPathNotFound", "TypeNotFound"]
+    );
+
+    // Creating a missing file resolves the imports of the files that were looking for it.
+    evaluator.remove_source(&lib_path);
+    evaluator.set_source(
+        &missing_path,
+        Arc::new(AtopileSource::new(
+            "module Thing:\n    pass\n".to_string(),
+            missing_path.clone(),
+        )),
+    );
+    assert_eq!(error_kinds(&evaluator), Vec::<String>::new());
+}
+
+#[test]
+fn instance_locations() {
+    let board_path = PathBuf::from("/project/board.ato");
+    let parts_path = PathBuf::from("/project/parts.ato");
+    let board = "from \"parts.ato\" import Resistor\nfrom \"parts.ato\" import BigResistor\n\nmodule Board:\n    r1 = new Resistor\n    r1 -> BigResistor\n    r1.resistance = 10kohm\n    r1.p1 ~ r1.p2\n";
+
+    let mut vfs = MemoryVfs::new();
+    vfs.insert(
+        &parts_path,
+        "component Resistor:\n    signal p1 ~ pin 1\n    resistance: resistance\n\ncomponent BigResistor from Resistor:\n    signal p2 ~ pin 2\n",
+    );
+    let mut evaluator = Evaluator::with_vfs(vfs);
+    evaluator.set_source(
+        &board_path,
+        Arc::new(AtopileSource::new(board.to_string(), board_path.clone())),
+    );
+
+    let state = serde_json::to_value(evaluator.state()).unwrap();
+    let instance = |name: &str| state["instances"][name].clone();
+    // The file and line each location starts at.
+    let at = |location: &serde_json::Value| {
+        let file = location["file"].as_str().unwrap();
+        let file = file.trim_start_matches("/project/").to_string();
+        (file, location["range"]["start"]["line"].as_u64().unwrap())
+    };
+    let all_at = |locations: &serde_json::Value| -> Vec<(String, u64)> {
+        locations
+            .as_array()
+            .map_or_else(Vec::new, |locations| locations.iter().map(at).collect())
+    };
+    let board = |line| ("board.ato".to_string(), line);
+    let parts = |line| ("parts.ato".to_string(), line);
+
+    // `r1` is created by `new`, and its type changed by `->`.
+    let r1 = instance("/project/board.ato:Board.r1");
+    assert_eq!(at(&r1["location"]), board(4));
+    assert_eq!(all_at(&r1["provenance"]), vec![board(5)]);
+    assert_eq!(at(&r1["assignments"]["resistance"]), board(6));
+
+    // Its signals are declared by its types, and copied in or merged by `new` and `->`.
+    let p1 = instance("/project/board.ato:Board.r1.p1");
+    assert_eq!(at(&p1["location"]), parts(1));
+    assert_eq!(all_at(&p1["provenance"]), vec![board(4), board(5)]);
+    let p2 = instance("/project/board.ato:Board.r1.p2");
+    assert_eq!(at(&p2["location"]), parts(5));
+    assert_eq!(all_at(&p2["provenance"]), vec![board(5)]);
+
+    // Inherited signals were copied through `from`.
+    assert_eq!(
+        all_at(&instance("/project/parts.ato:BigResistor.p1")["provenance"]),
+        vec![parts(4)]
+    );
+
+    // Connections are kept by the closest instance containing both ends.
+    let connections: Vec<_> = r1["connections"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|connection| {
+            (
+                at(&connection["location"]),
+                all_at(&connection["provenance"]),
+            )
+        })
+        .collect();
+    assert_eq!(
+        connections,
+        vec![
+            (parts(1), vec![board(4)]),
+            (parts(1), vec![parts(4), board(5)]),
+            (parts(5), vec![board(5)]),
+            (board(7), vec![]),
+        ]
+    );
+}
+
+#[test]
+fn kicad_netlist() {
+    let file_path = "tests/resources/corpus/kicad_netlist.ato";
+    let input = fs::read_to_string(file_path).unwrap().replace("\r\n", "\n");
+    let path_buf = PathBuf::from(file_path);
+
+    let mut evaluator = Evaluator::default();
+    evaluator.set_source(
+        &path_buf,
+        Arc::new(AtopileSource::new(input, path_buf.clone())),
+    );
+    evaluator.resolve_reference_designators();
+
+    let netlist = evaluator
+        .state()
+        .kicad_netlist(&path_buf, "Divider")
+        .expect("Divider should be evaluated");
+    insta::assert_snapshot!(netlist.to_string());
+}
+
+#[test]
+fn reference_designators() {
+    let file_path = "tests/resources/corpus/designators.ato";
+    let input = fs::read_to_string(file_path).unwrap().replace("\r\n", "\n");
+    let path_buf = PathBuf::from(file_path);
+
+    let mut evaluator = Evaluator::default();
+    evaluator.set_source(
+        &path_buf,
+        Arc::new(AtopileSource::new(input, path_buf.clone())),
+    );
+    evaluator.resolve_reference_designators();
+
+    let netlist = KicadNetlist::from_sexpr(
+        &evaluator
+            .state()
+            .kicad_netlist(&path_buf, "Board")
+            .expect("Board should be evaluated"),
+    );
+    let designators: Vec<_> = netlist
+        .components
+        .iter()
+        .map(|c| {
+            format!(
+                "{} {}",
+                c.reference,
+                c.sheetpath.as_deref().unwrap_or_default()
+            )
+        })
+        .collect();
+    insta::assert_yaml_snapshot!(designators);
+}
+
+#[test]
+fn reference_designators_are_stable() {
+    let path_buf = PathBuf::from("board.ato");
+    let board = |instances: &str| {
+        Arc::new(AtopileSource::new(
+            format!(
+                "component Resistor:\n    signal p1 ~ pin 1\n\nmodule Board:\n{}",
+                instances
+            ),
+            path_buf.clone(),
+        ))
+    };
+    let designators = |evaluator: &mut Evaluator| -> BTreeMap<String, String> {
+        evaluator.resolve_reference_designators();
+        let netlist = KicadNetlist::from_sexpr(
+            &evaluator
+                .state()
+                .kicad_netlist(&path_buf, "Board")
+                .expect("Board should be evaluated"),
+        );
+        netlist
+            .components
+            .iter()
+            .map(|c| (c.sheetpath.clone().unwrap_or_default(), c.reference.clone()))
+            .collect()
+    };
+
+    let mut evaluator = Evaluator::default();
+    evaluator.set_source(
+        &path_buf,
+        board("    r_b = new Resistor\n    r_c = new Resistor\n"),
+    );
+    let before = designators(&mut evaluator);
+    assert_eq!(before.values().collect::<Vec<_>>(), vec!["R1", "R2"]);
+
+    // A component that sorts before the others gets the next free number, and the others
+    // keep theirs.
+    evaluator.set_source(
+        &path_buf,
+        board("    r_a = new Resistor\n    r_b = new Resistor\n    r_c = new Resistor\n"),
+    );
+    let after = designators(&mut evaluator);
+    for (sheetpath, reference) in &before {
+        assert_eq!(after.get(sheetpath), Some(reference));
+    }
+    assert_eq!(after.len(), 3);
+    assert!(after.values().any(|reference| reference == "R3"));
+}
+
+#[test]
+fn reference_designators_sort_naturally() {
+    let path_buf = PathBuf::from("board.ato");
+    let instances: String = (1..=10)
+        .map(|i| format!("    r{} = new Resistor\n", i))
+        .collect();
+    let mut evaluator = Evaluator::default();
+    evaluator.set_source(
+        &path_buf,
+        Arc::new(AtopileSource::new(
+            format!(
+                "component Resistor:\n    signal p1 ~ pin 1\n\nmodule Board:\n{}",
+                instances
+            ),
+            path_buf.clone(),
+        )),
+    );
+    evaluator.resolve_reference_designators();
+
+    let netlist = KicadNetlist::from_sexpr(
+        &evaluator
+            .state()
+            .kicad_netlist(&path_buf, "Board")
+            .expect("Board should be evaluated"),
+    );
+    let designators: Vec<_> = netlist
+        .components
+        .iter()
+        .map(|c| {
+            let sheetpath = c.sheetpath.as_deref().unwrap_or_default();
+            format!("{} {}", c.reference, sheetpath.rsplit("::").next().unwrap())
+        })
+        .collect();
+    let expected: Vec<_> = (1..=10).map(|i| format!("R{} r{}", i, i)).collect();
+    assert_eq!(designators, expected);
+}
+
+#[test]
+fn net_ids_are_stable() {
+    let path_buf = PathBuf::from("board.ato");
+    let board = |connections: &str| {
+        Arc::new(AtopileSource::new(
+            format!(
+                "component Resistor:\n    signal p1 ~ pin 1\n    signal p2 ~ pin 2\n\nmodule Board:\n    signal vin\n    r_a = new Resistor\n    r_b = new Resistor\n{}",
+                connections
+            ),
+            path_buf.clone(),
+        ))
+    };
+    // The ids of the nets `vin` is on.
+    let net_ids = |evaluator: &Evaluator| -> Vec<String> {
+        let state = serde_json::to_value(evaluator.state()).unwrap();
+        state["nets"]["board.ato:Board"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter(|net| {
+                net["members"]
+                    .as_array()
+                    .unwrap()
+                    .contains(&serde_json::json!("board.ato:Board.vin"))
+            })
+            .map(|net| net["id"].as_str().unwrap().to_string())
+            .collect()
+    };
+
+    let mut evaluator = Evaluator::default();
+    evaluator.set_source(&path_buf, board("    vin ~ r_b.p1\n"));
+    assert_eq!(net_ids(&evaluator), vec!["vin"]);
+
+    // Connecting a member that sorts first doesn't rename the net.
+    evaluator.set_source(&path_buf, board("    vin ~ r_b.p1\n    r_a.p1 ~ vin\n"));
+    assert_eq!(net_ids(&evaluator), vec!["vin"]);
+}
+
+#[test]
+fn bare_number_arithmetic() {
+    let path_buf = PathBuf::from("numbers.ato");
+    let mut evaluator = Evaluator::default();
+    evaluator.set_source(
+        &path_buf,
+        Arc::new(AtopileSource::new(
+            "module Numbers:\n    product = 2 * 3\n    literal = 6\n".to_string(),
+            path_buf.clone(),
+        )),
+    );
+
+    // Bare numbers are unitless physical values, so arithmetic on them gives a dimensionless
+    // quantity just like writing the result out.
+    let state = serde_json::to_value(evaluator.state()).unwrap();
+    let attributes = &state["instances"]["numbers.ato:Numbers"]["attributes"];
+    assert_eq!(
+        attributes["product"]["Physical"]["dimension"],
+        "dimensionless"
+    );
+    assert_eq!(attributes["product"]["Physical"]["value"], 6.0);
+    assert_eq!(
+        attributes["product"]["Physical"]["value"],
+        attributes["literal"]["Physical"]["value"]
+    );
+}
@@ -0,0 +1,140 @@
+use atopile_analyzer::evaluator::Evaluator;
+use atopile_analyzer::netlist::KicadNetlist;
+use atopile_parser::AtopileSource;
+use std::fs;
+use std::sync::Arc;
+
+#[test]
+fn parse_kicad_netlist() {
+    let input = fs::read_to_string("tests/resources/netlists/default.net").unwrap();
+    let netlist = KicadNetlist::parse(&input).unwrap();
+
+    assert_eq!(
+        netlist
+            .component_at("/home/user/project/elec/src/main.ato:Divider::r_bottom")
+            .map(|c| c.reference.as_str()),
+        Some("R2")
+    );
+    assert_eq!(
+        netlist
+            .nets_of("R1")
+            .map(|n| n.name.as_str())
+            .collect::<Vec<_>>(),
+        vec!["output", "vin"]
+    );
+
+    insta::assert_yaml_snapshot!(netlist);
+}
+
+#[test]
+fn parse_kicad_netlist_errors() {
+    let err = KicadNetlist::parse("(export\n  (components (comp (ref \"R1\"))").unwrap_err();
+    assert_eq!(err.to_string(), "unclosed `(` at 1:31");
+
+    let err = KicadNetlist::parse("(export (version \"E)").unwrap_err();
+    assert_eq!(err.to_string(), "unterminated string at 0:17");
+
+    let err = KicadNetlist::parse("(design)").unwrap_err();
+    assert_eq!(err.to_string(), "expected `(export ...)` netlist at 0:0");
+
+    let err = KicadNetlist::parse(&"(".repeat(100_000)).unwrap_err();
+    assert_eq!(err.to_string(), "lists are nested too deeply at 0:64");
+}
+
+#[test]
+fn reference_designators_from_netlist() {
+    let temp = assert_fs::TempDir::new().unwrap();
+    let root = temp.path().canonicalize().unwrap();
+    let source_path = root.join("main.ato");
+    let input = fs::read_to_string("tests/resources/corpus/kicad_netlist.ato").unwrap();
+    fs::write(root.join("ato.yaml"), "").unwrap();
+    fs::write(&source_path, &input).unwrap();
+    fs::create_dir(root.join("build")).unwrap();
+
+    // Write a netlist for the design, then pin some designators in it.
+    let mut evaluator = Evaluator::default();
+    evaluator.set_source(
+        &source_path,
+        Arc::new(AtopileSource::new(input, source_path.clone())),
+    );
+    let netlist_path = root.join("build").join("default.net");
+    evaluator
+        .write_kicad_netlist(&source_path, "Divider", &netlist_path)
+        .unwrap();
+
+    // Nothing is written outside the build directory.
+    for out in [root.join("main.net"), root.join("build/../main.net")] {
+        let err = evaluator
+            .write_kicad_netlist(&source_path, "Divider", &out)
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "netlists can only be written to the project's build directory, not {}",
+                root.join("main.net").display()
+            )
+        );
+    }
+    assert!(!root.join("main.net").exists());
+    let netlist = fs::read_to_string(&netlist_path).unwrap();
+    assert!(netlist.contains("(ref \"R1\")") && netlist.contains("(ref \"R2\")"));
+    let netlist = netlist
+        .replace("(ref \"R1\")", "(ref \"R8\")")
+        .replace("(ref \"R2\")", "(ref \"R7\")");
+    fs::write(&netlist_path, netlist).unwrap();
+
+    evaluator.resolve_reference_designators();
+    let netlist = KicadNetlist::from_sexpr(
+        &evaluator
+            .state()
+            .kicad_netlist(&source_path, "Divider")
+            .unwrap(),
+    );
+    let references: Vec<_> = netlist
+        .components
+        .iter()
+        .map(|c| c.reference.as_str())
+        .collect();
+    assert_eq!(references, vec!["R7", "R8"]);
+
+    // A broken netlist is reported against the netlist file.
+    fs::write(&netlist_path, "(export (components").unwrap();
+    evaluator.resolve_reference_designators();
+    let diagnostics = evaluator.reporter().diagnostics();
+    assert_eq!(diagnostics.get(&netlist_path).map(|d| d.len()), Some(1));
+
+    // Reading it again doesn't report it twice, and fixing or removing it drops the error.
+    evaluator.resolve_reference_designators();
+    let diagnostics = evaluator.reporter().diagnostics();
+    assert_eq!(diagnostics.get(&netlist_path).map(|d| d.len()), Some(1));
+
+    fs::write(&netlist_path, "(export (components))").unwrap();
+    evaluator.resolve_reference_designators();
+    assert!(!evaluator
+        .reporter()
+        .diagnostics()
+        .contains_key(&netlist_path));
+
+    fs::write(&netlist_path, "(export (components").unwrap();
+    evaluator.resolve_reference_designators();
+    fs::remove_file(&netlist_path).unwrap();
+    evaluator.resolve_reference_designators();
+    assert!(!evaluator
+        .reporter()
+        .diagnostics()
+        .contains_key(&netlist_path));
+
+    // The designators pinned by the removed netlist don't outlive it.
+    let netlist = KicadNetlist::from_sexpr(
+        &evaluator
+            .state()
+            .kicad_netlist(&source_path, "Divider")
+            .unwrap(),
+    );
+    let references: Vec<_> = netlist
+        .components
+        .iter()
+        .map(|c| c.reference.as_str())
+        .collect();
+    assert_eq!(references, vec!["R1", "R2"]);
+}
@@ -0,0 +1,464 @@
+use atopile_analyzer::db::Database;
+use atopile_analyzer::diagnostics::AnalyzerDiagnosticKind;
+use atopile_analyzer::evaluator::Evaluator;
+use atopile_analyzer::project::{Dependency, EntryPoint, Project, ProjectConfig};
+use atopile_analyzer::vfs::MemoryVfs;
+use atopile_analyzer::AtopileAnalyzer;
+use atopile_parser::{AtopileSource, Position};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+#[test]
+fn parse_project_config() {
+    let config = ProjectConfig::parse(
+        r#"
+requires-atopile: "^0.3.0"
+paths:
+  src: elec/src
+builds:
+  default:
+    entry: elec/src/board.ato:Board
+    targets: [all]
+dependencies:
+  - generics ^1.0.0
+  - name: sensors
+    version-spec: ">=2"
+    path: vendor/sensors
+  - rp2040
+"#,
+    )
+    .unwrap();
+
+    assert_eq!(config.requires_atopile.as_deref(), Some("^0.3.0"));
+    assert_eq!(config.paths.src, PathBuf::from("elec/src"));
+    assert_eq!(config.paths.build, PathBuf::from("build"));
+    assert_eq!(
+        config.builds["default"].entry,
+        EntryPoint {
+            file: PathBuf::from("elec/src/board.ato"),
+            module: "Board".to_string(),
+        }
+    );
+    assert_eq!(
+        config.dependencies,
+        vec![
+            Dependency {
+                name: "generics".to_string(),
+                version_spec: Some("^1.0.0".to_string()),
+                path: None,
+            },
+            Dependency {
+                name: "sensors".to_string(),
+                version_spec: Some(">=2".to_string()),
+                path: Some(PathBuf::from("vendor/sensors")),
+            },
+            Dependency {
+                name: "rp2040".to_string(),
+                version_spec: None,
+                path: None,
+            },
+        ]
+    );
+
+    // An empty `ato.yaml` only marks the project root.
+    assert_eq!(ProjectConfig::parse(""), Ok(ProjectConfig::default()));
+}
+
+#[test]
+fn project_config_errors() {
+    let err = ProjectConfig::parse("builds:\n  default:\n    entry: board.ato\n").unwrap_err();
+    assert!(err.message.contains("expected `path/to/file.ato:Module`"));
+    assert_eq!(err.position, Some(Position { line: 2, column: 4 }));
+
+    let project = Project::new(Path::new("/project"), "builds: [".to_string());
+    assert!(project.error().is_some());
+    assert_eq!(project.config(), &ProjectConfig::default());
+}
+
+#[test]
+fn project_paths() {
+    let project = Project::new(
+        Path::new("/project"),
+        "paths:\n  build: out\nbuilds:\n  main:\n    entry: ./elec/../board.ato:Board\n"
+            .to_string(),
+    );
+    let build = &project.config().builds["main"];
+    assert_eq!(
+        project.entry_path(build),
+        PathBuf::from("/project/board.ato")
+    );
+    assert_eq!(
+        project.netlist_path("main"),
+        PathBuf::from("/project/out/main.net")
+    );
+    assert_eq!(project.src_dir(), PathBuf::from("/project"));
+}
+
+/// The diagnostics reported on `path`, as `code: message`.
+fn diagnostics(evaluator: &Evaluator<MemoryVfs>, path: &Path) -> Vec<String> {
+    evaluator
+        .reporter()
+        .diagnostics()
+        .get(path)
+        .map_or_else(Vec::new, |diags| {
+            diags
+                .iter()
+                .map(|diag| match &diag.kind {
+                    AnalyzerDiagnosticKind::Evaluator(err) => format!(
+                        "{}: {}",
+                        diag.code().code,
+                        err.message.as_deref().unwrap_or_default()
+                    ),
+                    _ => diag.code().code.to_string(),
+                })
+                .collect()
+        })
+}
+
+#[test]
+fn project_builds() {
+    let board_path = PathBuf::from("/project/board.ato");
+    let config_path = PathBuf::from("/project/ato.yaml");
+    let board = "component Resistor:\n    signal p1 ~ pin 1\n    resistance: resistance\n\nmodule Board:\n    supply: voltage\n    r1 = new Resistor\n\nmodule Unused:\n    r2 = new Resistor\n";
+
+    let mut vfs = MemoryVfs::new();
+    vfs.insert(
+        &config_path,
+        "builds:\n  main:\n    entry: board.ato:Board\n  other:\n    entry: board.ato:Boad\n  missing:\n    entry: missing.ato:Board\n",
+    );
+    let mut evaluator = Evaluator::with_vfs(vfs);
+    evaluator.set_source(
+        &board_path,
+        Arc::new(AtopileSource::new(board.to_string(), board_path.clone())),
+    );
+
+    // Only declared builds need their attributes assigned, including on the build's root.
+    assert_eq!(
+        diagnostics(&evaluator, &board_path),
+        vec![
+            "E0204: `Board.supply` is declared as `voltage` but never assigned",
+            "E0204: `Board.r1.resistance` is declared as `resistance` but never assigned",
+        ]
+    );
+    assert_eq!(
+        diagnostics(&evaluator, &config_path),
+        vec![
+            "E0602: `missing.ato` does not exist",
+            "E0602: `Boad` is not declared in `board.ato`; did you mean `Board`?",
+        ]
+    );
+}
+
+#[test]
+fn invalid_project_config() {
+    let board_path = PathBuf::from("/project/board.ato");
+    let config_path = PathBuf::from("/project/ato.yaml");
+
+    let mut vfs = MemoryVfs::new();
+    vfs.insert(&config_path, "builds:\n  main: [\n");
+    let mut evaluator = Evaluator::with_vfs(vfs);
+    evaluator.set_source(
+        &board_path,
+        Arc::new(AtopileSource::new(
+            "module Board:\n    pass\n".to_string(),
+            board_path.clone(),
+        )),
+    );
+
+    let diagnostics = evaluator.reporter().diagnostics();
+    let config_diagnostics = &diagnostics[&config_path];
+    assert_eq!(config_diagnostics.len(), 1);
+    assert_eq!(config_diagnostics[0].code().code, "E0601");
+}
+
+#[test]
+fn evaluate_entry_points() {
+    let board_path = PathBuf::from("/project/board.ato");
+
+    let mut vfs = MemoryVfs::new();
+    vfs.insert(
+        "/project/ato.yaml",
+        "builds:\n  main:\n    entry: board.ato:Board\n",
+    );
+    vfs.insert(
+        "/project/.ato/modules/lib/lib.ato",
+        "component Resistor:\n    signal p1 ~ pin 1\n\nmodule LibModule:\n    r = new Resistor\n",
+    );
+    vfs.insert(
+        &board_path,
+        "from \"lib/lib.ato\" import Resistor\n\nmodule Base:\n    r = new Resistor\n\nmodule Derived from Base:\n    pass\n\nmodule Unused:\n    pass\n\nmodule Board:\n    d = new Derived\n",
+    );
+    vfs.insert("/project/other.ato", "module Other:\n    x = new Missing\n");
+
+    let mut evaluator = Evaluator::with_vfs(vfs);
+    let builds = evaluator.evaluate_project(&board_path);
+
+    assert_eq!(builds.len(), 1);
+    assert_eq!(builds[0].name, "main");
+    assert_eq!(builds[0].entry, "/project/board.ato:Board");
+    let state = serde_json::to_value(&builds[0].state).unwrap();
+    let instances = state["instances"].as_object().unwrap();
+    assert!(instances.len() > 1);
+    assert!(instances
+        .keys()
+        .all(|instance| instance.starts_with("/project/board.ato:Board")));
+
+    // Only the blocks the build is made of are elaborated.
+    let state = serde_json::to_value(evaluator.state()).unwrap();
+    let mut roots: Vec<_> = state["instances"]
+        .as_object()
+        .unwrap()
+        .keys()
+        .filter(|instance| !instance.rsplit(':').next().unwrap().contains('.'))
+        .cloned()
+        .collect();
+    roots.sort();
+    assert_eq!(
+        roots,
+        vec![
+            "/project/.ato/modules/lib/lib.ato:Resistor",
+            "/project/board.ato:Base",
+            "/project/board.ato:Board",
+            "/project/board.ato:Derived",
+        ]
+    );
+
+    // Only the entry file and what it imports are evaluated, and only the project's own
+    // modules can be unused.
+    assert_eq!(
+        diagnostics(&evaluator, &board_path),
+        vec!["E0205: `Unused` isn't used by any build"]
+    );
+    assert!(!evaluator
+        .reporter()
+        .diagnostics()
+        .contains_key(Path::new("/project/other.ato")));
+
+    // Entry points can also be given explicitly.
+    let entry_points = [(
+        "sub".to_string(),
+        EntryPoint {
+            file: board_path.clone(),
+            module: "Derived".to_string(),
+        },
+    )]
+    .into_iter()
+    .collect();
+    let builds = evaluator.evaluate_entry_points(&entry_points);
+    assert_eq!(builds.len(), 1);
+    assert_eq!(builds[0].name, "sub");
+    assert_eq!(
+        diagnostics(&evaluator, &board_path),
+        vec![
+            "E0205: `Board` isn't used by any build",
+            "E0205: `Unused` isn't used by any build",
+        ]
+    );
+}
+
+#[test]
+fn dependency_versions() {
+    let dependency = |spec: &str| Dependency {
+        name: "generics".to_string(),
+        version_spec: Some(spec.to_string()),
+        path: None,
+    };
+    assert!(dependency("^1.0.0").accepts("1.2.0"));
+    assert!(dependency("^v1.0.0").accepts("v1.0.3"));
+    assert!(!dependency("^1.0.0").accepts("2.0.0"));
+    assert!(!dependency(">=2, <3").accepts("1.9.9"));
+    // Versions that aren't semver can't be checked.
+    assert!(dependency("^1.0.0").accepts("main"));
+}
+
+#[test]
+fn package_imports() {
+    let board_path = PathBuf::from("/project/board.ato");
+
+    let mut vfs = MemoryVfs::new();
+    vfs.insert(
+        "/project/ato.yaml",
+        "dependencies:\n  - generics ^1.0.0\n  - sensors ^2.0.0\n  - missing\n",
+    );
+    vfs.insert(
+        "/project/.ato/modules/generics/ato.yaml",
+        "package:\n  name: generics\n  version: 1.2.0\ndependencies:\n  - sensors\n",
+    );
+    // Packages resolve their own dependencies among the packages of the project.
+    vfs.insert(
+        "/project/.ato/modules/generics/resistors.ato",
+        "from \"sensors/sensor.ato\" import Sensor\n\ncomponent Resistor:\n    signal p1 ~ pin 1\n    sensor = new Sensor\n",
+    );
+    vfs.insert(
+        "/project/.ato/modules/sensors/ato.yaml",
+        "package:\n  version: v1.0.0\n",
+    );
+    vfs.insert(
+        "/project/.ato/modules/sensors/sensor.ato",
+        "component Sensor:\n    signal p1 ~ pin 1\n",
+    );
+    vfs.insert(
+        &board_path,
+        "from \"generics/resistors.ato\" import Resistor\nfrom \"sensors/sensor.ato\" import Sensor\nfrom \"missing/lib.ato\" import Thing\n\nmodule Board:\n    r1 = new Resistor\n    s1 = new Sensor\n",
+    );
+
+    let mut evaluator = Evaluator::with_vfs(vfs);
+    evaluator.evaluate_entry_points(
+        &[(
+            "main".to_string(),
+            EntryPoint {
+                file: board_path.clone(),
+                module: "Board".to_string(),
+            },
+        )]
+        .into_iter()
+        .collect(),
+    );
+
+    assert_eq!(
+        diagnostics(&evaluator, &board_path),
+        vec![
+            "E0108: package `sensors` is installed at version v1.0.0, but `^2.0.0` is required",
+            "E0107: package `missing` is not installed in `/project/.ato/modules/missing`",
+        ]
+    );
+    assert!(!evaluator
+        .reporter()
+        .diagnostics()
+        .contains_key(Path::new("/project/.ato/modules/generics/resistors.ato")));
+}
+
+#[test]
+fn reload_project_config() {
+    let dir = assert_fs::TempDir::new().unwrap();
+    let root = dir.path().canonicalize().unwrap();
+    let config_path = root.join("ato.yaml");
+    let board_path = root.join("board.ato");
+    std::fs::write(&config_path, "dependencies:\n  - generics\n").unwrap();
+
+    let mut db = Database::default();
+    let dependencies = |db: &Database| -> Vec<String> {
+        db.project(&board_path)
+            .unwrap()
+            .config()
+            .dependencies
+            .iter()
+            .map(|dependency| dependency.name.clone())
+            .collect()
+    };
+    assert_eq!(dependencies(&db), vec!["generics"]);
+
+    // The config is only read again once it is reported to have changed.
+    std::fs::write(&config_path, "dependencies:\n  - sensors\n").unwrap();
+    assert_eq!(dependencies(&db), vec!["generics"]);
+    db.reload_config(&config_path);
+    assert_eq!(dependencies(&db), vec!["sensors"]);
+
+    std::fs::remove_file(&config_path).unwrap();
+    db.reload_config(&config_path);
+    assert!(db.project(&board_path).is_none());
+}
+
+#[test]
+fn analyzer_evaluates_project_builds() {
+    let board_path = PathBuf::from("/project/board.ato");
+    let parts_path = PathBuf::from("/project/parts.ato");
+    let board = |body: &str| {
+        Arc::new(AtopileSource::new(
+            format!(
+                "from \"parts.ato\" import Amplifier\n\nmodule Board:\n{}",
+                body
+            ),
+            board_path.clone(),
+        ))
+    };
+
+    let mut vfs = MemoryVfs::new();
+    vfs.insert(
+        "/project/ato.yaml",
+        "builds:\n  main:\n    entry: board.ato:Board\n",
+    );
+    vfs.insert(
+        &parts_path,
+        "component Resistor:\n    signal p1 ~ pin 1\n\nmodule Amplifier:\n    r = new Resistor\n",
+    );
+    let mut analyzer = AtopileAnalyzer::with_vfs(vfs);
+
+    // Opening a file of a project with builds evaluates them, so unused modules are reported.
+    analyzer
+        .set_source(&board_path, board("    pass\n"))
+        .unwrap();
+    let unused = |analyzer: &mut AtopileAnalyzer<MemoryVfs>| -> Vec<String> {
+        analyzer
+            .diagnostics()
+            .unwrap()
+            .iter()
+            .filter(|diag| diag.code().code == "E0205")
+            .map(|diag| diag.file.display().to_string())
+            .collect()
+    };
+    assert_eq!(unused(&mut analyzer), vec!["/project/parts.ato"]);
+
+    // Using a module from a file that isn't re-evaluated elaborates it.
+    analyzer
+        .set_source(&board_path, board("    amp = new Amplifier\n"))
+        .unwrap();
+    assert!(unused(&mut analyzer).is_empty());
+    let builds = analyzer.get_builds();
+    let state = serde_json::to_value(&builds[0].state).unwrap();
+    assert!(state["instances"]
+        .as_object()
+        .unwrap()
+        .contains_key("/project/board.ato:Board.amp.r"));
+}
+
+#[test]
+fn analyzer_reloads_project_config() {
+    let temp = assert_fs::TempDir::new().unwrap();
+    let root = temp.path().canonicalize().unwrap();
+    let config_path = root.join("ato.yaml");
+    let board_path = root.join("board.ato");
+    let board = "module Board:\n    signal vin\n\nmodule Spare:\n    signal vout\n";
+    std::fs::write(&config_path, "").unwrap();
+    std::fs::write(&board_path, board).unwrap();
+
+    let mut analyzer = AtopileAnalyzer::new();
+    analyzer
+        .set_source(
+            &board_path,
+            Arc::new(AtopileSource::new(board.to_string(), board_path.clone())),
+        )
+        .unwrap();
+    let build_names = |analyzer: &mut AtopileAnalyzer| -> Vec<String> {
+        analyzer
+            .get_builds()
+            .into_iter()
+            .map(|build| build.name)
+            .collect()
+    };
+    assert_eq!(build_names(&mut analyzer), vec!["Board", "Spare"]);
+
+    // Declaring a build evaluates only it.
+    std::fs::write(
+        &config_path,
+        "builds:\n  main:\n    entry: board.ato:Board\n",
+    )
+    .unwrap();
+    analyzer.reload_config(&config_path).unwrap();
+    assert_eq!(build_names(&mut analyzer), vec!["main"]);
+    assert!(analyzer
+        .diagnostics()
+        .unwrap()
+        .iter()
+        .any(|diag| diag.code().code == "E0205"));
+
+    // Removing it again builds every root module.
+    std::fs::write(&config_path, "").unwrap();
+    analyzer.reload_config(&config_path).unwrap();
+    assert_eq!(build_names(&mut analyzer), vec!["Board", "Spare"]);
+    assert!(!analyzer
+        .diagnostics()
+        .unwrap()
+        .iter()
+        .any(|diag| diag.code().code == "E0205"));
+}
@@ -0,0 +1,61 @@
+use atopile_analyzer::units::{parse_unit, Dimension, Quantity, UnitError};
+
+#[test]
+fn parse_units_with_prefixes() {
+    assert_eq!(parse_unit("V"), Ok((1.0, Dimension::VOLTAGE)));
+    assert_eq!(parse_unit("kohm"), Ok((1e3, Dimension::RESISTANCE)));
+    assert_eq!(parse_unit("mA"), Ok((1e-3, Dimension::CURRENT)));
+    assert_eq!(parse_unit("m"), Ok((1.0, Dimension::LENGTH)));
+    assert_eq!(parse_unit("mm"), Ok((1e-3, Dimension::LENGTH)));
+    assert_eq!(
+        parse_unit("parsec"),
+        Err(UnitError::UnknownUnit("parsec".to_string()))
+    );
+}
+
+#[test]
+fn dimensions_compose() {
+    assert_eq!(
+        Dimension::VOLTAGE.checked_div(Dimension::CURRENT),
+        Some(Dimension::RESISTANCE)
+    );
+    assert_eq!(
+        Dimension::VOLTAGE.checked_mul(Dimension::CURRENT),
+        Some(Dimension::POWER)
+    );
+    assert_eq!(
+        Dimension::from_name("capacitance"),
+        Some(Dimension::CAPACITANCE)
+    );
+    assert_eq!(
+        Dimension::LENGTH
+            .checked_mul(Dimension::TIME)
+            .unwrap()
+            .to_string(),
+        "m·s"
+    );
+
+    // Exponents that don't fit are an error rather than an overflow.
+    assert_eq!(Dimension::VOLTAGE.checked_powi(50), None);
+    assert_eq!(
+        Dimension::MASS.checked_powi(-128).unwrap().checked_powi(-1),
+        None
+    );
+}
+
+#[test]
+fn format_quantities() {
+    let r = Quantity::new(4700.0, Dimension::RESISTANCE);
+    assert_eq!(r.to_string(), "4.7kohm");
+    assert_eq!(r.with_bounds(4465.0, 4935.0).to_string(), "4.7kohm ± 5%");
+    assert_eq!(
+        Quantity::new(2.7, Dimension::VOLTAGE)
+            .with_bounds(1.8, 3.6)
+            .to_string(),
+        "1.8V to 3.6V"
+    );
+    assert_eq!(
+        Quantity::new(100e-9, Dimension::CAPACITANCE).to_string(),
+        "100nF"
+    );
+}
@@ -1,13 +1,13 @@
 use std::collections::{HashMap, HashSet};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::Instant;
 
 use anyhow::Context;
 use atopile_analyzer::diagnostics::{
-    AnalyzerDiagnostic, AnalyzerDiagnosticKind, AnalyzerDiagnosticSeverity,
+    AnalyzerDiagnostic, AnalyzerDiagnosticKind, AnalyzerDiagnosticSeverity, Fix,
 };
-use atopile_analyzer::AtopileAnalyzer;
+use atopile_analyzer::{AtopileAnalyzer, Completion, CompletionKind};
 use atopile_parser::AtopileSource;
 use log::{info, Level, LevelFilter, Log, Metadata, Record};
 use serde::{Deserialize, Serialize};
@@ -31,6 +31,15 @@ impl Notification for NetlistUpdatedNotification {
     type Params = NetlistUpdatedNotification;
 }
 
+/// Parameters of `atopile/writeNetlist`: the module `module` declared in `uri` is written as a
+/// KiCad netlist to `out`.
+#[derive(Serialize, Deserialize)]
+struct WriteNetlistParams {
+    uri: Url,
+    module: String,
+    out: Url,
+}
+
 struct Backend {
     client: Client,
     analyzer: Mutex<AtopileAnalyzer>,
@@ -69,24 +78,39 @@ impl Log for LspLogger {
     fn flush(&self) {}
 }
 
-fn position_to_lsp(pos: atopile_parser::Position) -> Position {
+// The analyzer's columns are byte offsets, and the editor's count UTF-16 code units, so
+// positions are converted against the text of the file they are in.
+
+fn position_to_lsp(
+    analyzer: &AtopileAnalyzer,
+    path: &Path,
+    pos: atopile_parser::Position,
+) -> Position {
+    let pos = analyzer.position_to_utf16(path, pos);
     Position {
         line: pos.line as u32,
         character: pos.column as u32,
     }
 }
 
-fn position_from_lsp(pos: Position) -> atopile_parser::Position {
-    atopile_parser::Position {
-        line: pos.line as usize,
-        column: pos.character as usize,
-    }
+fn position_from_lsp(
+    analyzer: &AtopileAnalyzer,
+    path: &Path,
+    pos: Position,
+) -> atopile_parser::Position {
+    analyzer.position_from_utf16(
+        path,
+        atopile_parser::Position {
+            line: pos.line as usize,
+            column: pos.character as usize,
+        },
+    )
 }
 
-fn range_to_lsp(range: atopile_analyzer::Range) -> Range {
+fn range_to_lsp(analyzer: &AtopileAnalyzer, path: &Path, range: atopile_analyzer::Range) -> Range {
     Range {
-        start: position_to_lsp(range.start),
-        end: position_to_lsp(range.end),
+        start: position_to_lsp(analyzer, path, range.start),
+        end: position_to_lsp(analyzer, path, range.end),
     }
 }
 
@@ -97,10 +121,56 @@ fn diagnostic_severity_to_lsp(severity: AnalyzerDiagnosticSeverity) -> Diagnosti
     }
 }
 
-fn diagnostic_to_lsp(diag: &AnalyzerDiagnostic) -> Diagnostic {
+/// The path a document is analyzed under. Documents that aren't files, e.g. `untitled:` ones,
+/// only exist in memory and are keyed by their URI instead.
+fn uri_to_path(uri: &Url) -> PathBuf {
+    uri.to_file_path()
+        .unwrap_or_else(|_| PathBuf::from(uri.as_str()))
+}
+
+fn path_to_uri(path: &Path) -> Url {
+    Url::from_file_path(path).unwrap_or_else(|_| {
+        Url::parse(&path.to_string_lossy()).expect("Failed to convert path to URI")
+    })
+}
+
+fn location_to_lsp(analyzer: &AtopileAnalyzer, location: &atopile_analyzer::Location) -> Location {
+    Location {
+        uri: path_to_uri(&location.file),
+        range: range_to_lsp(analyzer, &location.file, location.range),
+    }
+}
+
+fn ranges_overlap(a: Range, b: Range) -> bool {
+    a.start <= b.end && b.start <= a.end
+}
+
+fn diagnostic_to_lsp(analyzer: &AtopileAnalyzer, diag: &AnalyzerDiagnostic) -> Diagnostic {
+    let related_information: Vec<_> = diag
+        .related()
+        .iter()
+        .map(|related| DiagnosticRelatedInformation {
+            location: location_to_lsp(analyzer, &related.location),
+            message: related.message.clone(),
+        })
+        .collect();
+
+    Diagnostic {
+        code: Some(NumberOrString::String(diag.code().code.to_string())),
+        source: Some("atopile".to_string()),
+        related_information: (!related_information.is_empty()).then_some(related_information),
+        ..diagnostic_message_to_lsp(analyzer, diag)
+    }
+}
+
+fn diagnostic_message_to_lsp(analyzer: &AtopileAnalyzer, diag: &AnalyzerDiagnostic) -> Diagnostic {
     match &diag.kind {
         AnalyzerDiagnosticKind::UnconnectedInterface(unconnected_diag) => Diagnostic {
-            range: range_to_lsp(unconnected_diag.instantiation_location.range),
+            range: range_to_lsp(
+                analyzer,
+                &unconnected_diag.instantiation_location.file,
+                unconnected_diag.instantiation_location.range,
+            ),
             severity: Some(diagnostic_severity_to_lsp(diag.severity)),
             message: format!(
                 "{} defines interface {}, which isn't connected in this module",
@@ -109,11 +179,87 @@ fn diagnostic_to_lsp(diag: &AnalyzerDiagnostic) -> Diagnostic {
             ..Default::default()
         },
         AnalyzerDiagnosticKind::Evaluator(evaluator_diag) => Diagnostic {
-            range: range_to_lsp(evaluator_diag.location.range),
+            range: range_to_lsp(
+                analyzer,
+                &evaluator_diag.location.file,
+                evaluator_diag.location.range,
+            ),
             severity: Some(diagnostic_severity_to_lsp(diag.severity)),
             message: evaluator_diag.to_string(),
             ..Default::default()
         },
+        AnalyzerDiagnosticKind::Syntax(syntax_diag) => Diagnostic {
+            range: range_to_lsp(
+                analyzer,
+                &syntax_diag.location.file,
+                syntax_diag.location.range,
+            ),
+            severity: Some(diagnostic_severity_to_lsp(diag.severity)),
+            message: syntax_diag.error.to_string(),
+            ..Default::default()
+        },
+    }
+}
+
+fn edits_to_lsp(
+    analyzer: &AtopileAnalyzer,
+    edits: &[atopile_analyzer::diagnostics::TextEdit],
+) -> WorkspaceEdit {
+    let mut changes: HashMap<Url, Vec<TextEdit>> = HashMap::new();
+    for edit in edits {
+        changes
+            .entry(path_to_uri(&edit.location.file))
+            .or_default()
+            .push(TextEdit {
+                range: range_to_lsp(analyzer, &edit.location.file, edit.location.range),
+                new_text: edit.new_text.clone(),
+            });
+    }
+
+    WorkspaceEdit {
+        changes: Some(changes),
+        ..Default::default()
+    }
+}
+
+fn completion_to_lsp(
+    analyzer: &AtopileAnalyzer,
+    path: &Path,
+    completion: Completion,
+) -> CompletionItem {
+    let kind = match completion.kind {
+        CompletionKind::Module => CompletionItemKind::MODULE,
+        CompletionKind::Component => CompletionItemKind::CLASS,
+        CompletionKind::Interface => CompletionItemKind::INTERFACE,
+        CompletionKind::File => CompletionItemKind::FILE,
+        CompletionKind::Instance => CompletionItemKind::VARIABLE,
+        CompletionKind::Signal | CompletionKind::Pin => CompletionItemKind::FIELD,
+        CompletionKind::Attribute => CompletionItemKind::PROPERTY,
+        CompletionKind::Unit => CompletionItemKind::UNIT,
+    };
+
+    CompletionItem {
+        text_edit: completion.range.map(|range| {
+            CompletionTextEdit::Edit(TextEdit {
+                range: range_to_lsp(analyzer, path, range),
+                new_text: completion.label.clone(),
+            })
+        }),
+        label: completion.label,
+        kind: Some(kind),
+        detail: completion.detail,
+        ..Default::default()
+    }
+}
+
+fn fix_to_lsp(analyzer: &AtopileAnalyzer, fix: &Fix, diagnostic: &Diagnostic) -> CodeAction {
+    CodeAction {
+        title: fix.title.clone(),
+        kind: Some(CodeActionKind::QUICKFIX),
+        diagnostics: Some(vec![diagnostic.clone()]),
+        edit: Some(edits_to_lsp(analyzer, &fix.edits)),
+        is_preferred: Some(true),
+        ..Default::default()
     }
 }
 
@@ -153,9 +299,7 @@ impl Backend {
         let update_start = Instant::now();
         info!("[update_source] starting for {}", uri);
 
-        let path = uri
-            .to_file_path()
-            .expect("Failed to convert URI to file path");
+        let path = uri_to_path(uri);
 
         let parsing_start = Instant::now();
         let source = Arc::new(AtopileSource::new(text.to_string(), path.clone()));
@@ -180,6 +324,31 @@ impl Backend {
             analyzer_start.elapsed().as_millis()
         );
 
+        self.publish_analysis(&mut analyzer, &path).await?;
+
+        info!(
+            "[profile] update_source total time: {}ms",
+            update_start.elapsed().as_millis()
+        );
+        Ok(())
+    }
+
+    /// Reload the `ato.yaml` at `uri` after it has been created, changed or deleted.
+    async fn reload_config(&self, uri: &Url) -> anyhow::Result<()> {
+        info!("[reload_config] reloading {}", uri);
+
+        let path = uri_to_path(uri);
+        let mut analyzer = self.analyzer.lock().await;
+        analyzer.reload_config(&path)?;
+        self.publish_analysis(&mut analyzer, &path).await
+    }
+
+    /// Push the netlist and the diagnostics of all files after `path` has changed.
+    async fn publish_analysis(
+        &self,
+        analyzer: &mut AtopileAnalyzer,
+        path: &Path,
+    ) -> anyhow::Result<()> {
         // Generate and push the netlist
         let netlist = analyzer.get_netlist();
 
@@ -209,8 +378,10 @@ impl Backend {
                     });
 
                 for (file, diagnostics) in &diagnostics_per_file {
-                    let lsp_diagnostics =
-                        diagnostics.iter().map(|d| diagnostic_to_lsp(d)).collect();
+                    let lsp_diagnostics = diagnostics
+                        .iter()
+                        .map(|d| diagnostic_to_lsp(analyzer, d))
+                        .collect();
 
                     info!(
                         "publishing diagnostics for file {:?}: {:?}",
@@ -218,11 +389,7 @@ impl Backend {
                     );
 
                     self.client
-                        .publish_diagnostics(
-                            Url::from_file_path(file).expect("Failed to convert file path to URI"),
-                            lsp_diagnostics,
-                            None,
-                        )
+                        .publish_diagnostics(path_to_uri(file), lsp_diagnostics, None)
                         .await;
                 }
 
@@ -234,11 +401,7 @@ impl Backend {
                     .difference(&files_with_diagnostics)
                 {
                     self.client
-                        .publish_diagnostics(
-                            Url::from_file_path(file).expect("Failed to convert file path to URI"),
-                            vec![],
-                            None,
-                        )
+                        .publish_diagnostics(path_to_uri(file), vec![], None)
                         .await;
                 }
 
@@ -258,10 +421,6 @@ impl Backend {
             }
         }
 
-        info!(
-            "[profile] update_source total time: {}ms",
-            update_start.elapsed().as_millis()
-        );
         Ok(())
     }
 
@@ -272,10 +431,28 @@ impl Backend {
         let netlist_json = serde_json::to_value(netlist)
             .map_err(|_| tower_lsp::jsonrpc::Error::internal_error())?;
 
-        info!("netlist: {}", netlist_json.to_string());
+        info!("netlist: {}", netlist_json);
 
         Ok(netlist_json)
     }
+
+    async fn get_builds(&self) -> Result<Value> {
+        let mut analyzer = self.analyzer.lock().await;
+        let builds = analyzer.get_builds();
+
+        serde_json::to_value(builds).map_err(|_| tower_lsp::jsonrpc::Error::internal_error())
+    }
+
+    async fn write_netlist(&self, params: WriteNetlistParams) -> Result<()> {
+        let mut analyzer = self.analyzer.lock().await;
+        let out = uri_to_path(&params.out);
+        analyzer
+            .write_netlist(&uri_to_path(&params.uri), &params.module, &out)
+            .map_err(|e| tower_lsp::jsonrpc::Error::invalid_params(e.to_string()))?;
+
+        info!("wrote netlist of {} to {}", params.module, out.display());
+        Ok(())
+    }
 }
 
 #[tower_lsp::async_trait]
@@ -287,6 +464,22 @@ impl LanguageServer for Backend {
                     TextDocumentSyncKind::FULL,
                 )),
                 definition_provider: Some(OneOf::Left(true)),
+                hover_provider: Some(HoverProviderCapability::Simple(true)),
+                completion_provider: Some(CompletionOptions {
+                    trigger_characters: Some(vec![".".into(), "\"".into(), "/".into()]),
+                    ..Default::default()
+                }),
+                references_provider: Some(OneOf::Left(true)),
+                rename_provider: Some(OneOf::Right(RenameOptions {
+                    prepare_provider: Some(true),
+                    work_done_progress_options: Default::default(),
+                })),
+                code_action_provider: Some(CodeActionProviderCapability::Options(
+                    CodeActionOptions {
+                        code_action_kinds: Some(vec![CodeActionKind::QUICKFIX]),
+                        ..Default::default()
+                    },
+                )),
                 ..Default::default()
             },
             server_info: Some(ServerInfo {
@@ -298,16 +491,33 @@ impl LanguageServer for Backend {
 
     async fn initialized(&self, _: InitializedParams) {
         info!("server initialized!");
+
+        // Builds and dependencies are declared in `ato.yaml`, which isn't opened as a document.
+        let watcher = DidChangeWatchedFilesRegistrationOptions {
+            watchers: vec![FileSystemWatcher {
+                glob_pattern: GlobPattern::String("**/ato.yaml".to_string()),
+                kind: None,
+            }],
+        };
+        let registration = Registration {
+            id: "atopile/watchConfig".to_string(),
+            method: "workspace/didChangeWatchedFiles".to_string(),
+            register_options: Some(serde_json::to_value(watcher).unwrap()),
+        };
+        if let Err(e) = self.client.register_capability(vec![registration]).await {
+            self.client
+                .log_message(
+                    MessageType::ERROR,
+                    format!("Failed to watch ato.yaml files: {:?}", e),
+                )
+                .await;
+        }
     }
 
     async fn did_open(&self, params: DidOpenTextDocumentParams) {
         info!("did_open");
 
-        let path = params
-            .text_document
-            .uri
-            .to_file_path()
-            .expect("Failed to convert URI to file path");
+        let path = uri_to_path(&params.text_document.uri);
 
         {
             let mut analyzer = self.analyzer.lock().await;
@@ -365,11 +575,7 @@ impl LanguageServer for Backend {
     async fn did_close(&self, params: DidCloseTextDocumentParams) {
         info!("did_close");
 
-        let path = params
-            .text_document
-            .uri
-            .to_file_path()
-            .expect("Failed to convert URI to file path");
+        let path = uri_to_path(&params.text_document.uri);
 
         let mut analyzer = self.analyzer.lock().await;
 
@@ -396,6 +602,16 @@ impl LanguageServer for Backend {
             .await;
     }
 
+    async fn did_change_watched_files(&self, params: DidChangeWatchedFilesParams) {
+        for change in params.changes {
+            if let Err(e) = self.reload_config(&change.uri).await {
+                self.client
+                    .log_message(MessageType::ERROR, format!("{:?}", e))
+                    .await;
+            }
+        }
+    }
+
     async fn goto_definition(
         &self,
         params: GotoDefinitionParams,
@@ -403,29 +619,153 @@ impl LanguageServer for Backend {
         info!("goto_definition: {:?}", params);
 
         let analyzer = self.analyzer.lock().await;
+        let path = uri_to_path(&params.text_document_position_params.text_document.uri);
         let result = analyzer
             .goto_definition(
-                &params
-                    .text_document_position_params
-                    .text_document
-                    .uri
-                    .to_file_path()
-                    .expect("Failed to convert URI to file path"),
-                position_from_lsp(params.text_document_position_params.position),
+                &path,
+                position_from_lsp(
+                    &analyzer,
+                    &path,
+                    params.text_document_position_params.position,
+                ),
             )
             .map_err(|_e| tower_lsp::jsonrpc::Error::invalid_request())?;
 
         Ok(result.map(|r| {
             GotoDefinitionResponse::Link(vec![LocationLink {
-                origin_selection_range: Some(range_to_lsp(r.source_range)),
-                target_uri: Url::from_file_path(&r.file)
-                    .expect("Failed to convert file path to URI"),
-                target_range: range_to_lsp(r.target_range),
-                target_selection_range: range_to_lsp(r.target_selection_range),
+                origin_selection_range: Some(range_to_lsp(&analyzer, &path, r.source_range)),
+                target_uri: path_to_uri(&r.file),
+                target_range: range_to_lsp(&analyzer, &r.file, r.target_range),
+                target_selection_range: range_to_lsp(&analyzer, &r.file, r.target_selection_range),
             }])
         }))
     }
 
+    async fn hover(&self, params: HoverParams) -> Result<Option<Hover>> {
+        info!("hover: {:?}", params);
+
+        let analyzer = self.analyzer.lock().await;
+        let path = uri_to_path(&params.text_document_position_params.text_document.uri);
+        let result = analyzer
+            .hover(
+                &path,
+                position_from_lsp(
+                    &analyzer,
+                    &path,
+                    params.text_document_position_params.position,
+                ),
+            )
+            .map_err(|_e| tower_lsp::jsonrpc::Error::invalid_request())?;
+
+        Ok(result.map(|hover| Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value: hover.contents,
+            }),
+            range: Some(range_to_lsp(&analyzer, &path, hover.range)),
+        }))
+    }
+
+    async fn completion(&self, params: CompletionParams) -> Result<Option<CompletionResponse>> {
+        info!("completion: {:?}", params);
+
+        let analyzer = self.analyzer.lock().await;
+        let path = uri_to_path(&params.text_document_position.text_document.uri);
+        let completions = analyzer
+            .completions(
+                &path,
+                position_from_lsp(&analyzer, &path, params.text_document_position.position),
+            )
+            .map_err(|_e| tower_lsp::jsonrpc::Error::invalid_request())?;
+
+        Ok(Some(CompletionResponse::Array(
+            completions
+                .into_iter()
+                .map(|completion| completion_to_lsp(&analyzer, &path, completion))
+                .collect(),
+        )))
+    }
+
+    async fn references(&self, params: ReferenceParams) -> Result<Option<Vec<Location>>> {
+        info!("references: {:?}", params);
+
+        let analyzer = self.analyzer.lock().await;
+        let path = uri_to_path(&params.text_document_position.text_document.uri);
+        let locations = analyzer
+            .find_references(
+                &path,
+                position_from_lsp(&analyzer, &path, params.text_document_position.position),
+                params.context.include_declaration,
+            )
+            .map_err(|_e| tower_lsp::jsonrpc::Error::invalid_request())?;
+
+        Ok(Some(
+            locations
+                .iter()
+                .map(|location| location_to_lsp(&analyzer, location))
+                .collect(),
+        ))
+    }
+
+    async fn prepare_rename(
+        &self,
+        params: TextDocumentPositionParams,
+    ) -> Result<Option<PrepareRenameResponse>> {
+        info!("prepare_rename: {:?}", params);
+
+        let analyzer = self.analyzer.lock().await;
+        let path = uri_to_path(&params.text_document.uri);
+        let range = analyzer
+            .prepare_rename(&path, position_from_lsp(&analyzer, &path, params.position))
+            .map_err(|_e| tower_lsp::jsonrpc::Error::invalid_request())?;
+
+        Ok(range.map(|range| PrepareRenameResponse::Range(range_to_lsp(&analyzer, &path, range))))
+    }
+
+    async fn rename(&self, params: RenameParams) -> Result<Option<WorkspaceEdit>> {
+        info!("rename: {:?}", params);
+
+        let analyzer = self.analyzer.lock().await;
+        let path = uri_to_path(&params.text_document_position.text_document.uri);
+        let edits = analyzer
+            .rename(
+                &path,
+                position_from_lsp(&analyzer, &path, params.text_document_position.position),
+                &params.new_name,
+            )
+            .map_err(|e| tower_lsp::jsonrpc::Error::invalid_params(e.to_string()))?;
+
+        Ok((!edits.is_empty()).then(|| edits_to_lsp(&analyzer, &edits)))
+    }
+
+    async fn code_action(&self, params: CodeActionParams) -> Result<Option<CodeActionResponse>> {
+        info!("code_action: {:?}", params);
+
+        let path = uri_to_path(&params.text_document.uri);
+
+        let mut analyzer = self.analyzer.lock().await;
+        let diagnostics = analyzer
+            .diagnostics()
+            .map_err(|_e| tower_lsp::jsonrpc::Error::internal_error())?;
+
+        let actions: Vec<_> = diagnostics
+            .iter()
+            .filter(|d| d.file == path && !d.fixes().is_empty())
+            .map(|d| (d, diagnostic_to_lsp(&analyzer, d)))
+            .filter(|(_, diagnostic)| ranges_overlap(diagnostic.range, params.range))
+            .flat_map(|(d, diagnostic)| {
+                d.fixes()
+                    .iter()
+                    .map(|fix| {
+                        CodeActionOrCommand::CodeAction(fix_to_lsp(&analyzer, fix, &diagnostic))
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+
+        Ok(Some(actions))
+    }
+
     async fn shutdown(&self) -> Result<()> {
         Ok(())
     }
@@ -438,6 +778,8 @@ async fn main() {
 
     let (service, socket) = LspService::build(Backend::new)
         .custom_method("atopile/getNetlist", Backend::get_netlist)
+        .custom_method("atopile/getBuilds", Backend::get_builds)
+        .custom_method("atopile/writeNetlist", Backend::write_netlist)
         .finish();
 
     Server::new(stdin, stdout, socket).serve(service).await;
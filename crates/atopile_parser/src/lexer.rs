@@ -1,4 +1,5 @@
 use crate::Spanned;
+use chumsky::error::{LabelError, RichPattern, RichReason};
 use chumsky::prelude::*;
 use std::{fmt, marker::PhantomData};
 
@@ -33,6 +34,7 @@ pub enum Token<'src> {
     Percent,     // %
     Dot,         // .
     Star,        // *
+    DoubleStar,  // **
     Plus,        // +
     Minus,       // -
     Div,         // /
@@ -100,6 +102,7 @@ impl fmt::Display for Token<'_> {
             Token::Percent => write!(f, "%"),
             Token::Dot => write!(f, "."),
             Token::Star => write!(f, "*"),
+            Token::DoubleStar => write!(f, "**"),
             Token::Plus => write!(f, "+"),
             Token::Minus => write!(f, "-"),
             Token::Div => write!(f, "/"),
@@ -136,6 +139,38 @@ impl fmt::Display for Token<'_> {
 type LexerError<'src> = Rich<'src, char, SimpleSpan>;
 type LexerExtra<'src> = extra::Err<LexerError<'src>>;
 
+/// Move an error from lexing part of a line to its position in the whole input.
+fn offset_error<'src>(error: &LexerError<'src>, offset: usize) -> LexerError<'src> {
+    let span = SimpleSpan::from(error.span().start + offset..error.span().end + offset);
+    match error.reason() {
+        RichReason::ExpectedFound { expected, found } => LabelError::<
+            &'src str,
+            RichPattern<'src, char>,
+        >::expected_found(
+            expected.iter().cloned(), *found, span
+        ),
+        RichReason::Custom(message) => Rich::custom(span, message),
+    }
+}
+
+/// The keywords of the language, which can't be used as names.
+pub const KEYWORDS: &[&str] = &[
+    "component",
+    "module",
+    "interface",
+    "pin",
+    "signal",
+    "new",
+    "from",
+    "import",
+    "assert",
+    "to",
+    "within",
+    "pass",
+    "True",
+    "False",
+];
+
 pub struct Lexer<'src> {
     phantom: PhantomData<&'src ()>,
 }
@@ -185,6 +220,7 @@ impl<'src> Lexer<'src> {
             just("->").to(Token::Arrow),
             just("%").to(Token::Percent),
             just(".").to(Token::Dot),
+            just("**").to(Token::DoubleStar),
             just("*").to(Token::Star),
             just("+").to(Token::Plus),
             just("-").to(Token::Minus),
@@ -345,7 +381,11 @@ pub fn lex(input: &str) -> (Vec<Spanned<Token<'_>>>, Vec<LexerError<'_>>) {
                                 .repeated()
                                 .collect::<Vec<_>>()
                                 .parse(before_comment);
-                            errors.extend(result.errors().cloned());
+                            errors.extend(
+                                result
+                                    .errors()
+                                    .map(|e| offset_error(e, content_offset + line_pos)),
+                            );
 
                             if let Some(toks) = result.output() {
                                 for (tok, tok_span) in
@@ -410,7 +450,11 @@ pub fn lex(input: &str) -> (Vec<Spanned<Token<'_>>>, Vec<LexerError<'_>>) {
                             .repeated()
                             .collect::<Vec<_>>()
                             .parse(&trimmed_line[line_pos..]);
-                        errors.extend(result.errors().cloned());
+                        errors.extend(
+                            result
+                                .errors()
+                                .map(|e| offset_error(e, content_offset + line_pos)),
+                        );
 
                         if let Some(toks) = result.output() {
                             for (tok, tok_span) in toks.iter().map(|t| (t.0.clone(), t.1.clone())) {
@@ -5,11 +5,14 @@ use std::{
     path::{Path, PathBuf},
 };
 
-use chumsky::span::SimpleSpan;
+use chumsky::{
+    error::{RichPattern, RichReason},
+    span::SimpleSpan,
+};
 #[cfg(test)]
 use insta::assert_debug_snapshot;
 use lexer::lex;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 pub mod lexer;
 pub mod parser;
@@ -59,7 +62,7 @@ impl<T> Spanned<T> {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Position {
     pub line: usize,
     pub column: usize,
@@ -73,10 +76,13 @@ impl Display for Position {
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct AtopileErrorReport {
-    span: SimpleSpan,
-    reason: String,
-    expected: Vec<String>,
-    found: Option<String>,
+    /// The byte range in the source that the error covers.
+    pub span: Span,
+    pub reason: String,
+    /// Descriptions of the tokens or constructs that would have been accepted.
+    pub expected: Vec<String>,
+    /// The token that was found instead, or `None` at the end of input.
+    pub found: Option<String>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -85,17 +91,84 @@ pub enum AtopileError {
     Parser(AtopileErrorReport),
 }
 
+impl AtopileErrorReport {
+    pub(crate) fn new(span: SimpleSpan, reason: &str) -> Self {
+        Self {
+            span: span.into_range(),
+            reason: reason.to_string(),
+            expected: vec![],
+            found: None,
+        }
+    }
+
+    /// Take the expected and found tokens from the chumsky error that caused this one.
+    pub(crate) fn with_cause<T: Debug + Clone + Display>(
+        mut self,
+        cause: &chumsky::error::Rich<'_, T>,
+    ) -> Self {
+        self.expected = cause
+            .expected()
+            .filter_map(|pattern| match pattern {
+                RichPattern::Token(token) => Some(format!("`{}`", token.deref())),
+                RichPattern::Label(label) => Some(label.to_string()),
+                RichPattern::Identifier(identifier) => Some(format!("`{}`", identifier)),
+                RichPattern::Any => Some("any token".to_string()),
+                RichPattern::EndOfInput => Some("end of input".to_string()),
+                RichPattern::SomethingElse => None,
+            })
+            .collect();
+        self.found = cause.found().map(|token| format!("`{}`", token));
+        self
+    }
+}
+
+/// The most expected alternatives that are listed in an error message.
+const MAX_EXPECTED: usize = 6;
+
+impl Display for AtopileErrorReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.reason)?;
+        let found = self.found.as_deref().unwrap_or("end of input");
+        match self.expected.as_slice() {
+            [] => Ok(()),
+            // A long list of alternatives (e.g. from the lexer) doesn't help anyone.
+            expected if expected.len() > MAX_EXPECTED => write!(f, ": found {}", found),
+            [expected] => write!(f, ": expected {}, found {}", expected, found),
+            [rest @ .., last] => write!(
+                f,
+                ": expected {} or {}, found {}",
+                rest.join(", "),
+                last,
+                found
+            ),
+        }
+    }
+}
+
 impl<'src, T: Debug + Clone + Display> From<chumsky::error::Rich<'src, T>> for AtopileErrorReport {
     fn from(err: chumsky::error::Rich<'src, T>) -> Self {
-        Self {
-            span: *err.span(),
-            reason: format!("{:?}", err.reason()),
-            expected: err.expected().map(|e| e.to_string()).collect(),
-            found: err.found().cloned().map(|c| c.to_string()),
+        let reason = match err.reason() {
+            RichReason::Custom(message) => message.as_str(),
+            RichReason::ExpectedFound { .. } => "unexpected input",
+        };
+        Self::new(*err.span(), reason).with_cause(&err)
+    }
+}
+
+impl AtopileError {
+    pub fn report(&self) -> &AtopileErrorReport {
+        match self {
+            AtopileError::Lexer(report) | AtopileError::Parser(report) => report,
         }
     }
 }
 
+impl Display for AtopileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.report())
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct AtopileSource {
     raw: String,
@@ -181,12 +254,59 @@ impl AtopileSource {
         Position { line, column }
     }
 
+    /// The text of line `line` without its line ending, or nothing if there is no such line.
+    fn line(&self, line: usize) -> &str {
+        let Some(&start) = self.line_to_index.get(line) else {
+            return "";
+        };
+        let end = self
+            .line_to_index
+            .get(line + 1)
+            .copied()
+            .unwrap_or(self.raw.len());
+        self.raw[start..end].trim_end_matches(['\n', '\r'])
+    }
+
+    /// Convert a position whose column counts UTF-16 code units, as editors' positions do, to
+    /// one whose column is a byte offset, as everywhere else. Columns past the end of the line
+    /// are clamped to it.
+    pub fn position_from_utf16(&self, position: Position) -> Position {
+        let line = self.line(position.line);
+        let mut units = 0;
+        let column = line
+            .char_indices()
+            .find(|(_, c)| {
+                units += c.len_utf16();
+                units > position.column
+            })
+            .map_or(line.len(), |(i, _)| i);
+        Position {
+            line: position.line,
+            column,
+        }
+    }
+
+    /// Convert a position whose column is a byte offset to one whose column counts UTF-16 code
+    /// units, as editors' positions do.
+    pub fn position_to_utf16(&self, position: Position) -> Position {
+        let line = self.line(position.line);
+        let column = line
+            .char_indices()
+            .take_while(|(i, _)| *i < position.column)
+            .map(|(_, c)| c.len_utf16())
+            .sum();
+        Position {
+            line: position.line,
+            column,
+        }
+    }
+
     pub fn ast(&self) -> &Vec<Spanned<parser::Stmt>> {
         &self.ast
     }
 
     /// Traverses all statements in the AST, providing each statement along with its parent context
-    pub fn traverse_all_stmts(&self) -> StmtTraverser {
+    pub fn traverse_all_stmts(&self) -> StmtTraverser<'_> {
         StmtTraverser::new(&self.ast)
     }
 
@@ -194,6 +314,10 @@ impl AtopileSource {
         &self.path
     }
 
+    pub fn text(&self) -> &str {
+        &self.raw
+    }
+
     pub fn errors(&self) -> &Vec<AtopileError> {
         &self.errors
     }
@@ -363,3 +487,18 @@ module M:
 
     assert_debug_snapshot!(source.stmt_at(1000), @r###"None"###);
 }
+
+#[test]
+fn test_lexer_error_span() {
+    let source = AtopileSource::new(
+        "module M:\n    r1.value = 10kohm @ 5".to_string(),
+        PathBuf::from("test.ato"),
+    );
+
+    let [AtopileError::Lexer(report)] = source.errors().as_slice() else {
+        panic!("expected one lexer error, got {:?}", source.errors());
+    };
+    assert_eq!(&source.raw[report.span.clone()], "@");
+    assert_eq!(report.found.as_deref(), Some("`@`"));
+    assert_eq!(report.to_string(), "unexpected input: found `@`");
+}
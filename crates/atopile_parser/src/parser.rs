@@ -3,13 +3,13 @@ use std::marker::PhantomData;
 use std::ops::Deref;
 
 use chumsky::input::{Cursor, InputRef, MapExtra, ValueInput};
-use chumsky::pratt::{infix, left};
+use chumsky::pratt::{infix, left, right};
 use chumsky::prelude::*;
 use chumsky::Parser;
 use serde::{Deserialize, Serialize};
 
 use crate::lexer::Token;
-use crate::Spanned;
+use crate::{AtopileErrorReport, Spanned};
 
 #[derive(Clone, Debug, PartialEq, Hash, Eq, Serialize, Deserialize)]
 pub struct Symbol(String);
@@ -88,12 +88,26 @@ pub enum Stmt {
     Pass,
 
     // Parse Error
-    ParseError(String),
+    ParseError(AtopileErrorReport),
 }
 
 impl Stmt {
     pub fn spanned_error(msg: &str, span: SimpleSpan) -> Spanned<Self> {
-        (Self::ParseError(msg.to_string()), span).into()
+        (Self::ParseError(AtopileErrorReport::new(span, msg)), span).into()
+    }
+
+    /// A parse error caused by a failed attempt to parse a statement, keeping the tokens that
+    /// were expected and found.
+    pub fn spanned_error_with_cause<T: fmt::Debug + Clone + fmt::Display>(
+        msg: &str,
+        span: SimpleSpan,
+        cause: &Rich<'_, T>,
+    ) -> Spanned<Self> {
+        (
+            Self::ParseError(AtopileErrorReport::new(span, msg).with_cause(cause)),
+            span,
+        )
+            .into()
     }
 }
 
@@ -478,6 +492,11 @@ impl<'src, I: ValueInput<'src, Token = Token<'src>, Span = SimpleSpan>> AtopileP
             ));
 
             operand.pratt((
+                infix(
+                    right(7),
+                    op(Token::DoubleStar, BinaryOperator::Pow),
+                    pratt_infix,
+                ),
                 infix(left(6), op(Token::Star, BinaryOperator::Mul), pratt_infix),
                 infix(left(6), op(Token::Div, BinaryOperator::Div), pratt_infix),
                 infix(left(5), op(Token::Plus, BinaryOperator::Add), pratt_infix),
@@ -668,11 +687,13 @@ impl<'src, I: ValueInput<'src, Token = Token<'src>, Span = SimpleSpan>> AtopileP
 
                 if let Some((ref mut block, ref start_cursor)) = current_block {
                     // We are in a multi-line block, so let's try to parse a block statement.
-                    let result = inp.parse(Self::block_stmt());
-                    if let Ok(stmt) = result {
-                        block.body.push(stmt);
-                        continue;
-                    }
+                    let cause = match inp.parse(Self::block_stmt()) {
+                        Ok(stmt) => {
+                            block.body.push(stmt);
+                            continue;
+                        }
+                        Err(cause) => cause,
+                    };
 
                     // We can't parse a block statement, so let's see if we found a dedent.
                     inp.rewind(checkpoint.clone());
@@ -683,20 +704,24 @@ impl<'src, I: ValueInput<'src, Token = Token<'src>, Span = SimpleSpan>> AtopileP
                         continue;
                     }
 
-                    // If we can't find either, let's skip to the next line and report an error.
+                    // If we can't find either, let's skip to the next line and record the error
+                    // in the block it belongs to.
                     skip_statement(inp);
 
-                    ast.push(Stmt::spanned_error(
-                        "syntax error",
+                    block.body.push(Stmt::spanned_error_with_cause(
+                        "unexpected input",
                         inp.span_since(checkpoint.cursor()),
+                        &cause,
                     ));
                 } else {
                     // Try to parse a normal top statement.
-                    let result = inp.parse(Self::top_stmt());
-                    if let Ok(stmt) = result {
-                        ast.push(stmt);
-                        continue;
-                    }
+                    let cause = match inp.parse(Self::top_stmt()) {
+                        Ok(stmt) => {
+                            ast.push(stmt);
+                            continue;
+                        }
+                        Err(cause) => cause,
+                    };
 
                     // Not a normal top statement, so let's try to parse a block header.
                     inp.rewind(checkpoint.clone());
@@ -712,7 +737,7 @@ impl<'src, I: ValueInput<'src, Token = Token<'src>, Span = SimpleSpan>> AtopileP
                         if is_multiline {
                             if inp.peek() != Some(Token::Indent) {
                                 ast.push(Stmt::spanned_error(
-                                    "syntax error: expected indent after block header",
+                                    "expected indent after block header",
                                     inp.span_since(checkpoint.cursor()),
                                 ));
                             } else {
@@ -742,22 +767,23 @@ impl<'src, I: ValueInput<'src, Token = Token<'src>, Span = SimpleSpan>> AtopileP
                             let block_checkpoint = inp.save();
                             loop {
                                 let stmt_checkpoint = inp.save();
-                                let result = inp.parse(Self::block_stmt());
-                                if let Ok(stmt) = result {
-                                    block.body.push(stmt);
-                                } else {
-                                    inp.rewind(stmt_checkpoint.clone());
-                                    while !matches!(
-                                        inp.peek(),
-                                        None | Some(Token::Newline) | Some(Token::Semicolon)
-                                    ) {
-                                        inp.next();
+                                match inp.parse(Self::block_stmt()) {
+                                    Ok(stmt) => block.body.push(stmt),
+                                    Err(cause) => {
+                                        inp.rewind(stmt_checkpoint.clone());
+                                        while !matches!(
+                                            inp.peek(),
+                                            None | Some(Token::Newline) | Some(Token::Semicolon)
+                                        ) {
+                                            inp.next();
+                                        }
+
+                                        block.body.push(Stmt::spanned_error_with_cause(
+                                            "unexpected input",
+                                            inp.span_since(stmt_checkpoint.cursor()),
+                                            &cause,
+                                        ));
                                     }
-
-                                    ast.push(Stmt::spanned_error(
-                                        "syntax error",
-                                        inp.span_since(stmt_checkpoint.cursor()),
-                                    ));
                                 }
 
                                 if inp.peek() != Some(Token::Semicolon) {
@@ -782,9 +808,10 @@ impl<'src, I: ValueInput<'src, Token = Token<'src>, Span = SimpleSpan>> AtopileP
                     inp.rewind(checkpoint.clone());
                     skip_statement(inp);
 
-                    ast.push(Stmt::spanned_error(
-                        "syntax error: unexpected top-level statement",
+                    ast.push(Stmt::spanned_error_with_cause(
+                        "unexpected top-level statement",
                         inp.span_since(checkpoint.cursor()),
+                        &cause,
                     ));
                 }
             }
@@ -894,6 +921,8 @@ mod tests {
         "u1.a -> Resistor"
     );
 
+    test_parser!(test_pow_expr, AtopileParser::expr(), "2 * v ** 2");
+
     test_parser!(
         test_complex_expr,
         AtopileParser::expr(),
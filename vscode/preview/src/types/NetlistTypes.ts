@@ -11,12 +11,25 @@ export interface ModuleRef {
   module_name: string;
 }
 
+/**
+ * A range in a source file. Positions are zero-based.
+ */
+export interface SourceLocation {
+  file: string;
+  range: {
+    start: { line: number; column: number };
+    end: { line: number; column: number };
+  };
+}
+
 /**
  * Connection between two nodes
  */
 export interface NodeConnection {
   left: string;
   right: string;
+  location: SourceLocation; // The `~` statement that produced this connection
+  provenance?: SourceLocation[]; // Statements it was copied into place through, innermost first
 }
 
 /**
@@ -50,6 +63,9 @@ export interface Instance {
   children: Record<string, string>;
   connections: NodeConnection[];
   reference_designator?: string | null;
+  location?: SourceLocation; // The statement that created this instance
+  provenance?: SourceLocation[]; // Statements it was copied into place through, innermost first
+  assignments?: Record<string, SourceLocation>; // Where each attribute was last assigned
 }
 
 /**